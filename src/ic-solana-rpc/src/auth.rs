@@ -15,7 +15,7 @@ use crate::{
 pub enum Auth {
     Manage,
     RegisterProvider,
-    // FreeRpc,
+    FreeRpc,
 }
 
 impl Display for Auth {
@@ -26,7 +26,7 @@ impl Display for Auth {
             match self {
                 Auth::Manage => "manage",
                 Auth::RegisterProvider => "register_provider",
-                // Auth::FreeRpc => "FreeRpc",
+                Auth::FreeRpc => "free_rpc",
             }
         )
     }
@@ -0,0 +1,263 @@
+//! Cache of RPC responses that can no longer change, or change rarely.
+//!
+//! Entries expire after a TTL set per method. When the cache is full, the entries closest to
+//! expiry are evicted first. Responses are keyed by the providers that served them, and removed
+//! when the URL of one of them changes.
+
+use std::{borrow::Cow, future::Future};
+
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_solana::{
+    add_metric_entry,
+    metrics::MetricRpcMethod,
+    request::RpcRequest,
+    rpc_client::{RpcClient, RpcCluster, RpcError, RpcResult},
+    types::{Cluster, CommitmentLevel},
+};
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    constants::{CACHED_RESPONSE_COST, CACHE_MAX_ENTRIES, CACHE_MAX_KEY_SIZE, CACHE_MAX_VALUE_SIZE},
+    free_rpc::reserve_free_rpc_call,
+    providers::find_provider,
+    state::{mutate_state, read_state, State},
+};
+
+/// Identifies a response by the IDs of the providers the request is sent to, JSON encoded, its
+/// method and its parameters.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CacheKey(pub String);
+
+impl CacheKey {
+    /// Returns the cache key of a request sent with `client`, or `None` if its responses can't be
+    /// cached.
+    ///
+    /// Providers are resolved by the client whatever the source, so that responses of a cluster
+    /// or of the healthiest providers are removed along with those of the provider serving them.
+    /// Responses of custom providers are not cached, as they are not vetted by the canister, and
+    /// neither are responses of a local cluster, which can be reset at any time.
+    pub fn new<P: Serialize>(client: &RpcClient, method: RpcRequest, params: &P) -> Option<Self> {
+        let mut ids = client.provider_ids.values().collect::<Vec<_>>();
+        if ids.is_empty() || ids.iter().any(|id| is_localnet(id)) {
+            return None;
+        }
+        ids.sort();
+        let ids = serde_json::to_string(&ids).ok()?;
+        let params = serde_json::to_string(params).ok()?;
+        let key = format!("{}|{}|{}", ids, method, params);
+        (key.len() <= CACHE_MAX_KEY_SIZE as usize).then_some(Self(key))
+    }
+
+    /// Returns the IDs of the providers the request was sent to.
+    fn provider_ids(&self) -> Option<Vec<String>> {
+        serde_json::Deserializer::from_str(&self.0)
+            .into_iter::<Vec<String>>()
+            .next()?
+            .ok()
+    }
+}
+
+/// Returns whether `provider_id` is the local cluster, or a provider of one.
+fn is_localnet(provider_id: &str) -> bool {
+    provider_id == Cluster::Localnet.to_string()
+        || find_provider(provider_id).is_some_and(|provider| provider.cluster == Some(RpcCluster::Localnet))
+}
+
+impl Storable for CacheKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        self.0.to_bytes()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(String::from_bytes(bytes))
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: CACHE_MAX_KEY_SIZE,
+        is_fixed_size: false,
+    };
+}
+
+/// A Candid-encoded response.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub value: Vec<u8>,
+    pub expires_at: u64,
+}
+
+impl Storable for CacheEntry {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Orders cache keys by expiry time.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ExpiryKey {
+    pub expires_at: u64,
+    pub key: CacheKey,
+}
+
+impl Storable for ExpiryKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = self.expires_at.to_be_bytes().to_vec();
+        bytes.extend_from_slice(self.key.0.as_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let (expires_at, key) = bytes.split_at(8);
+        Self {
+            expires_at: u64::from_be_bytes(expires_at.try_into().unwrap()),
+            key: CacheKey(String::from_utf8(key.to_vec()).unwrap()),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 8 + CACHE_MAX_KEY_SIZE,
+        is_fixed_size: false,
+    };
+}
+
+/// Returns the cached response for `key`, if any and not expired.
+pub fn get_cached<T: CandidType + DeserializeOwned>(key: &CacheKey) -> Option<T> {
+    let entry = read_state(|s| s.response_cache.get(key))?;
+    if entry.expires_at <= ic_cdk::api::time() {
+        return None;
+    }
+    Decode!(&entry.value, T).ok()
+}
+
+/// Caches a response for `ttl` nanoseconds.
+pub fn insert_cached<T: CandidType>(key: CacheKey, value: &T, ttl: u64) {
+    let Ok(value) = Encode!(value) else {
+        return;
+    };
+    if value.len() > CACHE_MAX_VALUE_SIZE {
+        return;
+    }
+    let now = ic_cdk::api::time();
+    let expires_at = now.saturating_add(ttl);
+
+    mutate_state(|s| {
+        // Drop expired entries, then the ones closest to expiry until there is room
+        while let Some((expiry, ())) = s.cache_expiry.first_key_value() {
+            if expiry.expires_at > now && s.response_cache.len() < CACHE_MAX_ENTRIES {
+                break;
+            }
+            s.cache_expiry.remove(&expiry);
+            s.response_cache.remove(&expiry.key);
+        }
+
+        if let Some(previous) = s.response_cache.insert(key.clone(), CacheEntry { value, expires_at }) {
+            s.cache_expiry.remove(&ExpiryKey {
+                expires_at: previous.expires_at,
+                key: key.clone(),
+            });
+        }
+        s.cache_expiry.insert(ExpiryKey { expires_at, key }, ());
+    });
+}
+
+/// Removes the cached responses of requests sent to the provider `provider_id`, which no longer
+/// apply once the provider points to another endpoint.
+pub fn remove_provider_cached(s: &mut State, provider_id: &str) {
+    let keys = s
+        .response_cache
+        .iter()
+        .filter(|(key, _)| {
+            key.provider_ids()
+                .is_some_and(|ids| ids.iter().any(|id| id == provider_id))
+        })
+        .map(|(key, entry)| ExpiryKey {
+            expires_at: entry.expires_at,
+            key,
+        })
+        .collect::<Vec<_>>();
+    for expiry in keys {
+        s.response_cache.remove(&expiry.key);
+        s.cache_expiry.remove(&expiry);
+    }
+}
+
+/// Charges the caller for a response served from the cache. Principals with free access use a
+/// request of their quota instead.
+pub fn charge_cached_response() -> RpcResult<()> {
+    if read_state(|s| s.is_demo_active) || reserve_free_rpc_call(ic_cdk::caller()) {
+        return Ok(());
+    }
+    let cycles_available = ic_cdk::api::call::msg_cycles_available128();
+    if cycles_available < CACHED_RESPONSE_COST {
+        return Err(RpcError::Text(format!(
+            "Insufficient cycles: available {}, required {}.",
+            cycles_available, CACHED_RESPONSE_COST
+        )));
+    }
+    ic_cdk::api::call::msg_cycles_accept128(CACHED_RESPONSE_COST);
+    Ok(())
+}
+
+/// Returns whether data read at `commitment` is finalized. Nodes default to the finalized
+/// commitment.
+pub fn is_finalized(commitment: Option<CommitmentLevel>) -> bool {
+    commitment.is_none_or(|commitment| commitment == CommitmentLevel::Finalized)
+}
+
+/// Serves a request from the cache, or calls `fetch` and caches its response for `ttl`
+/// nanoseconds. Requests without a key are always fetched.
+pub async fn cached<T, F>(key: Option<CacheKey>, method: RpcRequest, ttl: u64, fetch: F) -> RpcResult<T>
+where
+    T: CandidType + DeserializeOwned,
+    F: Future<Output = RpcResult<T>>,
+{
+    cached_if(key, method, ttl, fetch, |_| true).await
+}
+
+/// Same as [cached], but only caches responses for which `is_cacheable` returns true.
+pub async fn cached_if<T, F>(
+    key: Option<CacheKey>,
+    method: RpcRequest,
+    ttl: u64,
+    fetch: F,
+    is_cacheable: impl FnOnce(&T) -> bool,
+) -> RpcResult<T>
+where
+    T: CandidType + DeserializeOwned,
+    F: Future<Output = RpcResult<T>>,
+{
+    let Some(key) = key else {
+        return fetch.await;
+    };
+    if let Some(value) = get_cached(&key) {
+        charge_cached_response()?;
+        add_metric_entry!(cache_hits, MetricRpcMethod::from(method), 1);
+        return Ok(value);
+    }
+    add_metric_entry!(cache_misses, MetricRpcMethod::from(method), 1);
+
+    let value = fetch.await?;
+    if is_cacheable(&value) {
+        insert_cached(key, &value, ttl);
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_provider_ids() {
+        let ids = vec!["mainnet".to_string(), r#"a"|b"#.to_string()];
+        let key = CacheKey(format!("{}|getGenesisHash|null", serde_json::to_string(&ids).unwrap()));
+        assert_eq!(key.provider_ids(), Some(ids));
+        assert_eq!(CacheKey("Mainnet|getGenesisHash|null".to_string()).provider_ids(), None);
+    }
+}
@@ -18,11 +18,46 @@ pub const COLLATERAL_CYCLES_PER_NODE: u128 = 10_000_000;
 // Minimum number of bytes charged for a URL; improves consistency of costs between providers
 pub const RPC_URL_COST_BYTES: u32 = 256;
 
-// pub const MINIMUM_WITHDRAWAL_CYCLES: u128 = 1_000_000_000;
+// Share of the canister overhead credited to the owner of the provider serving a request, in
+// percent
+pub const PROVIDER_REVENUE_SHARE_PERCENT: u128 = 50;
+
+pub const MINIMUM_WITHDRAWAL_CYCLES: u128 = 1_000_000_000;
+
+// Default daily quota of principals with free RPC access
+pub const FREE_RPC_REQUESTS_PER_DAY: u64 = 1_000;
+pub const FREE_RPC_BYTES_PER_DAY: u64 = 100 * 1024 * 1024;
 
 pub const NODES_IN_SUBNET: u32 = 34;
 
 pub const PROVIDER_ID_MAX_SIZE: u32 = 128;
 
-// List of hosts which are not allowed to be used as RPC providers
+pub const HOSTNAME_MAX_SIZE: u32 = 253;
+
+pub const RATE_LIMIT_METHOD_MAX_SIZE: u32 = 128;
+
+// Maximum number of tracked token buckets, the full ones are dropped first
+pub const RATE_LIMIT_MAX_BUCKETS: usize = 10_000;
+
+// Number of recent calls kept per provider to rank its health
+pub const PROVIDER_HEALTH_WINDOW: usize = 20;
+
+// Age in nanoseconds after which a call no longer counts towards the health of a provider, so that
+// providers recover from outages
+pub const PROVIDER_HEALTH_MAX_AGE: u64 = 60 * 60 * 1_000_000_000;
+
+// Bounds of the response cache. Responses larger than the maximum size are not cached
+pub const CACHE_MAX_ENTRIES: u64 = 500;
+pub const CACHE_MAX_KEY_SIZE: u32 = 1024;
+pub const CACHE_MAX_VALUE_SIZE: usize = 512 * 1024;
+
+// How long responses stay cached, in nanoseconds
+pub const CACHE_TTL_IMMUTABLE: u64 = 24 * 60 * 60 * 1_000_000_000;
+pub const CACHE_TTL_SLOW_CHANGING: u64 = 60 * 60 * 1_000_000_000;
+
+// Cycles charged for a response served from the cache, which makes no HTTP outcall
+pub const CACHED_RESPONSE_COST: u128 = CANISTER_OVERHEAD * NODES_IN_SUBNET as u128;
+
+// List of hosts which are never allowed to be used as RPC providers. Hosts can also be blocked at
+// runtime, see `hosts::is_host_allowed`
 pub const RPC_HOSTS_BLOCKLIST: &[&str] = &[];
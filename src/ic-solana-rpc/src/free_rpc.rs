@@ -0,0 +1,167 @@
+//! Free access to RPC methods for principals authorized with [Auth::FreeRpc].
+//!
+//! Free access is limited by daily quotas. Every call to a provider, including retries, and every
+//! response served from the cache counts as a request. Once a quota is exceeded, requests are
+//! charged as usual until the next day.
+
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_canister_log::log;
+use ic_solana::{add_metric_entry, logs::INFO, metrics::MetricPrincipal};
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::Serialize;
+
+use crate::{
+    auth::{is_authorized, Auth},
+    constants::{FREE_RPC_BYTES_PER_DAY, FREE_RPC_REQUESTS_PER_DAY},
+    state::{mutate_state, read_state},
+    types::PrincipalStorable,
+};
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct FreeRpcQuota {
+    pub requests_per_day: u64,
+    /// Response bytes per day.
+    pub bytes_per_day: u64,
+}
+
+impl Default for FreeRpcQuota {
+    fn default() -> Self {
+        Self {
+            requests_per_day: FREE_RPC_REQUESTS_PER_DAY,
+            bytes_per_day: FREE_RPC_BYTES_PER_DAY,
+        }
+    }
+}
+
+/// Quota of a principal and its usage during the current day.
+#[derive(Debug, Clone, Default, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct FreeRpcUsage {
+    pub quota: FreeRpcQuota,
+    /// Days since the Unix epoch.
+    pub day: u64,
+    pub requests: u64,
+    pub bytes: u64,
+}
+
+impl FreeRpcUsage {
+    /// Resets the usage counters when `day` has started.
+    fn on_day(mut self, day: u64) -> Self {
+        if self.day != day {
+            self.day = day;
+            self.requests = 0;
+            self.bytes = 0;
+        }
+        self
+    }
+
+    pub fn is_exceeded(&self) -> bool {
+        self.requests >= self.quota.requests_per_day || self.bytes >= self.quota.bytes_per_day
+    }
+}
+
+impl Storable for FreeRpcUsage {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 200,
+        is_fixed_size: false,
+    };
+}
+
+fn today() -> u64 {
+    ic_cdk::api::time() / NANOS_PER_DAY
+}
+
+/// Returns the usage of a principal authorized with [Auth::FreeRpc].
+pub fn get_free_rpc_usage(principal: Principal) -> Option<FreeRpcUsage> {
+    if !is_authorized(&principal, Auth::FreeRpc) {
+        return None;
+    }
+    let usage = read_state(|s| s.free_rpc_usage.get(&PrincipalStorable(principal)));
+    Some(usage.unwrap_or_default().on_day(today()))
+}
+
+/// Reserves a free call to a provider for `principal`, counting it against its quota.
+///
+/// Returns `false` if `principal` has no free access or its quota is exceeded, in which case the
+/// call is charged.
+pub fn reserve_free_rpc_call(principal: Principal) -> bool {
+    let Some(mut usage) = get_free_rpc_usage(principal) else {
+        return false;
+    };
+    let metric = MetricPrincipal(principal.to_string());
+    if usage.is_exceeded() {
+        add_metric_entry!(err_free_rpc_quota_exceeded, metric, 1);
+        return false;
+    }
+    usage.requests += 1;
+    mutate_state(|s| s.free_rpc_usage.insert(PrincipalStorable(principal), usage));
+    add_metric_entry!(free_rpc_requests, metric, 1);
+    true
+}
+
+/// Counts the response bytes of a free call against the quota of `principal`.
+pub fn record_free_rpc_bytes(principal: Principal, bytes: u64) {
+    let Some(mut usage) = get_free_rpc_usage(principal) else {
+        return;
+    };
+    usage.bytes = usage.bytes.saturating_add(bytes);
+    mutate_state(|s| s.free_rpc_usage.insert(PrincipalStorable(principal), usage));
+    add_metric_entry!(free_rpc_bytes, MetricPrincipal(principal.to_string()), bytes);
+}
+
+/// Sets the daily quota of a principal, keeping its usage.
+pub fn do_set_free_rpc_quota(caller: Principal, principal: Principal, quota: FreeRpcQuota) {
+    log!(
+        INFO,
+        "[{}] Setting free RPC quota of {}: {:?}",
+        caller,
+        principal,
+        quota
+    );
+    mutate_state(|s| {
+        let principal = PrincipalStorable(principal);
+        let mut usage = s.free_rpc_usage.get(&principal).unwrap_or_default();
+        usage.quota = quota;
+        s.free_rpc_usage.insert(principal, usage);
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_free_rpc_usage() {
+        let mut usage = FreeRpcUsage {
+            quota: FreeRpcQuota {
+                requests_per_day: 2,
+                bytes_per_day: 100,
+            },
+            day: 1,
+            requests: 1,
+            bytes: 50,
+        };
+        assert!(!usage.is_exceeded());
+
+        usage.requests = 2;
+        assert!(usage.is_exceeded());
+        usage.requests = 1;
+        usage.bytes = 100;
+        assert!(usage.is_exceeded());
+
+        assert_eq!(usage.clone().on_day(1), usage);
+        let usage = usage.on_day(2);
+        assert_eq!((usage.day, usage.requests, usage.bytes), (2, 0, 0));
+        assert!(!usage.is_exceeded());
+    }
+}
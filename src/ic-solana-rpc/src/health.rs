@@ -0,0 +1,216 @@
+use std::{borrow::Cow, cmp::Reverse};
+
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_solana::rpc_client::{RpcCluster, RpcError, RpcResult};
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::Serialize;
+
+use crate::{
+    constants::{PROVIDER_HEALTH_MAX_AGE, PROVIDER_HEALTH_WINDOW},
+    providers::ProviderId,
+    state::{mutate_state, read_state},
+};
+
+/// Outcome of a single call to a provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct ProviderCall {
+    pub success: bool,
+    /// Latency in nanoseconds.
+    pub latency: u64,
+    /// Time of the call in nanoseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// Call history of a provider.
+#[derive(Debug, Clone, Default, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct ProviderHealth {
+    pub successes: u64,
+    pub errors: u64,
+    /// The most recent calls, oldest first.
+    pub recent: Vec<ProviderCall>,
+}
+
+impl ProviderHealth {
+    pub fn record(&mut self, call: ProviderCall) {
+        if call.success {
+            self.successes += 1;
+        } else {
+            self.errors += 1;
+        }
+        if self.recent.len() >= PROVIDER_HEALTH_WINDOW {
+            self.recent.remove(0);
+        }
+        self.recent.push(call);
+    }
+
+    /// Recent calls made at most [PROVIDER_HEALTH_MAX_AGE] before `now`.
+    fn recent_calls(&self, now: u64) -> impl Iterator<Item = &ProviderCall> {
+        self.recent
+            .iter()
+            .filter(move |call| now.saturating_sub(call.timestamp) <= PROVIDER_HEALTH_MAX_AGE)
+    }
+
+    /// Share of recent calls that succeeded, in permille. A provider without recent calls is
+    /// considered healthy so that it gets a chance to be picked.
+    pub fn success_rate(&self, now: u64) -> u64 {
+        let (calls, successes) = self.recent_calls(now).fold((0, 0), |(calls, successes), call| {
+            (calls + 1, successes + call.success as u64)
+        });
+        if calls == 0 {
+            return 1000;
+        }
+        successes * 1000 / calls
+    }
+
+    /// Average latency of recent successful calls, in nanoseconds.
+    pub fn average_latency(&self, now: u64) -> Option<u64> {
+        let latencies = self
+            .recent_calls(now)
+            .filter(|call| call.success)
+            .map(|call| call.latency)
+            .collect::<Vec<_>>();
+        if latencies.is_empty() {
+            None
+        } else {
+            Some(latencies.iter().sum::<u64>() / latencies.len() as u64)
+        }
+    }
+
+    /// Sort key ordering healthier providers first.
+    fn rank(&self, now: u64) -> (Reverse<u64>, u64) {
+        (
+            Reverse(self.success_rate(now)),
+            self.average_latency(now).unwrap_or_default(),
+        )
+    }
+}
+
+impl Storable for ProviderHealth {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 1000,
+        is_fixed_size: false,
+    };
+}
+
+pub fn get_provider_health(id: &str) -> Option<ProviderHealth> {
+    read_state(|s| s.provider_health.get(&ProviderId::new(id)))
+}
+
+/// Records a call to the registered provider `provider_id`.
+pub fn record_provider_call(provider_id: &str, success: bool, latency: u64) {
+    let id = ProviderId::new(provider_id);
+    mutate_state(|s| {
+        if !s.rpc_providers.contains_key(&id) {
+            return;
+        }
+        let mut health = s.provider_health.get(&id).unwrap_or_default();
+        health.record(ProviderCall {
+            success,
+            latency,
+            timestamp: ic_cdk::api::time(),
+        });
+        s.provider_health.insert(id, health);
+    });
+}
+
+/// Returns the IDs of the `count` healthiest providers registered for `cluster`, healthiest first.
+pub fn healthiest_providers(cluster: RpcCluster, count: u8) -> RpcResult<Vec<String>> {
+    if count == 0 {
+        return Err(RpcError::Text("The number of providers must be positive".to_string()));
+    }
+    let now = ic_cdk::api::time();
+    read_state(|s| {
+        let mut providers = s
+            .rpc_providers
+            .iter()
+            .filter(|(_, provider)| provider.cluster == Some(cluster))
+            .map(|(id, _)| (s.provider_health.get(&id).unwrap_or_default().rank(now), id.0))
+            .collect::<Vec<_>>();
+        if providers.is_empty() {
+            return Err(RpcError::Text(format!(
+                "No providers registered for cluster {:?}",
+                cluster
+            )));
+        }
+        providers.sort_by_key(|(rank, _)| *rank);
+        Ok(providers.into_iter().take(count as usize).map(|(_, id)| id).collect())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn call(success: bool, latency: u64) -> ProviderCall {
+        call_at(success, latency, 0)
+    }
+
+    fn call_at(success: bool, latency: u64, timestamp: u64) -> ProviderCall {
+        ProviderCall {
+            success,
+            latency,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_provider_health() {
+        let mut health = ProviderHealth::default();
+        assert_eq!(health.success_rate(0), 1000);
+        assert_eq!(health.average_latency(0), None);
+
+        health.record(call(true, 100));
+        health.record(call(false, 900));
+        health.record(call(true, 300));
+        health.record(call(true, 200));
+        assert_eq!((health.successes, health.errors), (3, 1));
+        assert_eq!(health.success_rate(0), 750);
+        assert_eq!(health.average_latency(0), Some(200));
+
+        for _ in 0..PROVIDER_HEALTH_WINDOW {
+            health.record(call(true, 100));
+        }
+        assert_eq!(health.recent.len(), PROVIDER_HEALTH_WINDOW);
+        assert_eq!(health.success_rate(0), 1000);
+        assert_eq!(health.successes + health.errors, 4 + PROVIDER_HEALTH_WINDOW as u64);
+    }
+
+    #[test]
+    fn test_rank() {
+        let mut fast = ProviderHealth::default();
+        fast.record(call(true, 100));
+        let mut slow = ProviderHealth::default();
+        slow.record(call(true, 500));
+        let mut failing = ProviderHealth::default();
+        failing.record(call(false, 50));
+
+        let mut ranked = [&failing, &slow, &ProviderHealth::default(), &fast];
+        ranked.sort_by_key(|health| health.rank(0));
+        assert_eq!(ranked, [&ProviderHealth::default(), &fast, &slow, &failing]);
+    }
+
+    #[test]
+    fn should_ignore_old_calls() {
+        let mut health = ProviderHealth::default();
+        health.record(call_at(false, 100, 0));
+        health.record(call_at(true, 300, PROVIDER_HEALTH_MAX_AGE));
+        assert_eq!(health.success_rate(PROVIDER_HEALTH_MAX_AGE), 500);
+
+        let now = PROVIDER_HEALTH_MAX_AGE + 1;
+        assert_eq!(health.success_rate(now), 1000);
+        assert_eq!(health.average_latency(now), Some(300));
+
+        // A provider recovers from an outage once its failed calls are old enough
+        let now = 2 * PROVIDER_HEALTH_MAX_AGE + 1;
+        assert_eq!(health.success_rate(now), 1000);
+        assert_eq!(health.average_latency(now), None);
+        assert_eq!(health.rank(now), ProviderHealth::default().rank(now));
+    }
+}
@@ -0,0 +1,79 @@
+use candid::Principal;
+use ic_canister_log::log;
+use ic_solana::logs::INFO;
+
+use crate::{
+    constants::HOSTNAME_MAX_SIZE,
+    memory::HostsMemory,
+    state::{mutate_state, read_state, State},
+    types::HostnameStorable,
+};
+
+/// Runtime list of RPC hosts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostList {
+    Blocklist,
+    Allowlist,
+}
+
+impl HostList {
+    fn get(self, state: &State) -> &HostsMemory {
+        match self {
+            HostList::Blocklist => &state.hosts_blocklist,
+            HostList::Allowlist => &state.hosts_allowlist,
+        }
+    }
+
+    fn get_mut(self, state: &mut State) -> &mut HostsMemory {
+        match self {
+            HostList::Blocklist => &mut state.hosts_blocklist,
+            HostList::Allowlist => &mut state.hosts_allowlist,
+        }
+    }
+}
+
+/// Returns whether RPC calls to `hostname` are allowed by the runtime lists.
+///
+/// A blocked host is never allowed. When the allowlist is not empty, only the hosts it contains
+/// are allowed.
+pub fn is_host_allowed(hostname: &str) -> bool {
+    let hostname = HostnameStorable(hostname.to_ascii_lowercase());
+    read_state(|s| {
+        !s.hosts_blocklist.contains_key(&hostname)
+            && (s.hosts_allowlist.is_empty() || s.hosts_allowlist.contains_key(&hostname))
+    })
+}
+
+pub fn get_hosts(list: HostList) -> Vec<String> {
+    read_state(|s| list.get(s).iter().map(|(host, _)| host.0).collect())
+}
+
+/// Adds hosts to a list.
+pub fn do_add_hosts(caller: Principal, list: HostList, hosts: Vec<String>) {
+    let hosts = hosts.iter().map(|host| parse_hostname(host)).collect::<Vec<_>>();
+    log!(INFO, "[{}] Adding hosts to the {:?}: {:?}", caller, list, hosts);
+    mutate_state(|s| {
+        for host in hosts {
+            list.get_mut(s).insert(host, ());
+        }
+    });
+}
+
+/// Removes hosts from a list.
+pub fn do_remove_hosts(caller: Principal, list: HostList, hosts: Vec<String>) {
+    let hosts = hosts.iter().map(|host| parse_hostname(host)).collect::<Vec<_>>();
+    log!(INFO, "[{}] Removing hosts from the {:?}: {:?}", caller, list, hosts);
+    mutate_state(|s| {
+        for host in hosts {
+            list.get_mut(s).remove(&host);
+        }
+    });
+}
+
+fn parse_hostname(host: &str) -> HostnameStorable {
+    let host = host.trim().to_ascii_lowercase();
+    if host.is_empty() || host.len() > HOSTNAME_MAX_SIZE as usize || host.contains(['/', ':', '?', '#']) {
+        ic_cdk::trap(&format!("Invalid hostname: {}", host));
+    }
+    HostnameStorable(host)
+}
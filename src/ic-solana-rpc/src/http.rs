@@ -1,9 +1,11 @@
+use std::collections::BTreeMap;
+
 use ic_canisters_http_types::{HttpRequest, HttpResponse, HttpResponseBuilder};
 use ic_cdk::api::management_canister::http_request::TransformContext;
 use ic_solana::{
     constants::HTTP_MAX_SIZE,
     logs::{Log, Priority, Sort},
-    rpc_client::{RpcApi, RpcClient, RpcClientConfig, RpcConfig, RpcServices},
+    rpc_client::{RpcApi, RpcClient, RpcClientConfig, RpcConfig, RpcResult, RpcServices},
     types::Cluster,
 };
 
@@ -14,14 +16,22 @@ use crate::{
         INGRESS_MESSAGE_BYTE_RECEIVED_COST, INGRESS_MESSAGE_RECEIVED_COST, INGRESS_OVERHEAD_BYTES, NODES_IN_SUBNET,
         RPC_URL_COST_BYTES,
     },
+    free_rpc::{record_free_rpc_bytes, reserve_free_rpc_call},
+    health::{healthiest_providers, record_provider_call},
     providers::find_provider,
+    rate_limit::check_rate_limit,
+    revenue::credit_provider,
     state::read_state,
     utils::validate_hostname,
 };
 
-/// Create an [RpcClient] based on the provided configuration.
-pub fn rpc_client(source: RpcServices, config: Option<RpcConfig>) -> RpcClient {
-    let providers = match source {
+/// Create an [RpcClient] based on the provided configuration, once the caller is within the rate
+/// limits of `method`.
+pub fn rpc_client(method: &str, source: RpcServices, config: Option<RpcConfig>) -> RpcResult<RpcClient> {
+    check_rate_limit(ic_cdk::caller(), method)?;
+
+    // Registered providers are reported to the call observer by ID, custom APIs are not
+    let provider_ids = match &source {
         RpcServices::Mainnet | RpcServices::Testnet | RpcServices::Devnet | RpcServices::Localnet => {
             let cluster = match source {
                 RpcServices::Mainnet => Cluster::Mainnet,
@@ -30,11 +40,22 @@ pub fn rpc_client(source: RpcServices, config: Option<RpcConfig>) -> RpcClient {
                 RpcServices::Localnet => Cluster::Localnet,
                 _ => unreachable!(),
             };
-            vec![get_provider_rpc_api(&cluster.to_string())]
+            vec![cluster.to_string()]
         }
-        RpcServices::Provider(ids) => ids.iter().map(|id| get_provider_rpc_api(id)).collect(),
+        RpcServices::Provider(ids) => ids.clone(),
+        RpcServices::Custom(_) => vec![],
+        RpcServices::Healthiest { cluster, count } => healthiest_providers(*cluster, *count)?,
+    };
+    let provider_apis = provider_ids
+        .into_iter()
+        .map(|id| (get_provider_rpc_api(&id), id))
+        .collect::<Vec<_>>();
+    let providers = match source {
         RpcServices::Custom(apis) => apis, // Use the custom APIs directly
+        // Keep the order of the providers, healthiest first
+        _ => provider_apis.iter().map(|(api, _)| api.clone()).collect(),
     };
+    let provider_ids = provider_apis.into_iter().collect::<BTreeMap<_, _>>();
 
     let config = config.unwrap_or_default();
 
@@ -50,11 +71,26 @@ pub fn rpc_client(source: RpcServices, config: Option<RpcConfig>) -> RpcClient {
                 (cycles_cost, get_cost_with_collateral(cycles_cost))
             }),
             host_validator: Some(|host| validate_hostname(host).is_ok()),
+            call_observer: Some(|_, outcome| {
+                if let Some(provider_id) = &outcome.provider_id {
+                    // A response exceeding the size limit says nothing about the provider's health
+                    if !outcome.is_too_large {
+                        record_provider_call(provider_id, outcome.is_success, outcome.latency);
+                    }
+                    if outcome.is_success {
+                        credit_provider(provider_id, outcome.cycles_charged);
+                    }
+                }
+                if outcome.is_free {
+                    record_free_rpc_bytes(ic_cdk::caller(), outcome.response_size);
+                }
+            }),
             transform_context: Some(TransformContext::from_name("__transform_json_rpc".to_owned(), vec![])),
             is_demo_active: s.is_demo_active,
+            free_call_reserver: Some(|| reserve_free_rpc_call(ic_cdk::caller())),
             use_compression: false,
         };
-        RpcClient::new(providers, Some(config))
+        Ok(RpcClient::new(providers, Some(config)).with_provider_ids(provider_ids))
     })
 }
 
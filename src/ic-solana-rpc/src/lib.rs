@@ -1,8 +1,14 @@
 pub mod auth;
+pub mod cache;
 pub mod constants;
+pub mod free_rpc;
+pub mod health;
+pub mod hosts;
 pub mod http;
 pub mod memory;
 pub mod providers;
+pub mod rate_limit;
+pub mod revenue;
 pub mod state;
 pub mod types;
 pub mod utils;
@@ -11,7 +11,7 @@ use ic_cdk::{
 use ic_solana::{
     metrics::{encode_metrics, read_metrics, Metrics},
     request::RpcRequest,
-    rpc_client::{RpcConfig, RpcResult, RpcServices},
+    rpc_client::{RpcConfig, RpcError, RpcResult, RpcServices},
     types::{
         response::{
             RpcAccountBalance, RpcBlockCommitment, RpcBlockProduction, RpcBlockhash,
@@ -32,9 +32,15 @@ use ic_solana::{
 };
 use ic_solana_rpc::{
     auth::{do_authorize, do_deauthorize, require_manage_or_controller, require_register_provider, Auth},
-    constants::NODES_IN_SUBNET,
+    cache::{cached, cached_if, is_finalized, CacheKey},
+    constants::{CACHE_TTL_IMMUTABLE, CACHE_TTL_SLOW_CHANGING, NODES_IN_SUBNET},
+    free_rpc::{self, do_set_free_rpc_quota, FreeRpcQuota, FreeRpcUsage},
+    health::{self, ProviderHealth},
+    hosts::{do_add_hosts, do_remove_hosts, get_hosts, HostList},
     http::{get_http_request_cost, rpc_client, serve_logs, serve_metrics},
     providers::{do_register_provider, do_unregister_provider, do_update_provider},
+    rate_limit::{self, do_set_rate_limit, RateLimit},
+    revenue::{self, do_withdraw_accumulated_cycles},
     state::{read_state, replace_state, InitArgs},
     types::{RegisterProviderArgs, UpdateProviderArgs},
     utils::{parse_pubkey, parse_pubkeys, parse_signature, parse_signatures},
@@ -49,7 +55,7 @@ pub async fn sol_get_account_info(
     pubkey: String,
     params: Option<RpcAccountInfoConfig>,
 ) -> RpcResult<Option<UiAccount>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getAccountInfo", source, config)?;
     let pubkey = parse_pubkey(&pubkey)?;
     client
         .get_account_info(&pubkey, params)
@@ -66,7 +72,7 @@ pub async fn sol_get_balance(
     pubkey: String,
     params: Option<RpcContextConfig>,
 ) -> RpcResult<u64> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getBalance", source, config)?;
     let pubkey = parse_pubkey(&pubkey)?;
     client.get_balance(&pubkey, params).await.map(|ctx| ctx.parse_value())
 }
@@ -80,8 +86,15 @@ pub async fn sol_get_block(
     slot: Slot,
     params: Option<RpcBlockConfig>,
 ) -> RpcResult<UiConfirmedBlock> {
-    let client = rpc_client(source, config);
-    client.get_block(slot, params).await.map(|ctx| ctx.into())
+    let params = params.unwrap_or_default();
+    let client = rpc_client("sol_getBlock", source, config)?;
+    let key = is_finalized(params.commitment)
+        .then(|| CacheKey::new(&client, RpcRequest::GetBlock, &(slot, params)))
+        .flatten();
+    cached(key, RpcRequest::GetBlock, CACHE_TTL_IMMUTABLE, async {
+        client.get_block(slot, Some(params)).await.map(|ctx| ctx.into())
+    })
+    .await
 }
 
 /// Returns commitment for a particular block.
@@ -92,7 +105,7 @@ pub async fn sol_get_block_commitment(
     config: Option<RpcConfig>,
     slot: Slot,
 ) -> RpcResult<RpcBlockCommitment> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getBlockCommitment", source, config)?;
     client.get_block_commitment(slot).await
 }
 
@@ -104,7 +117,7 @@ pub async fn sol_get_block_height(
     config: Option<RpcConfig>,
     params: Option<RpcContextConfig>,
 ) -> RpcResult<u64> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getBlockHeight", source, config)?;
     client.get_block_height(params).await
 }
 
@@ -116,7 +129,7 @@ pub async fn sol_get_block_production(
     config: Option<RpcConfig>,
     params: Option<RpcBlockProductionConfig>,
 ) -> RpcResult<RpcBlockProduction> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getBlockProduction", source, config)?;
     client
         .get_block_production(params.map(Into::into))
         .await
@@ -127,7 +140,7 @@ pub async fn sol_get_block_production(
 #[update(name = "sol_getBlockTime")]
 #[candid_method(rename = "sol_getBlockTime")]
 pub async fn sol_get_block_time(source: RpcServices, config: Option<RpcConfig>, slot: Slot) -> RpcResult<i64> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getBlockTime", source, config)?;
     client.get_block_time(slot).await
 }
 
@@ -141,7 +154,7 @@ pub async fn sol_get_blocks(
     last_slot: Option<Slot>,
     params: Option<CommitmentConfig>,
 ) -> RpcResult<Vec<u64>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getBlocks", source, config)?;
     client.get_blocks(start_slot, last_slot, params).await
 }
 
@@ -155,7 +168,7 @@ pub async fn sol_get_blocks_with_limit(
     limit: u64,
     params: Option<CommitmentConfig>,
 ) -> RpcResult<Vec<u64>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getBlocksWithLimit", source, config)?;
     client.get_blocks_with_limit(start_slot, limit, params).await
 }
 
@@ -163,7 +176,7 @@ pub async fn sol_get_blocks_with_limit(
 #[update(name = "sol_getClusterNodes")]
 #[candid_method(rename = "sol_getClusterNodes")]
 pub async fn sol_get_cluster_nodes(source: RpcServices, config: Option<RpcConfig>) -> RpcResult<Vec<RpcContactInfo>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getClusterNodes", source, config)?;
     client.get_cluster_nodes().await
 }
 
@@ -175,7 +188,7 @@ pub async fn sol_get_epoch_info(
     config: Option<RpcConfig>,
     params: Option<RpcContextConfig>,
 ) -> RpcResult<EpochInfo> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getEpochInfo", source, config)?;
     client.get_epoch_info(params).await
 }
 
@@ -183,8 +196,15 @@ pub async fn sol_get_epoch_info(
 #[update(name = "sol_getEpochSchedule")]
 #[candid_method(rename = "sol_getEpochSchedule")]
 pub async fn sol_get_epoch_schedule(source: RpcServices, config: Option<RpcConfig>) -> RpcResult<EpochSchedule> {
-    let client = rpc_client(source, config);
-    client.get_epoch_schedule().await
+    let client = rpc_client("sol_getEpochSchedule", source, config)?;
+    let key = CacheKey::new(&client, RpcRequest::GetEpochSchedule, &());
+    cached(
+        key,
+        RpcRequest::GetEpochSchedule,
+        CACHE_TTL_IMMUTABLE,
+        client.get_epoch_schedule(),
+    )
+    .await
 }
 
 /// Get the fee the network will charge for a particular Message.
@@ -196,7 +216,7 @@ pub async fn sol_get_fee_for_message(
     message: String,
     params: Option<RpcContextConfig>,
 ) -> RpcResult<u64> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getFeeForMessage", source, config)?;
     client
         .get_fee_for_message(message, params)
         .await
@@ -207,7 +227,7 @@ pub async fn sol_get_fee_for_message(
 #[update(name = "sol_getFirstAvailableBlock")]
 #[candid_method(rename = "sol_getFirstAvailableBlock")]
 pub async fn sol_get_first_available_block(source: RpcServices, config: Option<RpcConfig>) -> RpcResult<Slot> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getFirstAvailableBlock", source, config)?;
     client.get_first_available_block().await
 }
 
@@ -215,8 +235,15 @@ pub async fn sol_get_first_available_block(source: RpcServices, config: Option<R
 #[update(name = "sol_getGenesisHash")]
 #[candid_method(rename = "sol_getGenesisHash")]
 pub async fn sol_get_genesis_hash(source: RpcServices, config: Option<RpcConfig>) -> RpcResult<String> {
-    let client = rpc_client(source, config);
-    client.get_genesis_hash().await
+    let client = rpc_client("sol_getGenesisHash", source, config)?;
+    let key = CacheKey::new(&client, RpcRequest::GetGenesisHash, &());
+    cached(
+        key,
+        RpcRequest::GetGenesisHash,
+        CACHE_TTL_IMMUTABLE,
+        client.get_genesis_hash(),
+    )
+    .await
 }
 
 /// Returns the current health of the node.
@@ -225,7 +252,7 @@ pub async fn sol_get_genesis_hash(source: RpcServices, config: Option<RpcConfig>
 #[update(name = "sol_getHealth")]
 #[candid_method(rename = "sol_getHealth")]
 pub async fn sol_get_health(source: RpcServices, config: Option<RpcConfig>) -> RpcResult<String> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getHealth", source, config)?;
     client.get_health().await
 }
 
@@ -238,7 +265,7 @@ pub async fn sol_get_highest_snapshot_slot(
     source: RpcServices,
     config: Option<RpcConfig>,
 ) -> RpcResult<RpcSnapshotSlotInfo> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getHighestSnapshotSlot", source, config)?;
     client.get_highest_snapshot_slot().await
 }
 
@@ -246,7 +273,7 @@ pub async fn sol_get_highest_snapshot_slot(
 #[update(name = "sol_getIdentity")]
 #[candid_method(rename = "sol_getIdentity")]
 pub async fn sol_get_identity(source: RpcServices, config: Option<RpcConfig>) -> RpcResult<RpcIdentity> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getIdentity", source, config)?;
     client.get_identity().await
 }
 
@@ -257,15 +284,22 @@ pub async fn sol_get_inflation_governor(
     source: RpcServices,
     config: Option<RpcConfig>,
 ) -> RpcResult<RpcInflationGovernor> {
-    let client = rpc_client(source, config);
-    client.get_inflation_governor().await
+    let client = rpc_client("sol_getInflationGovernor", source, config)?;
+    let key = CacheKey::new(&client, RpcRequest::GetInflationGovernor, &());
+    cached(
+        key,
+        RpcRequest::GetInflationGovernor,
+        CACHE_TTL_SLOW_CHANGING,
+        client.get_inflation_governor(),
+    )
+    .await
 }
 
 /// Returns the specific inflation values for the current epoch.
 #[update(name = "sol_getInflationRate")]
 #[candid_method(rename = "sol_getInflationRate")]
 pub async fn sol_get_inflation_rate(source: RpcServices, config: Option<RpcConfig>) -> RpcResult<RpcInflationRate> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getInflationRate", source, config)?;
     client.get_inflation_rate().await
 }
 
@@ -278,7 +312,7 @@ pub async fn sol_get_inflation_reward(
     addresses: Vec<String>,
     params: Option<RpcEpochConfig>,
 ) -> RpcResult<Vec<Option<RpcInflationReward>>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getInflationReward", source, config)?;
     let pubkeys = parse_pubkeys(addresses)?;
     client.get_inflation_reward(&pubkeys, params).await
 }
@@ -293,7 +327,7 @@ pub async fn sol_get_signatures_for_address(
     pubkey: String,
     params: Option<RpcSignaturesForAddressConfig>,
 ) -> RpcResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getSignaturesForAddress", source, config)?;
     let pubkey = parse_pubkey(&pubkey)?;
     client.get_signatures_for_address(&pubkey, params).await
 }
@@ -306,7 +340,7 @@ pub async fn sol_get_slot(
     config: Option<RpcConfig>,
     params: Option<RpcContextConfig>,
 ) -> RpcResult<Slot> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getSlot", source, config)?;
     client.get_slot(params).await
 }
 
@@ -318,7 +352,7 @@ pub async fn sol_get_slot_leader(
     config: Option<RpcConfig>,
     params: Option<RpcContextConfig>,
 ) -> RpcResult<String> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getSlotLeader", source, config)?;
     client.get_slot_leader(params).await
 }
 
@@ -331,7 +365,7 @@ pub async fn sol_get_slot_leaders(
     start_slot: u64,
     limit: Option<u64>,
 ) -> RpcResult<Vec<String>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getSlotLeaders", source, config)?;
     client.get_slot_leaders(start_slot, limit).await
 }
 
@@ -343,7 +377,7 @@ pub async fn sol_get_stake_minimum_delegation(
     config: Option<RpcConfig>,
     params: Option<CommitmentConfig>,
 ) -> RpcResult<u64> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getStakeMinimumDelegation", source, config)?;
     Ok(client.get_stake_minimum_delegation(params).await?.value)
 }
 
@@ -355,7 +389,7 @@ pub async fn sol_get_supply(
     config: Option<RpcConfig>,
     params: Option<RpcSupplyConfig>,
 ) -> RpcResult<RpcSupply> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getSupply", source, config)?;
     Ok(client.get_supply(params).await?.value)
 }
 
@@ -368,7 +402,7 @@ pub async fn sol_get_token_account_balance(
     pubkey: String,
     commitment: Option<CommitmentLevel>,
 ) -> RpcResult<UiTokenAmount> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getTokenAccountBalance", source, config)?;
     let pubkey = parse_pubkey(&pubkey)?;
     Ok(client
         .get_token_account_balance(&pubkey, commitment.map(Into::into))
@@ -386,7 +420,7 @@ pub async fn sol_get_token_accounts_by_delegate(
     filter: RpcTokenAccountsFilter,
     params: Option<RpcAccountInfoConfig>,
 ) -> RpcResult<Vec<RpcKeyedAccount>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getTokenAccountsByDelegate", source, config)?;
     let pubkey = parse_pubkey(&pubkey)?;
     let accounts = client
         .get_token_accounts_by_delegate(&pubkey, filter, params)
@@ -405,7 +439,7 @@ pub async fn sol_get_token_accounts_by_owner(
     filter: RpcTokenAccountsFilter,
     params: Option<RpcAccountInfoConfig>,
 ) -> RpcResult<Vec<RpcKeyedAccount>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getTokenAccountsByOwner", source, config)?;
     let pubkey = parse_pubkey(&pubkey)?;
     let accounts = client
         .get_token_accounts_by_owner(&pubkey, filter, params)
@@ -423,7 +457,7 @@ pub async fn sol_get_token_largest_accounts(
     mint: String,
     params: Option<CommitmentConfig>,
 ) -> RpcResult<Vec<RpcTokenAccountBalance>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getTokenLargestAccounts", source, config)?;
     let mint = parse_pubkey(&mint)?;
     let accounts = client.get_token_largest_accounts(&mint, params).await?.parse_value();
     Ok(accounts.into_iter().map(Into::into).collect())
@@ -438,7 +472,7 @@ pub async fn sol_get_token_supply(
     mint: String,
     params: Option<CommitmentConfig>,
 ) -> RpcResult<UiTokenAmount> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getTokenSupply", source, config)?;
     let mint = parse_pubkey(&mint)?;
     Ok(client.get_token_supply(&mint, params).await?.parse_value())
 }
@@ -451,7 +485,7 @@ pub async fn sol_get_largest_accounts(
     config: Option<RpcConfig>,
     params: Option<RpcLargestAccountsConfig>,
 ) -> RpcResult<Vec<RpcAccountBalance>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getLargestAccounts", source, config)?;
     Ok(client.get_largest_accounts(params).await?.parse_value())
 }
 
@@ -463,7 +497,7 @@ pub async fn sol_get_latest_blockhash(
     config: Option<RpcConfig>,
     params: Option<RpcContextConfig>,
 ) -> RpcResult<RpcBlockhash> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getLatestBlockhash", source, config)?;
     Ok(client.get_latest_blockhash(params).await?.parse_value())
 }
 
@@ -476,7 +510,7 @@ pub async fn sol_get_leader_schedule(
     epoch: u64,
     params: Option<RpcLeaderScheduleConfig>,
 ) -> RpcResult<RpcLeaderSchedule> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getLeaderSchedule", source, config)?;
     client.get_leader_schedule(epoch, params).await
 }
 
@@ -484,7 +518,7 @@ pub async fn sol_get_leader_schedule(
 #[update(name = "sol_getMaxRetransmitSlot")]
 #[candid_method(rename = "sol_getMaxRetransmitSlot")]
 pub async fn sol_get_max_retransmit_slot(source: RpcServices, config: Option<RpcConfig>) -> RpcResult<u64> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getMaxRetransmitSlot", source, config)?;
     client.get_max_retransmit_slot().await
 }
 
@@ -492,7 +526,7 @@ pub async fn sol_get_max_retransmit_slot(source: RpcServices, config: Option<Rpc
 #[update(name = "sol_getMaxShredInsertSlot")]
 #[candid_method(rename = "sol_getMaxShredInsertSlot")]
 pub async fn sol_get_max_shred_insert_slot(source: RpcServices, config: Option<RpcConfig>) -> RpcResult<u64> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getMaxShredInsertSlot", source, config)?;
     client.get_max_shred_insert_slot().await
 }
 
@@ -505,7 +539,7 @@ pub async fn sol_get_minimum_balance_for_rent_exemption(
     size: usize,
     params: Option<CommitmentConfig>,
 ) -> RpcResult<u64> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getMinimumBalanceForRentExemption", source, config)?;
     client.get_minimum_balance_for_rent_exemption(size, params).await
 }
 
@@ -518,7 +552,7 @@ pub async fn sol_get_multiple_accounts(
     addresses: Vec<String>,
     params: Option<RpcAccountInfoConfig>,
 ) -> RpcResult<Vec<UiAccount>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getMultipleAccounts", source, config)?;
     let pubkeys = parse_pubkeys(addresses)?;
     let res = client.get_multiple_accounts(pubkeys, params).await?.parse_value();
     Ok(res.into_iter().map(Into::into).collect())
@@ -534,7 +568,7 @@ pub async fn sol_get_program_accounts(
     params: Option<RpcProgramAccountsConfig>,
 ) -> RpcResult<Vec<RpcKeyedAccount>> {
     let pubkey = parse_pubkey(&program)?;
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getProgramAccounts", source, config)?;
     let res = client.get_program_accounts(&pubkey, params).await?;
     Ok(res.into_iter().map(Into::into).collect())
 }
@@ -549,7 +583,7 @@ pub async fn sol_get_recent_performance_samples(
     config: Option<RpcConfig>,
     limit: u64,
 ) -> RpcResult<Vec<RpcPerfSample>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getRecentPerformanceSamples", source, config)?;
     client.get_recent_performance_samples(limit).await
 }
 
@@ -561,7 +595,7 @@ pub async fn sol_get_recent_prioritization_fees(
     config: Option<RpcConfig>,
     addresses: Vec<String>,
 ) -> RpcResult<Vec<RpcPrioritizationFee>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getRecentPrioritizationFees", source, config)?;
     let pubkeys = parse_pubkeys(addresses)?;
     client.get_recent_prioritization_fees(&pubkeys).await
 }
@@ -576,7 +610,7 @@ pub async fn sol_get_signature_statuses(
     signatures: Vec<String>,
     params: Option<RpcSignatureStatusConfig>,
 ) -> RpcResult<Vec<Option<TransactionStatus>>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getSignatureStatuses", source, config)?;
     let signatures = parse_signatures(signatures)?;
     Ok(client.get_signature_statuses(&signatures, params).await?.parse_value())
 }
@@ -590,10 +624,24 @@ pub async fn sol_get_transaction(
     signature: String,
     params: Option<RpcTransactionConfig>,
 ) -> RpcResult<Option<EncodedConfirmedTransactionWithStatusMeta>> {
-    let client = rpc_client(source, config);
+    let params = params.unwrap_or_default();
+    let client = rpc_client("sol_getTransaction", source, config)?;
+    let key = is_finalized(params.commitment)
+        .then(|| CacheKey::new(&client, RpcRequest::GetTransaction, &(&signature, params)))
+        .flatten();
     let signature = parse_signature(&signature)?;
-    let response = client.get_transaction(&signature, params).await?;
-    Ok(response.map(|tx| tx.into()))
+    // A transaction that is not found may still be finalized later
+    cached_if(
+        key,
+        RpcRequest::GetTransaction,
+        CACHE_TTL_IMMUTABLE,
+        async {
+            let response = client.get_transaction(&signature, Some(params)).await?;
+            Ok(response.map(|tx| tx.into()))
+        },
+        Option::is_some,
+    )
+    .await
 }
 
 /// Returns the current number of transactions from the ledger.
@@ -604,7 +652,7 @@ pub async fn sol_get_transaction_count(
     config: Option<RpcConfig>,
     params: Option<RpcContextConfig>,
 ) -> RpcResult<u64> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getTransactionCount", source, config)?;
     client.get_transaction_count(params).await
 }
 
@@ -612,7 +660,7 @@ pub async fn sol_get_transaction_count(
 #[update(name = "sol_getVersion")]
 #[candid_method(rename = "sol_getVersion")]
 pub async fn sol_get_version(source: RpcServices, config: Option<RpcConfig>) -> RpcResult<RpcVersionInfo> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getVersion", source, config)?;
     client.get_version().await
 }
 
@@ -624,7 +672,7 @@ pub async fn sol_get_vote_accounts(
     config: Option<RpcConfig>,
     params: Option<RpcGetVoteAccountsConfig>,
 ) -> RpcResult<RpcVoteAccountStatus> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getVoteAccounts", source, config)?;
     client.get_vote_accounts(params).await
 }
 
@@ -637,7 +685,7 @@ pub async fn sol_is_blockhash_valid(
     blockhash: String,
     params: Option<RpcContextConfig>,
 ) -> RpcResult<bool> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_isBlockhashValid", source, config)?;
     Ok(client.is_blockhash_valid(blockhash, params).await?.parse_value())
 }
 
@@ -645,7 +693,7 @@ pub async fn sol_is_blockhash_valid(
 #[update(name = "sol_minimumLedgerSlot")]
 #[candid_method(rename = "sol_minimumLedgerSlot")]
 pub async fn sol_minimum_ledger_slot(source: RpcServices, config: Option<RpcConfig>) -> RpcResult<u64> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_minimumLedgerSlot", source, config)?;
     client.minimum_ledger_slot().await
 }
 
@@ -658,7 +706,7 @@ pub async fn sol_request_airdrop(
     pubkey: String,
     lamports: u64,
 ) -> RpcResult<String> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_requestAirdrop", source, config)?;
     let pubkey = parse_pubkey(&pubkey)?;
     client.request_airdrop(&pubkey, lamports).await
 }
@@ -673,12 +721,41 @@ pub async fn sol_send_transaction(
     raw_signed_transaction: String,
     params: Option<RpcSendTransactionConfig>,
 ) -> RpcResult<String> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_sendTransaction", source, config)?;
     let tx = Transaction::from_str(&raw_signed_transaction).expect("Invalid transaction");
     let signature = client.send_transaction(tx, params.unwrap_or_default()).await?;
     Ok(signature.to_string())
 }
 
+/// Submits a signed transaction to the cluster and waits until it reaches the given commitment
+/// (`finalized` by default).
+///
+/// `last_valid_block_height` is the one returned by `sol_getLatestBlockhash` with the recent
+/// blockhash of the transaction. The transaction status is polled until the block height exceeds
+/// it, after which a `TransactionExpired` error is returned. Polling also stops with a
+/// `TransactionNotConfirmed` error, which carries the signature of the sent transaction, after
+/// 30 attempts or if an attempt fails.
+///
+/// Every request is charged separately: in the worst case `sendTransaction` and 30 times
+/// `getBlockHeight` and `getSignatureStatuses`, 61 requests in total, whose cost can be estimated
+/// with `requestCost`. Cycles of requests that are not made are refunded.
+#[update(name = "sol_sendAndConfirmTransaction")]
+#[candid_method(rename = "sol_sendAndConfirmTransaction")]
+pub async fn sol_send_and_confirm_transaction(
+    source: RpcServices,
+    config: Option<RpcConfig>,
+    raw_signed_transaction: String,
+    params: Option<RpcSendTransactionConfig>,
+    commitment: Option<CommitmentConfig>,
+    last_valid_block_height: u64,
+) -> RpcResult<TransactionStatus> {
+    let client = rpc_client("sol_sendAndConfirmTransaction", source, config)?;
+    let tx = Transaction::from_str(&raw_signed_transaction).map_err(|e| RpcError::ParseError(e.to_string()))?;
+    client
+        .send_and_confirm_transaction(tx, params.unwrap_or_default(), commitment, last_valid_block_height)
+        .await
+}
+
 /// Simulate sending a transaction.
 #[update(name = "sol_simulateTransaction")]
 #[candid_method(rename = "sol_simulateTransaction")]
@@ -688,7 +765,7 @@ pub async fn sol_simulate_transaction(
     raw_transaction: String,
     params: Option<RpcSimulateTransactionConfig>,
 ) -> RpcResult<RpcSimulateTransactionResult> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_simulateTransaction", source, config)?;
     let tx = Transaction::from_str(&raw_transaction).expect("Invalid transaction");
     let res = client.simulate_transaction(tx, params.unwrap_or_default()).await?;
     Ok(res.parse_value().into())
@@ -706,7 +783,7 @@ pub async fn sol_get_logs(
     pubkey: String,
     params: Option<RpcSignaturesForAddressConfig>,
 ) -> RpcResult<HashMap<String, RpcResult<Option<EncodedConfirmedTransactionWithStatusMeta>>>> {
-    let client = rpc_client(source, config);
+    let client = rpc_client("sol_getLogs", source, config)?;
     let pubkey = parse_pubkey(&pubkey)?;
     let commitment = params.as_ref().and_then(|p| p.commitment);
     let signatures = client.get_signatures_for_address(&pubkey, params).await?;
@@ -737,7 +814,7 @@ pub async fn request(
     params: CandidValue,
     max_response_bytes: Option<u64>,
 ) -> RpcResult<String> {
-    let client = rpc_client(source, None);
+    let client = rpc_client("request", source, None)?;
     let res = client
         .call::<_, serde_json::Value>(RpcRequest::Custom { method }, params, max_response_bytes)
         .await?;
@@ -767,6 +844,13 @@ fn get_providers() -> Vec<String> {
     read_state(|s| s.rpc_providers.iter().map(|(k, _)| k.0).collect())
 }
 
+/// Returns the call history of a provider.
+#[query(name = "getProviderHealth")]
+#[candid_method(query, rename = "getProviderHealth")]
+fn get_provider_health(provider_id: String) -> Option<ProviderHealth> {
+    health::get_provider_health(&provider_id)
+}
+
 #[update(name = "registerProvider", guard = "require_register_provider")]
 #[candid_method(rename = "registerProvider")]
 fn register_provider(args: RegisterProviderArgs) {
@@ -811,6 +895,86 @@ fn deauthorize(principal: Principal, auth: Auth) -> bool {
     do_deauthorize(principal, auth)
 }
 
+#[query(name = "getRateLimits")]
+#[candid_method(query, rename = "getRateLimits")]
+fn get_rate_limits() -> Vec<(Option<String>, RateLimit)> {
+    rate_limit::get_rate_limits()
+}
+
+/// Sets the rate limit of a method, or of all methods if no method is given, per caller. Removes
+/// the rate limit if no limit is given.
+#[update(name = "setRateLimit", guard = "require_manage_or_controller")]
+#[candid_method(rename = "setRateLimit")]
+fn set_rate_limit(method: Option<String>, limit: Option<RateLimit>) {
+    do_set_rate_limit(ic_cdk::caller(), method, limit)
+}
+
+/// Returns the free RPC quota and usage of a principal authorized with `FreeRpc`.
+#[query(name = "getFreeRpcUsage")]
+#[candid_method(query, rename = "getFreeRpcUsage")]
+fn get_free_rpc_usage(principal: Principal) -> Option<FreeRpcUsage> {
+    free_rpc::get_free_rpc_usage(principal)
+}
+
+#[update(name = "setFreeRpcQuota", guard = "require_manage_or_controller")]
+#[candid_method(rename = "setFreeRpcQuota")]
+fn set_free_rpc_quota(principal: Principal, quota: FreeRpcQuota) {
+    do_set_free_rpc_quota(ic_cdk::caller(), principal, quota)
+}
+
+/// Returns the cycles accumulated by the caller as the owner of RPC providers.
+#[query(name = "getAccumulatedCycles")]
+#[candid_method(query, rename = "getAccumulatedCycles")]
+fn get_accumulated_cycles() -> u128 {
+    revenue::get_accumulated_cycles(ic_cdk::caller())
+}
+
+/// Sends the cycles accumulated by the caller to a canister.
+#[update(name = "withdrawAccumulatedCycles")]
+#[candid_method(rename = "withdrawAccumulatedCycles")]
+async fn withdraw_accumulated_cycles(canister: Principal) -> RpcResult<u128> {
+    do_withdraw_accumulated_cycles(ic_cdk::caller(), canister).await
+}
+
+#[query(name = "getBlockedHosts")]
+#[candid_method(query, rename = "getBlockedHosts")]
+fn get_blocked_hosts() -> Vec<String> {
+    get_hosts(HostList::Blocklist)
+}
+
+/// Blocks RPC calls to the given hosts, including calls to custom providers.
+#[update(name = "blockHosts", guard = "require_manage_or_controller")]
+#[candid_method(rename = "blockHosts")]
+fn block_hosts(hosts: Vec<String>) {
+    do_add_hosts(ic_cdk::caller(), HostList::Blocklist, hosts)
+}
+
+#[update(name = "unblockHosts", guard = "require_manage_or_controller")]
+#[candid_method(rename = "unblockHosts")]
+fn unblock_hosts(hosts: Vec<String>) {
+    do_remove_hosts(ic_cdk::caller(), HostList::Blocklist, hosts)
+}
+
+#[query(name = "getAllowedHosts")]
+#[candid_method(query, rename = "getAllowedHosts")]
+fn get_allowed_hosts() -> Vec<String> {
+    get_hosts(HostList::Allowlist)
+}
+
+/// Allows RPC calls to the given hosts. Once the allowlist is not empty, calls to any other host
+/// are rejected.
+#[update(name = "allowHosts", guard = "require_manage_or_controller")]
+#[candid_method(rename = "allowHosts")]
+fn allow_hosts(hosts: Vec<String>) {
+    do_add_hosts(ic_cdk::caller(), HostList::Allowlist, hosts)
+}
+
+#[update(name = "disallowHosts", guard = "require_manage_or_controller")]
+#[candid_method(rename = "disallowHosts")]
+fn disallow_hosts(hosts: Vec<String>) {
+    do_remove_hosts(ic_cdk::caller(), HostList::Allowlist, hosts)
+}
+
 #[query]
 fn http_request(request: AssetHttpRequest) -> AssetHttpResponse {
     match request.path() {
@@ -7,16 +7,35 @@ use ic_stable_structures::{
 
 use crate::{
     auth::AuthSet,
+    cache::{CacheEntry, CacheKey, ExpiryKey},
+    free_rpc::FreeRpcUsage,
+    health::ProviderHealth,
     providers::{ProviderId, RpcProvider},
-    types::PrincipalStorable,
+    rate_limit::{RateLimit, RateLimitMethod},
+    types::{HostnameStorable, PrincipalStorable},
 };
 
 const AUTH_MEMORY_ID: MemoryId = MemoryId::new(2);
 const PROVIDERS_MEMORY_ID: MemoryId = MemoryId::new(3);
+const PROVIDER_HEALTH_MEMORY_ID: MemoryId = MemoryId::new(4);
+const CACHE_MEMORY_ID: MemoryId = MemoryId::new(5);
+const CACHE_EXPIRY_MEMORY_ID: MemoryId = MemoryId::new(6);
+const HOSTS_BLOCKLIST_MEMORY_ID: MemoryId = MemoryId::new(7);
+const HOSTS_ALLOWLIST_MEMORY_ID: MemoryId = MemoryId::new(8);
+const ACCUMULATED_CYCLES_MEMORY_ID: MemoryId = MemoryId::new(9);
+const FREE_RPC_USAGE_MEMORY_ID: MemoryId = MemoryId::new(10);
+const RATE_LIMITS_MEMORY_ID: MemoryId = MemoryId::new(11);
 
 pub type StableMemory = VirtualMemory<DefaultMemoryImpl>;
 pub type AuthMemory = StableBTreeMap<PrincipalStorable, AuthSet, StableMemory>;
 pub type ProvidersMemory = StableBTreeMap<ProviderId, RpcProvider, StableMemory>;
+pub type ProviderHealthMemory = StableBTreeMap<ProviderId, ProviderHealth, StableMemory>;
+pub type CacheMemory = StableBTreeMap<CacheKey, CacheEntry, StableMemory>;
+pub type CacheExpiryMemory = StableBTreeMap<ExpiryKey, (), StableMemory>;
+pub type HostsMemory = StableBTreeMap<HostnameStorable, (), StableMemory>;
+pub type AccumulatedCyclesMemory = StableBTreeMap<PrincipalStorable, u128, StableMemory>;
+pub type FreeRpcUsageMemory = StableBTreeMap<PrincipalStorable, FreeRpcUsage, StableMemory>;
+pub type RateLimitsMemory = StableBTreeMap<RateLimitMethod, RateLimit, StableMemory>;
 
 thread_local! {
     // Stable static data: these are preserved when the canister is upgraded.
@@ -35,3 +54,35 @@ pub fn init_auth_memory() -> AuthMemory {
 pub fn init_providers_memory() -> ProvidersMemory {
     ProvidersMemory::init(get_memory(PROVIDERS_MEMORY_ID))
 }
+
+pub fn init_provider_health_memory() -> ProviderHealthMemory {
+    ProviderHealthMemory::init(get_memory(PROVIDER_HEALTH_MEMORY_ID))
+}
+
+pub fn init_cache_memory() -> CacheMemory {
+    CacheMemory::init(get_memory(CACHE_MEMORY_ID))
+}
+
+pub fn init_cache_expiry_memory() -> CacheExpiryMemory {
+    CacheExpiryMemory::init(get_memory(CACHE_EXPIRY_MEMORY_ID))
+}
+
+pub fn init_hosts_blocklist_memory() -> HostsMemory {
+    HostsMemory::init(get_memory(HOSTS_BLOCKLIST_MEMORY_ID))
+}
+
+pub fn init_hosts_allowlist_memory() -> HostsMemory {
+    HostsMemory::init(get_memory(HOSTS_ALLOWLIST_MEMORY_ID))
+}
+
+pub fn init_accumulated_cycles_memory() -> AccumulatedCyclesMemory {
+    AccumulatedCyclesMemory::init(get_memory(ACCUMULATED_CYCLES_MEMORY_ID))
+}
+
+pub fn init_free_rpc_usage_memory() -> FreeRpcUsageMemory {
+    FreeRpcUsageMemory::init(get_memory(FREE_RPC_USAGE_MEMORY_ID))
+}
+
+pub fn init_rate_limits_memory() -> RateLimitsMemory {
+    RateLimitsMemory::init(get_memory(RATE_LIMITS_MEMORY_ID))
+}
@@ -3,12 +3,16 @@ use std::borrow::Cow;
 use candid::{CandidType, Decode, Deserialize, Encode, Principal};
 use ic_canister_log::log;
 use ic_cdk::api::{is_controller, management_canister::http_request::HttpHeader};
-use ic_solana::{logs::INFO, rpc_client::RpcApi};
+use ic_solana::{
+    logs::INFO,
+    rpc_client::{RpcApi, RpcCluster},
+};
 use ic_stable_structures::{storable::Bound, Storable};
 use serde::Serialize;
 
 use crate::{
     auth::{do_deauthorize, is_authorized, Auth},
+    cache::remove_provider_cached,
     constants::PROVIDER_ID_MAX_SIZE,
     state::{mutate_state, read_state},
     types::{RegisterProviderArgs, RpcAuth, UpdateProviderArgs},
@@ -21,6 +25,7 @@ pub struct RpcProvider {
     pub url: String,
     pub auth: Option<RpcAuth>,
     pub owner: Principal,
+    pub cluster: Option<RpcCluster>,
 }
 
 impl RpcProvider {
@@ -120,6 +125,7 @@ pub fn do_register_provider(caller: Principal, args: RegisterProviderArgs) {
         url: args.url,
         auth: args.auth,
         owner: caller,
+        cluster: args.cluster,
     };
     provider.validate();
     do_deauthorize(caller, Auth::RegisterProvider);
@@ -141,6 +147,8 @@ pub fn do_unregister_provider(caller: Principal, provider_id: &str) -> bool {
         if let Some(provider) = s.rpc_providers.get(&id) {
             if provider.owner == caller || is_controller(&caller) || is_manager {
                 log!(INFO, "[{}] Unregistering provider: {:?}", caller, provider_id);
+                s.provider_health.remove(&id);
+                remove_provider_cached(s, provider_id);
                 s.rpc_providers.remove(&id).is_some()
             } else {
                 ic_cdk::trap("Unauthorized");
@@ -161,6 +169,9 @@ pub fn do_update_provider(caller: Principal, args: UpdateProviderArgs) {
                 if args.url.is_some() {
                     ic_cdk::trap("You are not authorized to update the `url` field");
                 }
+                if args.cluster.is_some() {
+                    ic_cdk::trap("You are not authorized to update the `cluster` field");
+                }
                 if let Some(auth) = args.auth {
                     provider.auth = Some(auth);
                 }
@@ -168,10 +179,17 @@ pub fn do_update_provider(caller: Principal, args: UpdateProviderArgs) {
             } else if is_controller(&caller) || is_manager {
                 if let Some(url) = args.url {
                     provider.url = url;
+                    // The call history and cached responses belong to the previous endpoint
+                    s.provider_health.remove(&provider_id);
+                    remove_provider_cached(s, &provider_id.0);
                 }
                 if let Some(auth) = args.auth {
                     provider.auth = Some(auth);
                 }
+                if let Some(cluster) = args.cluster {
+                    provider.cluster = Some(cluster);
+                    remove_provider_cached(s, &provider_id.0);
+                }
                 s.rpc_providers.insert(provider_id, provider);
             } else {
                 ic_cdk::trap("Unauthorized");
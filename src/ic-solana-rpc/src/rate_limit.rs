@@ -0,0 +1,248 @@
+//! Per-caller rate limiting of RPC methods.
+//!
+//! A caller has a token bucket for every rate limit applying to a call: the limit of all methods
+//! and the limit of the called method, if configured. A call takes a token from each of these
+//! buckets and is rejected if any of them is empty.
+
+use std::{borrow::Cow, cell::RefCell, collections::BTreeMap};
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_canister_log::log;
+use ic_solana::{
+    add_metric_entry,
+    logs::INFO,
+    metrics::MetricMethod,
+    rpc_client::{RpcError, RpcResult},
+};
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::Serialize;
+
+use crate::{
+    constants::{RATE_LIMIT_MAX_BUCKETS, RATE_LIMIT_METHOD_MAX_SIZE},
+    state::{mutate_state, read_state},
+};
+
+const NANOS_PER_MINUTE: u64 = 60 * 1_000_000_000;
+
+/// Key of the rate limit applying to all methods.
+const ALL_METHODS: &str = "*";
+
+thread_local! {
+    // Buckets are not persisted, they are all full again after an upgrade.
+    static BUCKETS: RefCell<BTreeMap<(Principal, RateLimitMethod), u64>> = RefCell::default();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Size of the bucket, which is the number of calls that can be made in a burst.
+    pub capacity: u64,
+    /// Number of tokens added to the bucket per minute.
+    pub refill_per_minute: u64,
+}
+
+impl RateLimit {
+    /// Nanoseconds it takes to add a token to the bucket.
+    fn refill_interval(&self) -> u64 {
+        NANOS_PER_MINUTE / self.refill_per_minute
+    }
+
+    /// Takes a token from a bucket, which is tracked as the time at which it is full again.
+    ///
+    /// Returns the updated time, or the nanoseconds until a token is available if the bucket is
+    /// empty.
+    fn take_token(&self, full_at: u64, now: u64) -> Result<u64, u64> {
+        let interval = self.refill_interval();
+        let full_at = full_at.max(now).saturating_add(interval);
+        let max_wait = self.capacity.saturating_mul(interval);
+        match (full_at - now).checked_sub(max_wait) {
+            Some(retry_after) if retry_after > 0 => Err(retry_after),
+            _ => Ok(full_at),
+        }
+    }
+}
+
+impl Storable for RateLimit {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 100,
+        is_fixed_size: false,
+    };
+}
+
+/// Name of a rate limited method.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RateLimitMethod(pub String);
+
+impl RateLimitMethod {
+    fn new(method: Option<String>) -> Self {
+        Self(method.unwrap_or_else(|| ALL_METHODS.to_string()))
+    }
+
+    fn method(&self) -> Option<String> {
+        (self.0 != ALL_METHODS).then(|| self.0.clone())
+    }
+}
+
+impl Storable for RateLimitMethod {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        self.0.to_bytes()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(String::from_bytes(bytes))
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: RATE_LIMIT_METHOD_MAX_SIZE,
+        is_fixed_size: false,
+    };
+}
+
+/// Returns the configured rate limits. Limits of all methods have no method.
+pub fn get_rate_limits() -> Vec<(Option<String>, RateLimit)> {
+    read_state(|s| {
+        s.rate_limits
+            .iter()
+            .map(|(method, limit)| (method.method(), limit))
+            .collect()
+    })
+}
+
+/// Sets or removes the rate limit of `method`, or of all methods if `method` is `None`.
+pub fn do_set_rate_limit(caller: Principal, method: Option<String>, limit: Option<RateLimit>) {
+    let method = RateLimitMethod::new(method);
+    if method.0.is_empty() || method.0.len() > RATE_LIMIT_METHOD_MAX_SIZE as usize {
+        ic_cdk::trap(&format!("Invalid method: {}", method.0));
+    }
+    if limit.is_some_and(|limit| limit.capacity == 0 || limit.refill_per_minute == 0) {
+        ic_cdk::trap("The capacity and refill rate must be positive");
+    }
+    if limit.is_some_and(|limit| limit.refill_per_minute > NANOS_PER_MINUTE) {
+        ic_cdk::trap(&format!(
+            "The refill rate must be at most {} tokens per minute",
+            NANOS_PER_MINUTE
+        ));
+    }
+    log!(INFO, "[{}] Setting rate limit of `{}`: {:?}", caller, method.0, limit);
+    mutate_state(|s| match limit {
+        Some(limit) => s.rate_limits.insert(method.clone(), limit),
+        None => s.rate_limits.remove(&method),
+    });
+    BUCKETS.with_borrow_mut(|buckets| buckets.retain(|(_, key), _| *key != method));
+}
+
+/// Takes a token for a call of `method` by `caller`, or fails with [RpcError::RateLimited].
+pub fn check_rate_limit(caller: Principal, method: &str) -> RpcResult<()> {
+    let limits = read_state(|s| {
+        [
+            RateLimitMethod::new(None),
+            RateLimitMethod::new(Some(method.to_string())),
+        ]
+        .into_iter()
+        .filter_map(|key| s.rate_limits.get(&key).map(|limit| (key, limit)))
+        .collect::<Vec<_>>()
+    });
+    if limits.is_empty() {
+        return Ok(());
+    }
+    let now = ic_cdk::api::time();
+
+    BUCKETS.with_borrow_mut(|buckets| {
+        let mut updated = Vec::with_capacity(limits.len());
+        for (key, limit) in limits {
+            let bucket = (caller, key);
+            let full_at = buckets.get(&bucket).copied().unwrap_or_default();
+            match limit.take_token(full_at, now) {
+                Ok(full_at) => updated.push((bucket, full_at)),
+                Err(retry_after) => {
+                    add_metric_entry!(err_rate_limited, MetricMethod(method.to_string()), 1);
+                    return Err(RpcError::RateLimited { retry_after });
+                }
+            }
+        }
+        buckets.extend(updated);
+        evict_buckets(buckets, now, RATE_LIMIT_MAX_BUCKETS);
+        Ok(())
+    })
+}
+
+/// Drops buckets until at most `max_buckets` are tracked: first the full ones, which are the same
+/// as untracked ones, then the ones closest to being full.
+fn evict_buckets<K: Clone + Ord>(buckets: &mut BTreeMap<K, u64>, now: u64, max_buckets: usize) {
+    if buckets.len() <= max_buckets {
+        return;
+    }
+    buckets.retain(|_, full_at| *full_at > now);
+    let excess = buckets.len().saturating_sub(max_buckets);
+    if excess > 0 {
+        let mut by_full_at = buckets
+            .iter()
+            .map(|(bucket, full_at)| (*full_at, bucket.clone()))
+            .collect::<Vec<_>>();
+        by_full_at.sort_unstable();
+        for (_, bucket) in by_full_at.into_iter().take(excess) {
+            buckets.remove(&bucket);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_take_token() {
+        let limit = RateLimit {
+            capacity: 2,
+            refill_per_minute: 6,
+        };
+        let interval = limit.refill_interval();
+        assert_eq!(interval, 10_000_000_000);
+
+        let full_at = limit.take_token(0, 0).unwrap();
+        let full_at = limit.take_token(full_at, 0).unwrap();
+        assert_eq!(full_at, 2 * interval);
+        assert_eq!(limit.take_token(full_at, 0), Err(interval));
+        assert_eq!(limit.take_token(full_at, interval / 2), Err(interval / 2));
+
+        // A token is added after the refill interval
+        let full_at = limit.take_token(full_at, interval).unwrap();
+        assert_eq!(limit.take_token(full_at, interval), Err(interval));
+
+        // The bucket doesn't overflow
+        let now = 100 * interval;
+        let full_at = limit.take_token(full_at, now).unwrap();
+        let full_at = limit.take_token(full_at, now).unwrap();
+        assert_eq!(limit.take_token(full_at, now), Err(interval));
+    }
+
+    #[test]
+    fn test_evict_buckets() {
+        let mut buckets = BTreeMap::from([(1, 10), (2, 50), (3, 30), (4, 40)]);
+        evict_buckets(&mut buckets, 20, 4);
+        assert_eq!(buckets.len(), 4);
+
+        // Full buckets are dropped first
+        evict_buckets(&mut buckets, 20, 3);
+        assert_eq!(buckets, BTreeMap::from([(2, 50), (3, 30), (4, 40)]));
+
+        // Then the ones closest to being full
+        evict_buckets(&mut buckets, 20, 1);
+        assert_eq!(buckets, BTreeMap::from([(2, 50)]));
+    }
+
+    #[test]
+    fn test_max_refill_rate() {
+        let limit = RateLimit {
+            capacity: 1,
+            refill_per_minute: NANOS_PER_MINUTE,
+        };
+        assert_eq!(limit.refill_interval(), 1);
+    }
+}
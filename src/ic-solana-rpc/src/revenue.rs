@@ -0,0 +1,109 @@
+//! Cycles earned by the owners of RPC providers.
+//!
+//! Part of the cycles charged for a request served by a registered provider is credited to the
+//! provider's owner, who can later withdraw the accumulated cycles to a canister.
+
+use candid::Principal;
+use ic_canister_log::log;
+use ic_cdk::api::management_canister::main::{deposit_cycles, CanisterIdRecord};
+use ic_solana::{
+    add_metric,
+    logs::INFO,
+    rpc_client::{RpcError, RpcResult},
+};
+
+use crate::{
+    constants::{CANISTER_OVERHEAD, MINIMUM_WITHDRAWAL_CYCLES, NODES_IN_SUBNET, PROVIDER_REVENUE_SHARE_PERCENT},
+    providers::ProviderId,
+    state::{mutate_state, read_state},
+    types::PrincipalStorable,
+};
+
+/// Returns the share of `cycles_charged` credited to the provider serving a request.
+///
+/// The share is taken from the canister overhead, the part of the charge which isn't spent on the
+/// HTTP outcall itself.
+pub fn provider_share(cycles_charged: u128) -> u128 {
+    let overhead = CANISTER_OVERHEAD * NODES_IN_SUBNET as u128;
+    cycles_charged.min(overhead) * PROVIDER_REVENUE_SHARE_PERCENT / 100
+}
+
+pub fn get_accumulated_cycles(owner: Principal) -> u128 {
+    read_state(|s| s.accumulated_cycles.get(&PrincipalStorable(owner)).unwrap_or_default())
+}
+
+fn add_accumulated_cycles(owner: Principal, cycles: u128) {
+    mutate_state(|s| {
+        let owner = PrincipalStorable(owner);
+        let balance = s.accumulated_cycles.get(&owner).unwrap_or_default();
+        s.accumulated_cycles.insert(owner, balance.saturating_add(cycles));
+    });
+}
+
+/// Credits the owner of the registered provider `provider_id` with its share of the cycles charged
+/// for a request.
+pub fn credit_provider(provider_id: &str, cycles_charged: u128) {
+    let cycles = provider_share(cycles_charged);
+    if cycles == 0 {
+        return;
+    }
+    let owner = read_state(|s| {
+        s.rpc_providers
+            .get(&ProviderId::new(provider_id))
+            .map(|provider| provider.owner)
+    });
+    if let Some(owner) = owner {
+        add_accumulated_cycles(owner, cycles);
+    }
+}
+
+/// Sends all cycles accumulated by `caller` to `canister` and returns the amount sent.
+pub async fn do_withdraw_accumulated_cycles(caller: Principal, canister: Principal) -> RpcResult<u128> {
+    let cycles = get_accumulated_cycles(caller);
+    if cycles < MINIMUM_WITHDRAWAL_CYCLES {
+        return Err(RpcError::Text(format!(
+            "Insufficient accumulated cycles: available {}, minimum withdrawal {}.",
+            cycles, MINIMUM_WITHDRAWAL_CYCLES
+        )));
+    }
+
+    // Debit before the call, so that the cycles can't be withdrawn twice meanwhile
+    mutate_state(|s| s.accumulated_cycles.remove(&PrincipalStorable(caller)));
+    log!(
+        INFO,
+        "[{}] Withdrawing {} accumulated cycles to {}",
+        caller,
+        cycles,
+        canister
+    );
+
+    match deposit_cycles(CanisterIdRecord { canister_id: canister }, cycles).await {
+        Ok(()) => {
+            add_metric!(cycles_withdrawn, cycles);
+            Ok(cycles)
+        }
+        Err((code, message)) => {
+            add_accumulated_cycles(caller, cycles);
+            Err(RpcError::Text(format!(
+                "Failed to deposit cycles to {}: {:?} {}",
+                canister, code, message
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_provider_share() {
+        let overhead = CANISTER_OVERHEAD * NODES_IN_SUBNET as u128;
+        assert_eq!(provider_share(0), 0);
+        assert_eq!(provider_share(1_000), 1_000 * PROVIDER_REVENUE_SHARE_PERCENT / 100);
+        assert_eq!(
+            provider_share(overhead * 10),
+            overhead * PROVIDER_REVENUE_SHARE_PERCENT / 100
+        );
+    }
+}
@@ -1,11 +1,17 @@
 use std::cell::RefCell;
 
 use candid::{CandidType, Deserialize, Principal};
-use ic_solana::types::Cluster;
+use ic_solana::{rpc_client::RpcCluster, types::Cluster};
 
 use crate::{
     auth::{Auth, AuthSet},
-    memory::{init_auth_memory, init_providers_memory, AuthMemory, ProvidersMemory},
+    memory::{
+        init_accumulated_cycles_memory, init_auth_memory, init_cache_expiry_memory, init_cache_memory,
+        init_free_rpc_usage_memory, init_hosts_allowlist_memory, init_hosts_blocklist_memory,
+        init_provider_health_memory, init_providers_memory, init_rate_limits_memory, AccumulatedCyclesMemory,
+        AuthMemory, CacheExpiryMemory, CacheMemory, FreeRpcUsageMemory, HostsMemory, ProviderHealthMemory,
+        ProvidersMemory, RateLimitsMemory,
+    },
     providers::{ProviderId, RpcProvider},
     types::PrincipalStorable,
 };
@@ -14,6 +20,14 @@ thread_local! {
     pub static STATE: RefCell<Option<State>> = RefCell::new(Some(State {
         auth: init_auth_memory(),
         rpc_providers: init_providers_memory(),
+        provider_health: init_provider_health_memory(),
+        response_cache: init_cache_memory(),
+        cache_expiry: init_cache_expiry_memory(),
+        hosts_blocklist: init_hosts_blocklist_memory(),
+        hosts_allowlist: init_hosts_allowlist_memory(),
+        accumulated_cycles: init_accumulated_cycles_memory(),
+        free_rpc_usage: init_free_rpc_usage_memory(),
+        rate_limits: init_rate_limits_memory(),
         is_demo_active: false,
     }));
 }
@@ -28,19 +42,27 @@ pub struct InitArgs {
 pub struct State {
     pub auth: AuthMemory,
     pub rpc_providers: ProvidersMemory,
+    pub provider_health: ProviderHealthMemory,
+    pub response_cache: CacheMemory,
+    pub cache_expiry: CacheExpiryMemory,
+    pub hosts_blocklist: HostsMemory,
+    pub hosts_allowlist: HostsMemory,
+    pub accumulated_cycles: AccumulatedCyclesMemory,
+    pub free_rpc_usage: FreeRpcUsageMemory,
+    pub rate_limits: RateLimitsMemory,
     pub is_demo_active: bool,
-    // pub hosts_blocklist: Vec<String>,
 }
 
 impl State {
     fn init_default_providers(providers: &mut ProvidersMemory) {
-        for cluster in [Cluster::Mainnet, Cluster::Testnet, Cluster::Devnet] {
+        for cluster in [RpcCluster::Mainnet, RpcCluster::Testnet, RpcCluster::Devnet] {
             providers.insert(
-                ProviderId(cluster.to_string()),
+                ProviderId(Cluster::from(cluster).to_string()),
                 RpcProvider {
-                    url: cluster.url().to_string(),
+                    url: Cluster::from(cluster).url().to_string(),
                     owner: ic_cdk::caller(),
                     auth: None,
+                    cluster: Some(cluster),
                 },
             );
         }
@@ -70,8 +92,15 @@ impl From<InitArgs> for State {
             Self {
                 auth,
                 rpc_providers,
+                provider_health: s.provider_health,
+                response_cache: s.response_cache,
+                cache_expiry: s.cache_expiry,
+                hosts_blocklist: s.hosts_blocklist,
+                hosts_allowlist: s.hosts_allowlist,
+                accumulated_cycles: s.accumulated_cycles,
+                free_rpc_usage: s.free_rpc_usage,
+                rate_limits: s.rate_limits,
                 is_demo_active: value.demo.unwrap_or(false),
-                // hosts_blocklist: value.hosts_blocklist.unwrap_or_default(),
             }
         })
     }
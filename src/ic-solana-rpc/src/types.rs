@@ -1,9 +1,12 @@
 use std::borrow::Cow;
 
 use candid::{CandidType, Deserialize, Principal};
+use ic_solana::rpc_client::RpcCluster;
 use ic_stable_structures::{storable::Bound, Storable};
 use serde::Serialize;
 
+use crate::constants::HOSTNAME_MAX_SIZE;
+
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct PrincipalStorable(pub Principal);
 
@@ -22,6 +25,24 @@ impl Storable for PrincipalStorable {
     };
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HostnameStorable(pub String);
+
+impl Storable for HostnameStorable {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        self.0.to_bytes()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(String::from_bytes(bytes))
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: HOSTNAME_MAX_SIZE,
+        is_fixed_size: false,
+    };
+}
+
 #[derive(Debug, CandidType, Deserialize, Serialize)]
 pub struct SendTransactionRequest {
     pub instructions: Vec<String>,
@@ -44,6 +65,8 @@ pub struct RegisterProviderArgs {
     pub url: String,
     /// Optional authentication
     pub auth: Option<RpcAuth>,
+    /// Cluster the endpoint serves, required to be picked by `RpcServices::Healthiest`
+    pub cluster: Option<RpcCluster>,
 }
 
 #[derive(Clone, CandidType, Deserialize)]
@@ -54,4 +77,6 @@ pub struct UpdateProviderArgs {
     pub url: Option<String>,
     /// Optional authentication
     pub auth: Option<RpcAuth>,
+    /// Cluster the endpoint serves
+    pub cluster: Option<RpcCluster>,
 }
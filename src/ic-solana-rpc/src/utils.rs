@@ -6,7 +6,7 @@ use ic_solana::{
 };
 use url::Host;
 
-use crate::constants::RPC_HOSTS_BLOCKLIST;
+use crate::{constants::RPC_HOSTS_BLOCKLIST, hosts::is_host_allowed};
 
 pub fn hostname_from_url(url: &str) -> Option<String> {
     url::Url::parse(url).ok().and_then(|url| match url.host() {
@@ -22,7 +22,7 @@ pub fn hostname_from_url(url: &str) -> Option<String> {
 }
 
 pub fn validate_hostname(hostname: &str) -> Result<(), &'static str> {
-    if RPC_HOSTS_BLOCKLIST.contains(&hostname) {
+    if RPC_HOSTS_BLOCKLIST.contains(&hostname) || !is_host_allowed(hostname) {
         Err("Hostname not allowed")
     } else {
         Ok(())
@@ -7,6 +7,9 @@ use ic_solana::{
 };
 use ic_solana_rpc::{
     auth::Auth,
+    free_rpc::{FreeRpcQuota, FreeRpcUsage},
+    health::ProviderHealth,
+    rate_limit::RateLimit,
     state::InitArgs,
     types::{RegisterProviderArgs, UpdateProviderArgs},
 };
@@ -131,6 +134,10 @@ impl SolanaRpcSetup {
         self.setup.call_query("getProviders", ())
     }
 
+    pub fn get_provider_health(&self, id: &str) -> Option<ProviderHealth> {
+        self.setup.call_query("getProviderHealth", (id,))
+    }
+
     pub fn register_provider(&self, args: RegisterProviderArgs) -> CallFlow<()> {
         self.setup.call_update("registerProvider", (args,))
     }
@@ -155,4 +162,52 @@ impl SolanaRpcSetup {
     pub fn deauthorize(&self, principal: Principal, auth: Auth) -> CallFlow<bool> {
         self.setup.call_update("deauthorize", (principal, auth))
     }
+
+    pub fn get_rate_limits(&self) -> Vec<(Option<String>, RateLimit)> {
+        self.setup.call_query("getRateLimits", ())
+    }
+
+    pub fn set_rate_limit(&self, method: Option<&str>, limit: Option<RateLimit>) -> CallFlow<()> {
+        self.setup.call_update("setRateLimit", (method, limit))
+    }
+
+    pub fn get_free_rpc_usage(&self, principal: Principal) -> Option<FreeRpcUsage> {
+        self.setup.call_query("getFreeRpcUsage", (principal,))
+    }
+
+    pub fn set_free_rpc_quota(&self, principal: Principal, quota: FreeRpcQuota) -> CallFlow<()> {
+        self.setup.call_update("setFreeRpcQuota", (principal, quota))
+    }
+
+    pub fn get_accumulated_cycles(&self) -> u128 {
+        self.setup.call_query("getAccumulatedCycles", ())
+    }
+
+    pub fn withdraw_accumulated_cycles(&self, canister: Principal) -> CallFlow<RpcResult<u128>> {
+        self.setup.call_update("withdrawAccumulatedCycles", (canister,))
+    }
+
+    pub fn get_blocked_hosts(&self) -> Vec<String> {
+        self.setup.call_query("getBlockedHosts", ())
+    }
+
+    pub fn block_hosts(&self, hosts: &[&str]) -> CallFlow<()> {
+        self.setup.call_update("blockHosts", (hosts,))
+    }
+
+    pub fn unblock_hosts(&self, hosts: &[&str]) -> CallFlow<()> {
+        self.setup.call_update("unblockHosts", (hosts,))
+    }
+
+    pub fn get_allowed_hosts(&self) -> Vec<String> {
+        self.setup.call_query("getAllowedHosts", ())
+    }
+
+    pub fn allow_hosts(&self, hosts: &[&str]) -> CallFlow<()> {
+        self.setup.call_update("allowHosts", (hosts,))
+    }
+
+    pub fn disallow_hosts(&self, hosts: &[&str]) -> CallFlow<()> {
+        self.setup.call_update("disallowHosts", (hosts,))
+    }
 }
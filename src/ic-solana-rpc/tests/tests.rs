@@ -2,10 +2,11 @@ mod setup;
 
 use std::collections::HashMap;
 
+use candid::Principal;
 use ic_solana::{
-    metrics::{MetricRpcHost, Metrics},
+    metrics::{MetricMethod, MetricPrincipal, MetricRpcHost, Metrics},
     request::RpcRequest,
-    rpc_client::{RpcConfig, RpcResult, RpcServices},
+    rpc_client::{RpcApi, RpcCluster, RpcConfig, RpcError, RpcResult, RpcServices},
     types::{
         tagged::{
             EncodedConfirmedTransactionWithStatusMeta, RpcKeyedAccount, RpcSimulateTransactionResult,
@@ -19,7 +20,14 @@ use ic_solana::{
         TransactionDetails, TransactionStatus, UiDataSliceConfig, UiTokenAmount, UiTransactionEncoding,
     },
 };
-use ic_solana_rpc::{auth::Auth, state::InitArgs, types::RegisterProviderArgs};
+use ic_solana_rpc::{
+    auth::Auth,
+    constants::MINIMUM_WITHDRAWAL_CYCLES,
+    free_rpc::FreeRpcQuota,
+    rate_limit::RateLimit,
+    state::InitArgs,
+    types::{RegisterProviderArgs, UpdateProviderArgs},
+};
 use test_utils::{MockOutcallBuilder, TestSetup};
 
 use crate::setup::{mock_update, SolanaRpcSetup, MOCK_RAW_TX};
@@ -666,6 +674,40 @@ fn test_send_transaction() {
     );
 }
 
+#[test]
+fn test_send_and_confirm_transaction() {
+    let res = SolanaRpcSetup::default()
+        .call_update::<_, RpcResult<TransactionStatus>>(
+            "sol_sendAndConfirmTransaction",
+            (RpcServices::Mainnet, (), MOCK_RAW_TX, (), (), 3090u64),
+        )
+        .mock_http_once(MockOutcallBuilder::new(
+            200,
+            r#"{"jsonrpc":"2.0","result":"2id3YC2jK9G5Wo2phDx4gJVAew8DcY5NAojnVuao8rkxwPYPe8cSwE5GzhEgJA2y8fVjDEo6iR6ykBvDxrTQrtpb","id":1}"#,
+        ))
+        .mock_http_once(MockOutcallBuilder::new(200, r#"{"jsonrpc":"2.0","result":3000,"id":2}"#))
+        .mock_http_once(MockOutcallBuilder::new(
+            200,
+            r#"{"jsonrpc":"2.0","result":{"context":{"slot":82},"value":[{"slot":48,"confirmations":null,"err":null,"status":{"Ok":null},"confirmationStatus":"finalized"}]},"id":3}"#,
+        ))
+        .wait()
+        .unwrap();
+
+    assert_eq!(res.slot, 48);
+    assert!(res.err.is_none());
+}
+
+#[test]
+fn should_not_send_and_confirm_invalid_transaction() {
+    let res = SolanaRpcSetup::default()
+        .call_update::<_, RpcResult<TransactionStatus>>(
+            "sol_sendAndConfirmTransaction",
+            (RpcServices::Mainnet, (), "invalid", (), (), 3090u64),
+        )
+        .wait();
+    assert!(matches!(res, Err(RpcError::ParseError(_))));
+}
+
 #[test]
 fn test_simulate_transaction() {
     let res = mock_update::<_, RpcSimulateTransactionResult>(
@@ -775,6 +817,7 @@ fn should_allow_manager_to_register_and_unregister_providers() {
             id: provider_id.clone(),
             url: Cluster::Mainnet.url().into(),
             auth: None,
+            cluster: None,
         })
         .wait();
     let providers = setup.get_providers();
@@ -803,6 +846,7 @@ fn should_allow_caller_with_access_register_provider() {
             id: provider_id.clone(),
             url: Cluster::Mainnet.url().into(),
             auth: None,
+            cluster: None,
         })
         .wait();
     let providers = setup.get_providers();
@@ -824,6 +868,7 @@ fn should_not_allow_caller_without_access_to_register_provider() {
             id: "test_mainnet1".to_string(),
             url: Cluster::Mainnet.url().into(),
             auth: None,
+            cluster: None,
         })
         .wait();
 }
@@ -922,6 +967,7 @@ fn upgrade_should_keep_state() {
             id: "test_mainnet1".to_string(),
             url: Cluster::Mainnet.url().into(),
             auth: None,
+            cluster: None,
         })
         .wait();
 
@@ -936,3 +982,468 @@ fn upgrade_should_keep_state() {
     let providers = setup.get_providers();
     assert!(providers.contains(&"test_mainnet1".to_string()));
 }
+
+#[test]
+fn should_send_requests_to_healthiest_providers() {
+    let setup = SolanaRpcSetup::default();
+    let provider_url = "https://mainnet.example-rpc.com";
+    setup
+        .clone()
+        .as_controller()
+        .register_provider(RegisterProviderArgs {
+            id: "test_mainnet1".to_string(),
+            url: provider_url.to_string(),
+            auth: None,
+            cluster: Some(RpcCluster::Mainnet),
+        })
+        .wait();
+
+    let source = || RpcServices::Healthiest {
+        cluster: RpcCluster::Mainnet,
+        count: 1,
+    };
+    let calls = |id: &str| {
+        setup
+            .get_provider_health(id)
+            .map(|health| (health.successes, health.errors))
+    };
+
+    // Neither provider has been called yet, so the first one is picked
+    let _ = setup
+        .request(source(), "getHealth", "", 1000)
+        .mock_http(MockOutcallBuilder::new(500, "").with_url(Cluster::Mainnet.url()))
+        .wait();
+    assert_eq!(calls("mainnet"), Some((0, 1)));
+    assert_eq!(calls("test_mainnet1"), None);
+
+    // The failing provider is skipped
+    let result = setup
+        .request(source(), "getHealth", "", 1000)
+        .mock_http(MockOutcallBuilder::new(200, r#"{"jsonrpc":"2.0","result":"ok","id":1}"#).with_url(provider_url))
+        .wait();
+    assert!(result.is_ok());
+    assert_eq!(calls("mainnet"), Some((0, 1)));
+    assert_eq!(calls("test_mainnet1"), Some((1, 0)));
+
+    // Custom APIs are not attributed to the provider registered with the same URL
+    let _ = setup
+        .request(
+            RpcServices::Custom(vec![RpcApi::new(provider_url)]),
+            "getHealth",
+            "",
+            1000,
+        )
+        .mock_http(MockOutcallBuilder::new(500, "").with_url(provider_url))
+        .wait();
+    assert_eq!(calls("test_mainnet1"), Some((1, 0)));
+
+    setup
+        .clone()
+        .as_controller()
+        .unregister_provider("test_mainnet1")
+        .wait();
+    assert_eq!(calls("test_mainnet1"), None);
+}
+
+#[test]
+fn should_reject_healthiest_providers_without_candidates() {
+    let setup = SolanaRpcSetup::default();
+    let request = |cluster: RpcCluster, count: u8| {
+        setup
+            .request(RpcServices::Healthiest { cluster, count }, "getHealth", "", 1000)
+            .wait()
+    };
+
+    assert_eq!(
+        request(RpcCluster::Mainnet, 0),
+        Err(RpcError::Text("The number of providers must be positive".to_string()))
+    );
+    assert_eq!(
+        request(RpcCluster::Localnet, 1),
+        Err(RpcError::Text(
+            "No providers registered for cluster Localnet".to_string()
+        ))
+    );
+}
+
+#[test]
+fn should_serve_immutable_responses_from_cache() {
+    let setup = SolanaRpcSetup::default();
+    let genesis_hash =
+        |source: RpcServices| setup.call_update::<_, RpcResult<String>>("sol_getGenesisHash", (source, ()));
+    let response = r#"{"jsonrpc":"2.0","result":"5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d","id":1}"#;
+
+    let fetched = genesis_hash(RpcServices::Mainnet)
+        .mock_http(MockOutcallBuilder::new(200, response))
+        .wait();
+    assert_eq!(fetched, Ok("5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d".to_string()));
+
+    // Served without an HTTP outcall
+    assert_eq!(genesis_hash(RpcServices::Mainnet).wait(), fetched);
+
+    // Responses are cached per source
+    genesis_hash(RpcServices::Devnet)
+        .mock_http(MockOutcallBuilder::new(200, response))
+        .wait()
+        .unwrap();
+
+    let metrics = setup.get_metrics();
+    let method = || RpcRequest::GetGenesisHash.into();
+    assert_eq!(metrics.cache_hits, HashMap::from([(method(), 1)]));
+    assert_eq!(metrics.cache_misses, HashMap::from([(method(), 2)]));
+}
+
+#[test]
+fn should_not_serve_stale_provider_responses_from_cache() {
+    let setup = SolanaRpcSetup::default();
+    let owner = TestSetup::principal(5);
+    let register = |id: &str, url: &str, cluster: RpcCluster| {
+        setup
+            .clone()
+            .as_controller()
+            .authorize(owner, Auth::RegisterProvider)
+            .wait();
+        setup
+            .clone()
+            .as_caller(owner)
+            .register_provider(RegisterProviderArgs {
+                id: id.to_string(),
+                url: url.to_string(),
+                auth: None,
+                cluster: Some(cluster),
+            })
+            .wait();
+    };
+    let genesis_hash = |id: &str| {
+        setup.call_update::<_, RpcResult<String>>(
+            "sol_getGenesisHash",
+            (RpcServices::Provider(vec![id.to_string()]), ()),
+        )
+    };
+    let response = r#"{"jsonrpc":"2.0","result":"5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d","id":1}"#;
+
+    // A local cluster can be reset at any time
+    register(
+        "test_localnet1",
+        "https://localnet.example-rpc.com",
+        RpcCluster::Localnet,
+    );
+    for _ in 0..2 {
+        assert!(genesis_hash("test_localnet1")
+            .mock_http_once(MockOutcallBuilder::new(200, response))
+            .wait()
+            .is_ok());
+    }
+
+    // Responses of the previous URL are dropped
+    register("test_devnet1", "https://devnet.example-rpc.com", RpcCluster::Devnet);
+    let fetched = genesis_hash("test_devnet1")
+        .mock_http_once(MockOutcallBuilder::new(200, response))
+        .wait();
+    assert_eq!(genesis_hash("test_devnet1").wait(), fetched);
+    setup
+        .clone()
+        .as_controller()
+        .update_provider(UpdateProviderArgs {
+            id: "test_devnet1".to_string(),
+            url: Some("https://devnet2.example-rpc.com".to_string()),
+            auth: None,
+            cluster: None,
+        })
+        .wait();
+    assert!(genesis_hash("test_devnet1")
+        .mock_http_once(MockOutcallBuilder::new(200, response).with_url("https://devnet2.example-rpc.com"))
+        .wait()
+        .is_ok());
+
+    // So are the responses of a cluster served by the provider
+    let cluster_genesis_hash =
+        || setup.call_update::<_, RpcResult<String>>("sol_getGenesisHash", (RpcServices::Mainnet, ()));
+    let fetched = cluster_genesis_hash()
+        .mock_http_once(MockOutcallBuilder::new(200, response))
+        .wait();
+    assert_eq!(cluster_genesis_hash().wait(), fetched);
+    setup
+        .clone()
+        .as_controller()
+        .update_provider(UpdateProviderArgs {
+            id: "mainnet".to_string(),
+            url: Some("https://mainnet2.example-rpc.com".to_string()),
+            auth: None,
+            cluster: None,
+        })
+        .wait();
+    assert!(cluster_genesis_hash()
+        .mock_http_once(MockOutcallBuilder::new(200, response).with_url("https://mainnet2.example-rpc.com"))
+        .wait()
+        .is_ok());
+}
+
+#[test]
+fn should_reject_requests_to_blocked_hosts() {
+    let setup = SolanaRpcSetup::default();
+    let host = "api.mainnet-beta.solana.com";
+    let get_health = |source: RpcServices| setup.request(source, "getHealth", "", 1000);
+    let response = r#"{"jsonrpc":"2.0","result":"ok","id":1}"#;
+
+    setup
+        .clone()
+        .as_controller()
+        .block_hosts(&[" API.Mainnet-Beta.solana.com "])
+        .wait();
+    assert_eq!(setup.get_blocked_hosts(), vec![host.to_string()]);
+
+    let expected = Err(RpcError::Text(format!("Disallowed RPC service host: {}", host)));
+    assert_eq!(get_health(RpcServices::Mainnet).wait(), expected);
+    assert_eq!(
+        get_health(RpcServices::Custom(vec![RpcApi::new(Cluster::Mainnet.url())])).wait(),
+        expected
+    );
+
+    setup.clone().as_controller().unblock_hosts(&[host]).wait();
+    assert!(setup.get_blocked_hosts().is_empty());
+    assert!(get_health(RpcServices::Mainnet)
+        .mock_http(MockOutcallBuilder::new(200, response))
+        .wait()
+        .is_ok());
+}
+
+#[test]
+fn should_only_allow_requests_to_allowed_hosts() {
+    let setup = SolanaRpcSetup::default();
+    let get_health = |source: RpcServices| setup.request(source, "getHealth", "", 1000);
+    let response = r#"{"jsonrpc":"2.0","result":"ok","id":1}"#;
+
+    setup
+        .clone()
+        .as_controller()
+        .allow_hosts(&["api.devnet.solana.com"])
+        .wait();
+    assert_eq!(setup.get_allowed_hosts(), vec!["api.devnet.solana.com".to_string()]);
+
+    assert!(get_health(RpcServices::Devnet)
+        .mock_http(MockOutcallBuilder::new(200, response))
+        .wait()
+        .is_ok());
+    assert_eq!(
+        get_health(RpcServices::Custom(vec![RpcApi::new("https://rpc.example.com")])).wait(),
+        Err(RpcError::Text(
+            "Disallowed RPC service host: rpc.example.com".to_string()
+        ))
+    );
+
+    setup
+        .clone()
+        .as_controller()
+        .disallow_hosts(&["api.devnet.solana.com"])
+        .wait();
+    assert!(setup.get_allowed_hosts().is_empty());
+}
+
+#[test]
+#[should_panic(expected = "Unauthorized")]
+fn should_not_allow_caller_without_access_to_block_hosts() {
+    SolanaRpcSetup::default()
+        .block_hosts(&["api.mainnet-beta.solana.com"])
+        .wait();
+}
+
+#[test]
+fn should_not_withdraw_below_minimum_accumulated_cycles() {
+    let setup = SolanaRpcSetup::default().as_caller(TestSetup::principal(3));
+    assert_eq!(setup.get_accumulated_cycles(), 0);
+    assert_eq!(
+        setup.withdraw_accumulated_cycles(TestSetup::principal(4)).wait(),
+        Err(RpcError::Text(format!(
+            "Insufficient accumulated cycles: available 0, minimum withdrawal {}.",
+            MINIMUM_WITHDRAWAL_CYCLES
+        )))
+    );
+    assert_eq!(setup.get_metrics().cycles_withdrawn, 0);
+}
+
+#[test]
+fn should_serve_free_rpc_requests_within_quota() {
+    let setup = SolanaRpcSetup::new(InitArgs {
+        demo: None,
+        managers: Some(vec![TestSetup::controller_id()]),
+    });
+    let principal = TestSetup::principal(3);
+    let get_health = || {
+        setup
+            .clone()
+            .as_caller(principal)
+            .request(RpcServices::Mainnet, "getHealth", "", 1000)
+    };
+    let response = r#"{"jsonrpc":"2.0","result":"ok","id":1}"#;
+
+    assert_eq!(setup.get_free_rpc_usage(principal), None);
+    setup.clone().as_controller().authorize(principal, Auth::FreeRpc).wait();
+    let quota = FreeRpcQuota {
+        requests_per_day: 1,
+        bytes_per_day: 1_000_000,
+    };
+    setup
+        .clone()
+        .as_controller()
+        .set_free_rpc_quota(principal, quota)
+        .wait();
+
+    // Served without attached cycles
+    assert!(get_health()
+        .mock_http(MockOutcallBuilder::new(200, response))
+        .wait()
+        .is_ok());
+    let usage = setup.get_free_rpc_usage(principal).unwrap();
+    assert_eq!((usage.quota, usage.requests), (quota, 1));
+    assert!(usage.bytes > 0);
+
+    // Charged once the quota is exceeded
+    assert!(matches!(
+        get_health().wait(),
+        Err(RpcError::Text(message)) if message.starts_with("Insufficient cycles")
+    ));
+
+    let metrics = setup.get_metrics();
+    let metric = || MetricPrincipal(principal.to_string());
+    assert_eq!(metrics.free_rpc_requests, HashMap::from([(metric(), 1)]));
+    assert_eq!(metrics.err_free_rpc_quota_exceeded, HashMap::from([(metric(), 1)]));
+}
+
+#[test]
+fn should_count_every_outcall_against_free_rpc_quota() {
+    let setup = SolanaRpcSetup::new(InitArgs {
+        demo: None,
+        managers: Some(vec![TestSetup::controller_id()]),
+    });
+    let principal = TestSetup::principal(3);
+    setup.clone().as_controller().authorize(principal, Auth::FreeRpc).wait();
+    setup
+        .clone()
+        .as_controller()
+        .set_free_rpc_quota(
+            principal,
+            FreeRpcQuota {
+                requests_per_day: 1,
+                bytes_per_day: 1_000_000,
+            },
+        )
+        .wait();
+
+    // Only the first outcall is free, the second one has to be paid for
+    let source = RpcServices::Custom(vec![
+        RpcApi::new(Cluster::Mainnet.url()),
+        RpcApi::new(Cluster::Devnet.url()),
+    ]);
+    assert!(setup
+        .clone()
+        .as_caller(principal)
+        .request(source, "getHealth", "", 1000)
+        .mock_http(MockOutcallBuilder::new(
+            200,
+            r#"{"jsonrpc":"2.0","result":"ok","id":1}"#
+        ))
+        .wait()
+        .is_err());
+
+    assert_eq!(setup.get_free_rpc_usage(principal).unwrap().requests, 1);
+    assert_eq!(
+        setup.get_metrics().err_free_rpc_quota_exceeded,
+        HashMap::from([(MetricPrincipal(principal.to_string()), 1)])
+    );
+}
+
+#[test]
+#[should_panic(expected = "Unauthorized")]
+fn should_not_allow_caller_without_access_to_set_free_rpc_quota() {
+    SolanaRpcSetup::default()
+        .set_free_rpc_quota(TestSetup::principal(3), FreeRpcQuota::default())
+        .wait();
+}
+
+#[test]
+fn should_rate_limit_callers() {
+    let setup = SolanaRpcSetup::default();
+    let get_health = |caller: Principal| {
+        setup
+            .clone()
+            .as_caller(caller)
+            .call_update::<_, RpcResult<String>>("sol_getHealth", (RpcServices::Mainnet, ()))
+    };
+    let response = r#"{"jsonrpc":"2.0","result":"ok","id":1}"#;
+    let limit = RateLimit {
+        capacity: 1,
+        refill_per_minute: 1,
+    };
+
+    setup
+        .clone()
+        .as_controller()
+        .set_rate_limit(Some("sol_getHealth"), Some(limit))
+        .wait();
+    assert_eq!(
+        setup.get_rate_limits(),
+        vec![(Some("sol_getHealth".to_string()), limit)]
+    );
+
+    let caller = TestSetup::principal(3);
+    assert!(get_health(caller)
+        .mock_http(MockOutcallBuilder::new(200, response))
+        .wait()
+        .is_ok());
+    assert!(matches!(
+        get_health(caller).wait(),
+        Err(RpcError::RateLimited { retry_after }) if retry_after > 0
+    ));
+
+    // Buckets are kept per caller
+    assert!(get_health(TestSetup::principal(4))
+        .mock_http(MockOutcallBuilder::new(200, response))
+        .wait()
+        .is_ok());
+
+    assert_eq!(
+        setup.get_metrics().err_rate_limited,
+        HashMap::from([(MetricMethod("sol_getHealth".to_string()), 1)])
+    );
+
+    setup
+        .clone()
+        .as_controller()
+        .set_rate_limit(Some("sol_getHealth"), None)
+        .wait();
+    assert!(setup.get_rate_limits().is_empty());
+    assert!(get_health(caller)
+        .mock_http(MockOutcallBuilder::new(200, response))
+        .wait()
+        .is_ok());
+}
+
+#[test]
+#[should_panic(expected = "Unauthorized")]
+fn should_not_allow_caller_without_access_to_set_rate_limit() {
+    SolanaRpcSetup::default()
+        .set_rate_limit(
+            None,
+            Some(RateLimit {
+                capacity: 1,
+                refill_per_minute: 1,
+            }),
+        )
+        .wait();
+}
+
+#[test]
+#[should_panic(expected = "The refill rate must be at most 60000000000 tokens per minute")]
+fn should_not_set_rate_limit_refilling_too_fast() {
+    SolanaRpcSetup::default()
+        .as_controller()
+        .set_rate_limit(
+            None,
+            Some(RateLimit {
+                capacity: 1,
+                refill_per_minute: 60_000_000_001,
+            }),
+        )
+        .wait();
+}
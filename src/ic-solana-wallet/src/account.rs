@@ -0,0 +1,63 @@
+use candid::{CandidType, Deserialize, Principal};
+use serde::Serialize;
+use serde_bytes::ByteBuf;
+
+use crate::error::{WalletError, WalletResult};
+
+/// Maximum length of a subaccount label, in bytes.
+pub const MAX_LABEL_LENGTH: usize = 64;
+
+/// Maximum number of accounts, including the default one, a principal can use.
+pub const MAX_ACCOUNTS: usize = 100;
+
+/// Identifies one of the Solana accounts derived for a principal.
+///
+/// Every principal has a default account, derived from the principal alone, and up to
+/// [`MAX_ACCOUNTS`] - 1 subaccounts, derived from the principal and either a number or a label.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Subaccount {
+    Index(u32),
+    Label(String),
+}
+
+impl Subaccount {
+    /// Checks that a label is at most [`MAX_LABEL_LENGTH`] bytes long.
+    pub fn validate(&self) -> WalletResult<()> {
+        match self {
+            Self::Label(label) if label.len() > MAX_LABEL_LENGTH => Err(WalletError::InvalidArgument(format!(
+                "subaccount label must be at most {MAX_LABEL_LENGTH} bytes long"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the derivation path component of the subaccount.
+    ///
+    /// The component is prefixed with the variant tag, so that a label can never derive the same
+    /// key as an index.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Index(index) => [&[0u8][..], &index.to_be_bytes()].concat(),
+            Self::Label(label) => [&[1u8][..], label.as_bytes()].concat(),
+        }
+    }
+}
+
+/// A Solana account the wallet has derived for a principal.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct WalletAccount {
+    pub subaccount: Option<Subaccount>,
+    pub address: String,
+}
+
+/// Returns the key derivation path of a principal's account.
+///
+/// The default account keeps the path `[principal]`, so its address does not change when
+/// subaccounts are used.
+pub fn derivation_path(owner: &Principal, subaccount: Option<&Subaccount>) -> Vec<ByteBuf> {
+    let mut path = vec![ByteBuf::from(owner.as_slice())];
+    if let Some(subaccount) = subaccount {
+        path.push(ByteBuf::from(subaccount.to_bytes()));
+    }
+    path
+}
@@ -0,0 +1,122 @@
+use candid::{
+    utils::{ArgumentDecoder, ArgumentEncoder},
+    Principal,
+};
+use ic_cdk::api::call::{call_with_payment128, msg_cycles_accept128, msg_cycles_available128, msg_cycles_refunded128};
+use ic_solana::{
+    constants::HEADER_SIZE_LIMIT,
+    request::RpcRequest,
+    rpc_client::{RpcConfig, RpcServices},
+    types::{RpcContextConfig, RpcSendTransactionConfig},
+};
+use serde::Serialize;
+
+use crate::error::{WalletError, WalletResult};
+
+/// Cycles the RPC canister requires to be attached to a request on top of its cost. They are not
+/// charged.
+///
+/// Mirrors `COLLATERAL_CYCLES_PER_NODE * NODES_IN_SUBNET` of the RPC canister.
+pub const RPC_COLLATERAL_CYCLES: u128 = 10_000_000 * 34;
+
+/// Request id used to estimate the payload size, the longest one the RPC client can generate.
+const ESTIMATE_REQUEST_ID: u64 = u64::MAX;
+
+/// Returns the number of providers the RPC canister sends a request to.
+fn num_providers(source: &RpcServices) -> u128 {
+    match source {
+        RpcServices::Provider(ids) => ids.len() as u128,
+        RpcServices::Custom(apis) => apis.len() as u128,
+        RpcServices::Healthiest { count, .. } => *count as u128,
+        _ => 1,
+    }
+}
+
+/// Estimates the cycles to attach to a call to the RPC canister, using its `requestCost` query.
+///
+/// `max_response_bytes` is the response size estimate the RPC client uses for `method`.
+pub async fn estimate_rpc_cost<P: Serialize>(
+    sol_canister: Principal,
+    source: &RpcServices,
+    config: Option<&RpcConfig>,
+    method: RpcRequest,
+    params: P,
+    max_response_bytes: u64,
+) -> WalletResult<u128> {
+    let payload = method.build_json(ESTIMATE_REQUEST_ID, params).to_string();
+    let max_response_bytes = config
+        .and_then(|config| config.response_size_estimate)
+        .unwrap_or(max_response_bytes + HEADER_SIZE_LIMIT);
+
+    let (cost,) = ic_cdk::call::<_, (u128,)>(sol_canister, "requestCost", (payload, max_response_bytes)).await?;
+
+    // Requests are free while the RPC canister is in demo mode
+    if cost == 0 {
+        return Ok(0);
+    }
+    Ok(cost * num_providers(source) + RPC_COLLATERAL_CYCLES)
+}
+
+/// Estimates the cycles to attach to `sol_getLatestBlockhash`.
+pub async fn latest_blockhash_cost(
+    sol_canister: Principal,
+    source: &RpcServices,
+    config: Option<&RpcConfig>,
+) -> WalletResult<u128> {
+    let params = (Option::<RpcContextConfig>::None,);
+    estimate_rpc_cost(
+        sol_canister,
+        source,
+        config,
+        RpcRequest::GetLatestBlockhash,
+        params,
+        156,
+    )
+    .await
+}
+
+/// Estimates the cycles to attach to `sol_sendTransaction` for a serialized transaction.
+pub async fn send_transaction_cost(
+    sol_canister: Principal,
+    source: &RpcServices,
+    config: Option<&RpcConfig>,
+    raw_transaction: &str,
+    params: Option<RpcSendTransactionConfig>,
+) -> WalletResult<u128> {
+    let params = (raw_transaction, params.unwrap_or_default());
+    estimate_rpc_cost(sol_canister, source, config, RpcRequest::SendTransaction, params, 156).await
+}
+
+/// Checks that the caller attached at least `required` cycles to their call.
+///
+/// Controllers fund the wallet, so their calls are paid from its balance.
+pub fn require_attached_cycles(required: u128) -> WalletResult<()> {
+    if ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Ok(());
+    }
+    let available = msg_cycles_available128();
+    if available < required {
+        return Err(WalletError::InsufficientCycles { available, required });
+    }
+    Ok(())
+}
+
+/// Calls the RPC canister with `cycles` attached from the wallet's balance.
+///
+/// The caller is charged the cycles the RPC canister kept, out of the cycles they attached to
+/// their call. Fails with [`WalletError::InsufficientCycles`] if the caller did not attach
+/// `cycles`. Attached cycles that are not charged are refunded to the caller when the wallet
+/// replies.
+pub async fn call_with_cycles<A: ArgumentEncoder, R: for<'a> ArgumentDecoder<'a>>(
+    sol_canister: Principal,
+    method: &str,
+    args: A,
+    cycles: u128,
+) -> WalletResult<R> {
+    require_attached_cycles(cycles)?;
+
+    let result = call_with_payment128(sol_canister, method, args, cycles).await;
+    msg_cycles_accept128(cycles.saturating_sub(msg_cycles_refunded128()));
+
+    Ok(result?)
+}
@@ -4,6 +4,7 @@ use std::{
 };
 
 use candid::{CandidType, Principal};
+use ic_crypto_ed25519::{DerivationIndex, DerivationPath as Ed25519DerivationPath, PublicKey};
 use ic_management_canister_types::{
     DerivationPath, SchnorrAlgorithm, SchnorrKeyId, SchnorrPublicKeyArgs, SchnorrPublicKeyResponse,
     SignWithSchnorrArgs, SignWithSchnorrReply,
@@ -11,9 +12,21 @@ use ic_management_canister_types::{
 use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
 
+use crate::{
+    cycles::require_attached_cycles,
+    error::{WalletError, WalletResult},
+};
+
 // https://internetcomputer.org/docs/current/references/t-sigs-how-it-works/#fees-for-the-t-schnorr-production-key
 pub const EDDSA_SIGN_COST: u128 = 26_153_846_153;
 
+/// Delay before fetching the canister's public key again after a failure, in seconds. It doubles
+/// after every failure, up to [`EDDSA_PUBLIC_KEY_MAX_RETRY_DELAY`].
+pub const EDDSA_PUBLIC_KEY_RETRY_DELAY: u64 = 5;
+
+/// Maximum delay between two attempts to fetch the canister's public key, in seconds.
+pub const EDDSA_PUBLIC_KEY_MAX_RETRY_DELAY: u64 = 60 * 60;
+
 #[derive(Debug, Clone, Deserialize, Serialize, CandidType)]
 pub enum SchnorrKey {
     TestKeyLocal,
@@ -48,7 +61,7 @@ impl FromStr for SchnorrKey {
 }
 
 /// Fetches the ed25519 public key from the schnorr canister.
-pub async fn eddsa_public_key(key: SchnorrKey, derivation_path: Vec<ByteBuf>) -> Vec<u8> {
+pub async fn eddsa_public_key(key: SchnorrKey, derivation_path: Vec<ByteBuf>) -> WalletResult<Vec<u8>> {
     let res: Result<(SchnorrPublicKeyResponse,), _> = ic_cdk::call(
         Principal::management_canister(),
         "schnorr_public_key",
@@ -63,12 +76,74 @@ pub async fn eddsa_public_key(key: SchnorrKey, derivation_path: Vec<ByteBuf>) ->
     )
     .await;
 
-    res.expect("Failed to fetch ed25519 public key").0.public_key
+    res.map(|(response,)| response.public_key)
+        .map_err(|(code, message)| WalletError::PublicKeyUnavailable(format!("{code:?}: {message}")))
+}
+
+/// The canister's ed25519 public key and chain code.
+///
+/// The public key of any derivation path can be derived from it locally, without calling the
+/// schnorr canister.
+#[derive(Debug, Clone, Deserialize, Serialize, CandidType)]
+pub struct EddsaPublicKey {
+    pub public_key: ByteBuf,
+    pub chain_code: ByteBuf,
+}
+
+impl EddsaPublicKey {
+    /// Derives the ed25519 public key of the given derivation path, as `schnorr_public_key` would
+    /// return it for that path.
+    pub fn derive(&self, derivation_path: &[ByteBuf]) -> [u8; 32] {
+        let public_key = PublicKey::deserialize_raw(&self.public_key).expect("Invalid ed25519 public key");
+        let chain_code = <[u8; 32]>::try_from(self.chain_code.as_slice()).expect("Invalid chain code");
+        let path = Ed25519DerivationPath::new(
+            derivation_path
+                .iter()
+                .map(|index| DerivationIndex(index.to_vec()))
+                .collect(),
+        );
+        public_key
+            .derive_subkey_with_chain_code(&path, &chain_code)
+            .0
+            .serialize_raw()
+    }
+}
+
+/// Fetches the canister's ed25519 public key and chain code from the schnorr canister.
+pub async fn eddsa_canister_public_key(key: SchnorrKey) -> WalletResult<EddsaPublicKey> {
+    let res: Result<(SchnorrPublicKeyResponse,), _> = ic_cdk::call(
+        Principal::management_canister(),
+        "schnorr_public_key",
+        (SchnorrPublicKeyArgs {
+            canister_id: None,
+            derivation_path: DerivationPath::new(vec![]),
+            key_id: SchnorrKeyId {
+                algorithm: SchnorrAlgorithm::Ed25519,
+                name: key.to_string(),
+            },
+        },),
+    )
+    .await;
+
+    let (response,) =
+        res.map_err(|(code, message)| WalletError::PublicKeyUnavailable(format!("{code:?}: {message}")))?;
+    Ok(EddsaPublicKey {
+        public_key: ByteBuf::from(response.public_key),
+        chain_code: ByteBuf::from(response.chain_code),
+    })
 }
 
 /// Signs a message with an ed25519 key.
-pub async fn sign_with_eddsa(key: SchnorrKey, derivation_path: Vec<ByteBuf>, message: Vec<u8>) -> Vec<u8> {
-    ic_cdk::api::call::msg_cycles_accept128(EDDSA_SIGN_COST);
+///
+/// The signing fee is paid from the canister's balance and charged to the caller once the message
+/// is signed, out of the cycles they attached. Fails with [`WalletError::InsufficientCycles`] if
+/// the caller did not attach the fee.
+pub async fn sign_with_eddsa(
+    key: SchnorrKey,
+    derivation_path: Vec<ByteBuf>,
+    message: Vec<u8>,
+) -> WalletResult<Vec<u8>> {
+    require_attached_cycles(EDDSA_SIGN_COST)?;
 
     let res: Result<(SignWithSchnorrReply,), _> = ic_cdk::api::call::call_with_payment(
         Principal::management_canister(),
@@ -85,5 +160,7 @@ pub async fn sign_with_eddsa(key: SchnorrKey, derivation_path: Vec<ByteBuf>, mes
     )
     .await;
 
-    res.expect("Failed to sign with ed25519").0.signature
+    let (reply,) = res.map_err(|(code, message)| WalletError::SigningFailed(format!("{code:?}: {message}")))?;
+    ic_cdk::api::call::msg_cycles_accept128(EDDSA_SIGN_COST);
+    Ok(reply.signature)
 }
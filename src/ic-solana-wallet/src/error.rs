@@ -0,0 +1,55 @@
+use candid::{CandidType, Deserialize};
+use ic_cdk::api::call::RejectionCode;
+use ic_solana::rpc_client::RpcError;
+use thiserror::Error;
+
+pub type WalletResult<T> = Result<T, WalletError>;
+
+/// Errors returned by the wallet canister.
+#[derive(Debug, Clone, PartialEq, Eq, Error, Deserialize, CandidType)]
+pub enum WalletError {
+    #[error("Invalid transaction: {0}")]
+    InvalidTransaction(String),
+
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
+
+    #[error("Signing failed: {0}")]
+    SigningFailed(String),
+
+    #[error("Wallet public key is not available: {0}")]
+    PublicKeyUnavailable(String),
+
+    #[error("Insufficient cycles: {available} available, {required} required")]
+    InsufficientCycles { available: u128, required: u128 },
+
+    #[error("Spending policy violation: {0}")]
+    SpendingPolicyViolation(String),
+
+    #[error("RPC error: {0}")]
+    RpcError(RpcError),
+
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("Invalid proposal: {0}")]
+    InvalidProposal(String),
+}
+
+impl From<RpcError> for WalletError {
+    fn from(err: RpcError) -> Self {
+        WalletError::RpcError(err)
+    }
+}
+
+impl From<(RejectionCode, String)> for WalletError {
+    fn from(err: (RejectionCode, String)) -> Self {
+        WalletError::RpcError(err.into())
+    }
+}
@@ -1,3 +1,11 @@
+pub mod account;
+pub mod cycles;
 pub mod eddsa;
+pub mod error;
+pub mod memory;
+pub mod nonce;
+pub mod policy;
+pub mod shared;
+pub mod siws;
 pub mod state;
 pub mod utils;
@@ -1,65 +1,165 @@
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 
-use candid::candid_method;
-use ic_cdk::update;
+use candid::{candid_method, Principal};
+use ic_canister_log::log;
+use ic_cdk::{query, update};
 use ic_solana::{
-    rpc_client::{RpcConfig, RpcResult, RpcServices},
-    types::{BlockHash, Pubkey, RpcSendTransactionConfig, Transaction},
+    logs::INFO,
+    offchain_message::OffchainMessage,
+    rpc_client::{RpcConfig, RpcError, RpcResult, RpcServices},
+    siws::SiwsMessage,
+    system_instruction::{create_nonce_account_with_seed, withdraw_nonce_account, NONCE_STATE_SIZE},
+    types::{
+        BlockHash, Message, Pubkey, RpcBlockhash, RpcContextConfig, RpcSendTransactionConfig, Signature, Transaction,
+    },
 };
 use ic_solana_wallet::{
-    eddsa::{eddsa_public_key, sign_with_eddsa},
-    state::{read_state, InitArgs, State},
-    utils::validate_caller_not_anonymous,
+    account::{derivation_path, Subaccount, WalletAccount},
+    cycles::{call_with_cycles, latest_blockhash_cost, send_transaction_cost},
+    eddsa::{
+        eddsa_canister_public_key, sign_with_eddsa, EDDSA_PUBLIC_KEY_MAX_RETRY_DELAY, EDDSA_PUBLIC_KEY_RETRY_DELAY,
+        EDDSA_SIGN_COST,
+    },
+    error::{WalletError, WalletResult},
+    nonce::{get_nonce, nonce_account_address, NONCE_ACCOUNT_SEED},
+    policy::{day, SpendingPolicy},
+    shared::{
+        shared_wallet_derivation_path, Proposal, ProposalInfo, ProposalStatus, SharedWallet, DEFAULT_PROPOSAL_TTL,
+        PRUNE_PROPOSALS_INTERVAL,
+    },
+    siws::{prepare_siws_message, SignedSiwsMessage},
+    state::{mutate_state, read_state, InitArgs, State},
+    utils::{require_controller, validate_caller_not_anonymous},
 };
 use serde_bytes::ByteBuf;
 
 /// Returns the public key of the Solana wallet associated with the caller.
 ///
+/// The account is listed by `accounts` once it has signed a message or transaction.
+///
+/// # Parameters
+///
+/// - `subaccount` (`Option<Subaccount>`): The caller's subaccount. Defaults to the caller's main
+///   account.
+///
 /// # Returns
 ///
-/// - `String`: The Solana public key as a string.
-#[update]
-#[candid_method]
-pub async fn address() -> String {
-    let caller = validate_caller_not_anonymous();
-    let key_name = read_state(|s| s.schnorr_key.to_owned());
-    let derived_path = vec![ByteBuf::from(caller.as_slice())];
-    let pk = eddsa_public_key(key_name, derived_path).await;
-    Pubkey::try_from(pk.as_slice()).expect("Invalid public key").to_string()
+/// - `WalletResult<String>`: The Solana public key as a string, or a `WalletError` if the wallet's
+///   public key has not been fetched yet.
+#[query]
+#[candid_method(query)]
+pub fn address(subaccount: Option<Subaccount>) -> WalletResult<String> {
+    let caller = validate_caller_not_anonymous()?;
+    if let Some(subaccount) = &subaccount {
+        subaccount.validate()?;
+    }
+    derive_caller_pubkey(caller, subaccount.as_ref())
+        .map(|pubkey| pubkey.to_string())
+        .ok_or_else(|| WalletError::PublicKeyUnavailable("not initialized yet".to_string()))
+}
+
+/// Returns the accounts the caller has used so far.
+#[query]
+#[candid_method(query)]
+pub fn accounts() -> Vec<WalletAccount> {
+    // The anonymous principal can't use any account
+    read_state(|s| s.accounts(&ic_cdk::caller()))
 }
 
 /// Signs a provided message using the caller's Eddsa key.
 ///
+/// The message is wrapped in Solana's off-chain message envelope before signing, so the signature
+/// can't be passed off as the signature of a transaction. Verify it against
+/// `OffchainMessage::serialize`.
+///
 /// # Parameters
 ///
 /// - `message` (`String`): The message to be signed.
+/// - `subaccount` (`Option<Subaccount>`): The caller's subaccount to sign with.
+/// - `raw` (`Option<bool>`): If `true`, the message bytes are signed as they are. Only use this for
+///   messages whose format can't be mistaken for a transaction.
 ///
 /// # Returns
 ///
-/// - `RpcResult<String>`: The signature as a base58 encoded string on success, or an `RpcError` on
-///   failure.
+/// - `WalletResult<Vec<u8>>`: The signature on success, or a `WalletError` on failure.
 #[update(name = "signMessage")]
 #[candid_method(query, rename = "signMessage")]
-pub async fn sign_message(message: String) -> Vec<u8> {
-    let caller = validate_caller_not_anonymous();
+pub async fn sign_message(message: String, subaccount: Option<Subaccount>, raw: Option<bool>) -> WalletResult<Vec<u8>> {
+    let caller = validate_caller_not_anonymous()?;
+
+    let data = if raw.unwrap_or_default() {
+        message.into_bytes()
+    } else {
+        OffchainMessage::new(0, message.as_bytes())
+            .map_err(|err| WalletError::InvalidMessage(err.to_string()))?
+            .serialize()
+    };
+
+    let pubkey = caller_pubkey(caller, subaccount.as_ref()).await?;
     let key_name = read_state(|s| s.schnorr_key.to_owned());
-    let derived_path = vec![ByteBuf::from(caller.as_slice())];
-    sign_with_eddsa(key_name, derived_path, message.as_bytes().into()).await
+    let derived_path = derivation_path(&caller, subaccount.as_ref());
+    let signature = sign_with_eddsa(key_name, derived_path, data).await?;
+    record_account(caller, subaccount.as_ref(), pubkey);
+    Ok(signature)
+}
+
+/// Signs a Sign-In With Solana message with the caller's key, to prove control of the caller's
+/// address to off-chain services.
+///
+/// The message address is set to the caller's address, and the issue time defaults to the current
+/// time.
+///
+/// # Parameters
+///
+/// - `message` (`SiwsMessage`): The sign-in request of the service.
+/// - `subaccount` (`Option<Subaccount>`): The caller's subaccount to sign in with.
+///
+/// # Returns
+///
+/// - `WalletResult<SignedSiwsMessage>`: The signed message text and its signature on success, or a
+///   `WalletError` on failure.
+#[update(name = "signInWithSolana")]
+#[candid_method(rename = "signInWithSolana")]
+pub async fn sign_in_with_solana(
+    message: SiwsMessage,
+    subaccount: Option<Subaccount>,
+) -> WalletResult<SignedSiwsMessage> {
+    let caller = validate_caller_not_anonymous()?;
+    let address = caller_pubkey(caller, subaccount.as_ref()).await?;
+    let message = prepare_siws_message(message, &address, ic_cdk::api::time())?;
+
+    let key_name = read_state(|s| s.schnorr_key.to_owned());
+    let derived_path = derivation_path(&caller, subaccount.as_ref());
+    let signature = sign_with_eddsa(key_name, derived_path, message.as_bytes().into()).await?;
+    record_account(caller, subaccount.as_ref(), address);
+
+    Ok(SignedSiwsMessage {
+        message,
+        signature: ByteBuf::from(signature),
+    })
 }
 
 /// Signs and sends a transaction to the Solana network.
 ///
+/// The caller's wallet address must be one of the transaction's required signers. Signatures
+/// already present in the transaction are verified and kept.
+///
 /// # Parameters
 ///
 /// - `provider` (`String`): The Solana RPC provider ID.
-/// - `raw_transaction` (`String`): The serialized unsigned transaction.
+/// - `raw_transaction` (`String`): The serialized, unsigned or partially signed transaction.
 /// - `config` (`Option<RpcSendTransactionConfig>`): Optional configuration for sending the
 ///   transaction.
+/// - `durable_nonce` (`Option<bool>`): If `true`, an `AdvanceNonceAccount` instruction for the
+///   caller's nonce account is prepended and its nonce value is used in place of a recent
+///   blockhash, so the transaction does not expire while it is being signed. The message is
+///   rebuilt, so the transaction must not carry other signatures.
+/// - `subaccount` (`Option<Subaccount>`): The caller's subaccount to sign with.
 ///
 /// # Returns
 ///
-/// - `RpcResult<String>`: The transaction signature as a string on success, or an `RpcError` on
-///   failure.
+/// - `WalletResult<String>`: The transaction signature as a string on success, or a `WalletError`
+///   on failure.
 #[update(name = "sendTransaction")]
 #[candid_method(query, rename = "sendTransaction")]
 pub async fn send_transaction(
@@ -67,42 +167,745 @@ pub async fn send_transaction(
     config: Option<RpcConfig>,
     raw_transaction: String,
     params: Option<RpcSendTransactionConfig>,
-) -> RpcResult<String> {
-    let caller = validate_caller_not_anonymous();
+    durable_nonce: Option<bool>,
+    subaccount: Option<Subaccount>,
+) -> WalletResult<String> {
+    let caller = validate_caller_not_anonymous()?;
     let sol_canister = read_state(|s| s.sol_canister);
 
-    let mut tx = Transaction::from_str(&raw_transaction).expect("Invalid transaction");
+    let mut tx = parse_transaction(&raw_transaction)?;
 
-    // Fetch the recent blockhash if it's not set
+    if durable_nonce.unwrap_or_default() {
+        let authority = caller_pubkey(caller, subaccount.as_ref()).await?;
+        let nonce_account = nonce_account_address(&authority);
+        let instructions = tx
+            .message
+            .decompile_instructions()
+            .ok_or_else(|| WalletError::InvalidTransaction("invalid instruction account index".to_string()))?;
+        let payer = tx.message.account_keys.first().copied();
+
+        let mut message = Message::new_with_nonce(instructions, payer.as_ref(), &nonce_account, &authority);
+        message.recent_blockhash = get_nonce(sol_canister, &source, config.clone(), &nonce_account).await?;
+        tx = Transaction::new_unsigned(message);
+    }
+
+    sign_and_send_transaction(caller, subaccount, source, config, tx, params).await
+}
+
+/// Signs a transaction with the caller's key without sending it.
+///
+/// The caller's wallet address must be one of the transaction's required signers. Signatures
+/// already present in the transaction are verified and kept, so the returned transaction can be
+/// passed on to the remaining signers.
+///
+/// # Parameters
+///
+/// - `raw_transaction` (`String`): The serialized, unsigned or partially signed transaction.
+/// - `subaccount` (`Option<Subaccount>`): The caller's subaccount to sign with.
+///
+/// # Returns
+///
+/// - `WalletResult<String>`: The serialized, partially signed transaction on success, or a
+///   `WalletError` on failure.
+#[update(name = "signTransaction")]
+#[candid_method(rename = "signTransaction")]
+pub async fn sign_transaction(
+    source: RpcServices,
+    config: Option<RpcConfig>,
+    raw_transaction: String,
+    subaccount: Option<Subaccount>,
+) -> WalletResult<String> {
+    let caller = validate_caller_not_anonymous()?;
+    let mut tx = parse_transaction(&raw_transaction)?;
+    sign_with_caller_key(caller, subaccount.as_ref(), &source, config, &mut tx).await?;
+    Ok(tx.to_string())
+}
+
+/// Returns the cycles charged for every signature made with a wallet key.
+#[query(name = "getSigningCost")]
+#[candid_method(query, rename = "getSigningCost")]
+pub fn get_signing_cost() -> u128 {
+    EDDSA_SIGN_COST
+}
+
+/// Estimates the cycles to attach to a `sendTransaction` call.
+///
+/// The estimate covers the signing fee, the RPC canister's cost of fetching the latest blockhash
+/// if the transaction doesn't have one, and its cost of sending the transaction. It includes the
+/// collateral the RPC canister requires to be attached, so the caller gets part of the cycles
+/// refunded. Durable nonce transactions cost one more RPC request.
+///
+/// # Returns
+///
+/// - `WalletResult<u128>`: The cycles to attach, or a `WalletError` if the transaction is invalid
+///   or the RPC canister could not be reached.
+#[update(name = "estimateSendCost")]
+#[candid_method(rename = "estimateSendCost")]
+pub async fn estimate_send_cost(
+    source: RpcServices,
+    config: Option<RpcConfig>,
+    raw_transaction: String,
+    params: Option<RpcSendTransactionConfig>,
+) -> WalletResult<u128> {
+    let sol_canister = read_state(|s| s.sol_canister);
+    let tx = parse_transaction(&raw_transaction)?;
+
+    let mut cycles = EDDSA_SIGN_COST;
     if tx.message.recent_blockhash == BlockHash::default() {
-        let response =
-            ic_cdk::call::<_, (RpcResult<String>,)>(sol_canister, "sol_getLatestBlockhash", (&source,)).await?;
-        tx.message.recent_blockhash = BlockHash::from_str(&response.0?).expect("Invalid recent blockhash");
+        cycles += latest_blockhash_cost(sol_canister, &source, config.as_ref()).await?;
+    }
+
+    // The transaction is sent with all of its signatures
+    let num_signers = tx.message.header.num_required_signatures as usize;
+    let signed_tx = Transaction {
+        signatures: vec![Signature::default(); num_signers],
+        message: tx.message,
+    };
+    cycles += send_transaction_cost(sol_canister, &source, config.as_ref(), &signed_tx.to_string(), params).await?;
+
+    Ok(cycles)
+}
+
+/// Returns the address of the durable nonce account associated with the caller.
+///
+/// The account is derived from the caller's Solana wallet address and has to be
+/// created with `createNonceAccount` before it can be used.
+#[update(name = "nonceAccount")]
+#[candid_method(rename = "nonceAccount")]
+pub async fn nonce_account(subaccount: Option<Subaccount>) -> WalletResult<String> {
+    let caller = validate_caller_not_anonymous()?;
+    let authority = caller_pubkey(caller, subaccount.as_ref()).await?;
+    Ok(nonce_account_address(&authority).to_string())
+}
+
+/// Creates and initializes the caller's durable nonce account, funded by the caller's wallet.
+///
+/// # Parameters
+///
+/// - `lamports` (`Option<u64>`): The amount to fund the account with. Defaults to the minimum
+///   balance for rent exemption.
+/// - `subaccount` (`Option<Subaccount>`): The caller's subaccount that owns the nonce account.
+///
+/// # Returns
+///
+/// - `WalletResult<String>`: The transaction signature as a string on success, or a `WalletError`
+///   on failure.
+#[update(name = "createNonceAccount")]
+#[candid_method(rename = "createNonceAccount")]
+pub async fn create_nonce_account(
+    source: RpcServices,
+    config: Option<RpcConfig>,
+    lamports: Option<u64>,
+    subaccount: Option<Subaccount>,
+) -> WalletResult<String> {
+    let caller = validate_caller_not_anonymous()?;
+    let sol_canister = read_state(|s| s.sol_canister);
+
+    let lamports = match lamports {
+        Some(lamports) => lamports,
+        None => {
+            ic_cdk::call::<_, (RpcResult<u64>,)>(
+                sol_canister,
+                "sol_getMinimumBalanceForRentExemption",
+                (&source, config.clone(), NONCE_STATE_SIZE as usize),
+            )
+            .await?
+            .0?
+        }
+    };
+
+    let authority = caller_pubkey(caller, subaccount.as_ref()).await?;
+    let nonce_account = nonce_account_address(&authority);
+    let instructions = create_nonce_account_with_seed(
+        &authority,
+        &nonce_account,
+        &authority,
+        NONCE_ACCOUNT_SEED,
+        &authority,
+        lamports,
+    );
+    let tx = Transaction::new_unsigned(Message::new(&instructions, Some(&authority)));
+
+    sign_and_send_transaction(caller, subaccount, source, config, tx, None).await
+}
+
+/// Returns the current nonce value of the caller's durable nonce account.
+///
+/// # Returns
+///
+/// - `WalletResult<String>`: The base58 encoded nonce on success, or a `WalletError` on failure.
+#[update(name = "getNonce")]
+#[candid_method(rename = "getNonce")]
+pub async fn get_nonce_value(
+    source: RpcServices,
+    config: Option<RpcConfig>,
+    subaccount: Option<Subaccount>,
+) -> WalletResult<String> {
+    let caller = validate_caller_not_anonymous()?;
+    let sol_canister = read_state(|s| s.sol_canister);
+    let nonce_account = nonce_account_address(&caller_pubkey(caller, subaccount.as_ref()).await?);
+    let nonce = get_nonce(sol_canister, &source, config, &nonce_account).await?;
+    Ok(nonce.to_string())
+}
+
+/// Withdraws lamports from the caller's durable nonce account.
+///
+/// Withdrawing the entire balance closes the account.
+///
+/// # Parameters
+///
+/// - `to` (`String`): The recipient address.
+/// - `lamports` (`u64`): The amount to withdraw.
+/// - `subaccount` (`Option<Subaccount>`): The caller's subaccount that owns the nonce account.
+///
+/// # Returns
+///
+/// - `WalletResult<String>`: The transaction signature as a string on success, or a `WalletError`
+///   on failure.
+#[update(name = "withdrawNonceAccount")]
+#[candid_method(rename = "withdrawNonceAccount")]
+pub async fn withdraw_nonce(
+    source: RpcServices,
+    config: Option<RpcConfig>,
+    to: String,
+    lamports: u64,
+    subaccount: Option<Subaccount>,
+) -> WalletResult<String> {
+    let caller = validate_caller_not_anonymous()?;
+    let to = Pubkey::from_str(&to)
+        .map_err(|_| WalletError::InvalidTransaction(format!("invalid recipient address {to}")))?;
+
+    let authority = caller_pubkey(caller, subaccount.as_ref()).await?;
+    let nonce_account = nonce_account_address(&authority);
+    let instruction = withdraw_nonce_account(&nonce_account, &authority, &to, lamports);
+    let tx = Transaction::new_unsigned(Message::new(&[instruction], Some(&authority)));
+
+    sign_and_send_transaction(caller, subaccount, source, config, tx, None).await
+}
+
+/// Sets or removes the spending policy of a principal.
+///
+/// The policy applies to all of the principal's accounts.
+///
+/// # Returns
+///
+/// - `WalletResult<()>`: A `WalletError` if the policy lists an invalid address.
+#[update(name = "setSpendingPolicy", guard = "require_controller")]
+#[candid_method(rename = "setSpendingPolicy")]
+pub fn set_spending_policy(principal: Principal, policy: Option<SpendingPolicy>) -> WalletResult<()> {
+    match policy {
+        Some(policy) => {
+            policy.validate().map_err(WalletError::InvalidArgument)?;
+            mutate_state(|s| s.policies.insert(principal, policy));
+        }
+        None => {
+            mutate_state(|s| s.policies.remove(&principal));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the spending policy of a principal.
+///
+/// Principals can read their own policy, controllers can read any policy.
+#[query(name = "getSpendingPolicy")]
+#[candid_method(query, rename = "getSpendingPolicy")]
+pub fn get_spending_policy(principal: Principal) -> WalletResult<Option<SpendingPolicy>> {
+    let caller = ic_cdk::caller();
+    if caller != principal && !ic_cdk::api::is_controller(&caller) {
+        return Err(WalletError::Unauthorized);
+    }
+    Ok(read_state(|s| s.policies.get(&principal).cloned()))
+}
+
+/// Registers a shared wallet whose transactions need the approval of `threshold` of `approvers`.
+///
+/// # Returns
+///
+/// - `WalletResult<String>`: The address of the shared wallet, or a `WalletError` if the wallet
+///   already exists, the approvers or threshold are invalid, or the wallet's public key could not
+///   be fetched.
+#[update(name = "createSharedWallet", guard = "require_controller")]
+#[candid_method(rename = "createSharedWallet")]
+pub async fn create_shared_wallet(wallet_id: String, approvers: Vec<Principal>, threshold: u8) -> WalletResult<String> {
+    if read_state(|s| s.shared_wallets.contains_key(&wallet_id)) {
+        return Err(WalletError::InvalidArgument(format!(
+            "shared wallet {wallet_id} already exists"
+        )));
+    }
+    let mut unique_approvers = approvers.clone();
+    unique_approvers.sort();
+    unique_approvers.dedup();
+    if unique_approvers.len() != approvers.len() {
+        return Err(WalletError::InvalidArgument("duplicate approvers".to_string()));
+    }
+    if threshold == 0 || threshold as usize > approvers.len() {
+        return Err(WalletError::InvalidArgument(
+            "threshold must be between 1 and the number of approvers".to_string(),
+        ));
+    }
+
+    if read_state(|s| s.eddsa_public_key.is_none()) {
+        init_eddsa_public_key().await?;
+    }
+    let address = derive_pubkey(&shared_wallet_derivation_path(&wallet_id))
+        .ok_or_else(|| WalletError::PublicKeyUnavailable("not initialized yet".to_string()))?
+        .to_string();
+
+    mutate_state(|s| {
+        s.shared_wallets.insert(
+            wallet_id,
+            SharedWallet {
+                approvers,
+                threshold,
+                address: address.clone(),
+            },
+        )
+    });
+    Ok(address)
+}
+
+/// Returns a shared wallet.
+#[query(name = "getSharedWallet")]
+#[candid_method(query, rename = "getSharedWallet")]
+pub fn get_shared_wallet(wallet_id: String) -> Option<SharedWallet> {
+    read_state(|s| s.shared_wallets.get(&wallet_id))
+}
+
+/// Proposes a transaction for a shared wallet. Only approvers of the wallet can propose.
+///
+/// The recent blockhash of the transaction is replaced with the latest one when the proposal is
+/// executed, so the transaction does not expire while it waits for approvals.
+///
+/// # Parameters
+///
+/// - `wallet_id` (`String`): The shared wallet id.
+/// - `raw_transaction` (`String`): The serialized unsigned transaction.
+/// - `ttl` (`Option<u64>`): The time in seconds the proposal can be approved for. Defaults to seven
+///   days. Expired proposals are removed within an hour, unless their transaction was signed.
+///
+/// # Returns
+///
+/// - `WalletResult<u64>`: The proposal id, or a `WalletError` if the wallet doesn't exist, the
+///   caller is not an approver or the transaction is invalid.
+#[update(name = "proposeTransaction")]
+#[candid_method(rename = "proposeTransaction")]
+pub fn propose_transaction(wallet_id: String, raw_transaction: String, ttl: Option<u64>) -> WalletResult<u64> {
+    let caller = validate_caller_not_anonymous()?;
+    let wallet = read_state(|s| s.shared_wallets.get(&wallet_id))
+        .ok_or_else(|| WalletError::NotFound(format!("shared wallet {wallet_id}")))?;
+    if !wallet.is_approver(&caller) {
+        return Err(WalletError::Unauthorized);
+    }
+
+    let tx = parse_transaction(&raw_transaction)?;
+    if tx.message.decompile_instructions().is_none() {
+        return Err(WalletError::InvalidTransaction(
+            "invalid instruction account index".to_string(),
+        ));
+    }
+    if !tx
+        .message
+        .signer_keys()
+        .iter()
+        .any(|key| key.to_string() == wallet.address)
+    {
+        return Err(WalletError::InvalidTransaction(format!(
+            "wallet address {} is not a signer of the transaction",
+            wallet.address
+        )));
+    }
+
+    let now = ic_cdk::api::time();
+    let ttl = Duration::from_secs(ttl.unwrap_or(DEFAULT_PROPOSAL_TTL));
+    Ok(mutate_state(|s| {
+        s.add_proposal(Proposal {
+            id: 0,
+            wallet_id,
+            proposer: caller,
+            transaction: Transaction::new_unsigned(tx.message).to_string(),
+            approvals: vec![],
+            created_at: now,
+            expires_at: now.saturating_add(ttl.as_nanos() as u64),
+            status: ProposalStatus::Pending,
+        })
+    }))
+}
+
+/// Returns a proposal with its decoded instructions. Only approvers of the wallet can read it.
+#[query(name = "getProposal")]
+#[candid_method(query, rename = "getProposal")]
+pub fn get_proposal(proposal_id: u64) -> WalletResult<Option<ProposalInfo>> {
+    let caller = validate_caller_not_anonymous()?;
+    read_state(|s| {
+        let Some(proposal) = s.proposals.get(&proposal_id) else {
+            return Ok(None);
+        };
+        let is_approver = s
+            .shared_wallets
+            .get(&proposal.wallet_id)
+            .is_some_and(|wallet| wallet.is_approver(&caller));
+        if !is_approver {
+            return Err(WalletError::Unauthorized);
+        }
+        Ok(Some(ProposalInfo::from(proposal)))
+    })
+}
+
+/// Approves a proposal. Once the threshold of approvals is reached, the transaction is signed by
+/// the shared wallet and sent to the Solana network.
+///
+/// The signed transaction is stored with the proposal before it is sent. If sending fails,
+/// approving the proposal again re-sends that same transaction, so it can't be executed twice. If
+/// it expires before it lands, propose the transaction again.
+///
+/// # Returns
+///
+/// - `WalletResult<Option<String>>`: The transaction signature once the proposal is executed,
+///   `None` while more approvals are needed, or a `WalletError` on failure.
+#[update(name = "approveProposal")]
+#[candid_method(rename = "approveProposal")]
+pub async fn approve_proposal(
+    source: RpcServices,
+    config: Option<RpcConfig>,
+    proposal_id: u64,
+    params: Option<RpcSendTransactionConfig>,
+) -> WalletResult<Option<String>> {
+    let caller = validate_caller_not_anonymous()?;
+    let proposal = read_state(|s| s.proposals.get(&proposal_id))
+        .ok_or_else(|| WalletError::NotFound(format!("proposal {proposal_id}")))?;
+    let wallet = read_state(|s| s.shared_wallets.get(&proposal.wallet_id))
+        .ok_or_else(|| WalletError::NotFound(format!("shared wallet {}", proposal.wallet_id)))?;
+
+    if !wallet.is_approver(&caller) {
+        return Err(WalletError::Unauthorized);
+    }
+    match &proposal.status {
+        ProposalStatus::Executed { signature } => return Ok(Some(signature.clone())),
+        ProposalStatus::Executing => {
+            return Err(WalletError::InvalidProposal(format!(
+                "proposal {proposal_id} is being executed"
+            )))
+        }
+        ProposalStatus::Signed { transaction, .. } => {
+            let tx = parse_transaction(transaction)?;
+            return send_proposal(proposal_id, &source, config, &tx, params).await.map(Some);
+        }
+        ProposalStatus::Pending => {}
+    }
+    if proposal.is_expired(ic_cdk::api::time()) {
+        return Err(WalletError::InvalidProposal(format!(
+            "proposal {proposal_id} has expired"
+        )));
+    }
+
+    let threshold = wallet.threshold as usize;
+    if proposal.approvals.contains(&caller) && proposal.approvals.len() < threshold {
+        return Err(WalletError::InvalidProposal(format!(
+            "proposal {proposal_id} is already approved by the caller"
+        )));
     }
 
+    let approvals = mutate_state(|s| {
+        let mut proposal = s.proposals.get(&proposal_id)?;
+        if !proposal.approvals.contains(&caller) {
+            proposal.approvals.push(caller);
+        }
+        let approvals = proposal.approvals.len();
+        s.proposals.insert(proposal_id, proposal);
+        Some(approvals)
+    })
+    .ok_or_else(|| WalletError::NotFound(format!("proposal {proposal_id}")))?;
+    if approvals < threshold {
+        return Ok(None);
+    }
+
+    let mut tx = parse_transaction(&proposal.transaction)?;
+    tx.message.recent_blockhash = BlockHash::default();
+    let pubkey = Pubkey::from_str(&wallet.address)
+        .map_err(|_| WalletError::InvalidArgument(format!("invalid wallet address {}", wallet.address)))?;
+    let derived_path = shared_wallet_derivation_path(&proposal.wallet_id);
+
+    set_proposal_status(proposal_id, ProposalStatus::Executing);
+    if let Err(err) = sign_with_key(pubkey, derived_path, None, &source, config.clone(), &mut tx).await {
+        // Nothing was signed, so the proposal can be executed again
+        set_proposal_status(proposal_id, ProposalStatus::Pending);
+        return Err(err);
+    }
+    set_proposal_status(
+        proposal_id,
+        ProposalStatus::Signed {
+            transaction: tx.to_string(),
+            signature: tx.signatures[0].to_string(),
+        },
+    );
+
+    send_proposal(proposal_id, &source, config, &tx, params).await.map(Some)
+}
+
+/// Sends the signed transaction of a proposal and marks the proposal as executed once it is sent.
+async fn send_proposal(
+    proposal_id: u64,
+    source: &RpcServices,
+    config: Option<RpcConfig>,
+    tx: &Transaction,
+    params: Option<RpcSendTransactionConfig>,
+) -> WalletResult<String> {
+    let signature = send_signed_transaction(source, config, tx, params).await?;
+    set_proposal_status(
+        proposal_id,
+        ProposalStatus::Executed {
+            signature: signature.clone(),
+        },
+    );
+    Ok(signature)
+}
+
+fn set_proposal_status(proposal_id: u64, status: ProposalStatus) {
+    mutate_state(|s| {
+        if let Some(mut proposal) = s.proposals.get(&proposal_id) {
+            proposal.status = status;
+            s.proposals.insert(proposal_id, proposal);
+        }
+    })
+}
+
+/// Returns the Solana public key of the caller's account, fetching the canister's public key if it
+/// is not cached yet.
+///
+/// Fails if the caller can't use the account. The account is not recorded as used, see
+/// [`record_account`].
+async fn caller_pubkey(caller: Principal, subaccount: Option<&Subaccount>) -> WalletResult<Pubkey> {
+    if let Some(subaccount) = subaccount {
+        subaccount.validate()?;
+    }
+    read_state(|s| s.validate_account(&caller, subaccount))?;
+    match derive_caller_pubkey(caller, subaccount) {
+        Some(pubkey) => Ok(pubkey),
+        None => {
+            init_eddsa_public_key().await?;
+            derive_caller_pubkey(caller, subaccount)
+                .ok_or_else(|| WalletError::PublicKeyUnavailable("not initialized yet".to_string()))
+        }
+    }
+}
+
+/// Records an account as used once the caller has paid for signing with it, so that accounts
+/// can't be added for free.
+fn record_account(caller: Principal, subaccount: Option<&Subaccount>, pubkey: Pubkey) {
+    // The limit was checked by `caller_pubkey`, so only concurrent calls can exceed it
+    let _ = mutate_state(|s| s.add_account(caller, subaccount.cloned(), pubkey));
+}
+
+/// Returns the Solana public key of the caller's account from the cache, or derives it locally
+/// from the canister's public key.
+fn derive_caller_pubkey(caller: Principal, subaccount: Option<&Subaccount>) -> Option<Pubkey> {
+    read_state(|s| s.account(&caller, subaccount)).or_else(|| derive_pubkey(&derivation_path(&caller, subaccount)))
+}
+
+/// Derives the Solana public key of a derivation path from the canister's public key.
+fn derive_pubkey(derived_path: &[ByteBuf]) -> Option<Pubkey> {
+    read_state(|s| {
+        s.eddsa_public_key
+            .as_ref()
+            .map(|eddsa_public_key| Pubkey::from(eddsa_public_key.derive(derived_path)))
+    })
+}
+
+/// Fetches and caches the canister's public key.
+async fn init_eddsa_public_key() -> WalletResult<()> {
     let key_name = read_state(|s| s.schnorr_key.to_owned());
-    let derived_path = vec![ByteBuf::from(caller.as_slice())];
+    let eddsa_public_key = eddsa_canister_public_key(key_name).await?;
+    mutate_state(|s| s.eddsa_public_key = Some(eddsa_public_key));
+    Ok(())
+}
 
-    let signature = sign_with_eddsa(key_name, derived_path, tx.message_data())
-        .await
-        .try_into()
-        .expect("Invalid signature");
+/// Fetches the canister's public key after `delay`, so that `address` can be served as a query.
+///
+/// Failed attempts are retried with an exponential backoff until the key is set.
+fn schedule_init_eddsa_public_key(delay: Duration) {
+    ic_cdk_timers::set_timer(delay, move || {
+        if read_state(|s| s.eddsa_public_key.is_some()) {
+            return;
+        }
+        ic_cdk::spawn(async move {
+            if let Err(err) = init_eddsa_public_key().await {
+                let delay = (delay * 2).clamp(
+                    Duration::from_secs(EDDSA_PUBLIC_KEY_RETRY_DELAY),
+                    Duration::from_secs(EDDSA_PUBLIC_KEY_MAX_RETRY_DELAY),
+                );
+                log!(
+                    INFO,
+                    "Failed to fetch the wallet public key, retrying in {}s: {err}",
+                    delay.as_secs()
+                );
+                schedule_init_eddsa_public_key(delay);
+            }
+        })
+    });
+}
 
-    tx.add_signature(0, signature);
+/// Removes expired proposals periodically, see [`State::prune_proposals`].
+fn schedule_prune_proposals() {
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(PRUNE_PROPOSALS_INTERVAL), || {
+        mutate_state(|s| s.prune_proposals(ic_cdk::api::time()))
+    });
+}
+
+/// Decodes a serialized transaction.
+fn parse_transaction(raw_transaction: &str) -> WalletResult<Transaction> {
+    Transaction::from_str(raw_transaction).map_err(|err| WalletError::InvalidTransaction(err.to_string()))
+}
 
-    let response = ic_cdk::call::<_, (RpcResult<String>,)>(
+/// Signs a transaction with the caller's key and sends it to the Solana network.
+async fn sign_and_send_transaction(
+    caller: Principal,
+    subaccount: Option<Subaccount>,
+    source: RpcServices,
+    config: Option<RpcConfig>,
+    mut tx: Transaction,
+    params: Option<RpcSendTransactionConfig>,
+) -> WalletResult<String> {
+    sign_with_caller_key(caller, subaccount.as_ref(), &source, config.clone(), &mut tx).await?;
+    send_signed_transaction(&source, config, &tx, params).await
+}
+
+/// Sends a signed transaction to the Solana network, forwarding the cycles the RPC canister
+/// charges for it.
+async fn send_signed_transaction(
+    source: &RpcServices,
+    config: Option<RpcConfig>,
+    tx: &Transaction,
+    params: Option<RpcSendTransactionConfig>,
+) -> WalletResult<String> {
+    let sol_canister = read_state(|s| s.sol_canister);
+    let raw_tx = tx.to_string();
+    let cycles = send_transaction_cost(sol_canister, source, config.as_ref(), &raw_tx, params).await?;
+
+    let (response,) = call_with_cycles::<_, (RpcResult<String>,)>(
         sol_canister,
         "sol_sendTransaction",
-        (&source, config, tx.to_string(), params),
+        (source, config, raw_tx, params),
+        cycles,
     )
     .await?;
 
-    response.0
+    Ok(response?)
+}
+
+/// Adds the signature of the caller's account to a transaction at the position of its address.
+///
+/// See [`sign_with_key`]. The transaction is also checked against the caller's spending policy.
+async fn sign_with_caller_key(
+    caller: Principal,
+    subaccount: Option<&Subaccount>,
+    source: &RpcServices,
+    config: Option<RpcConfig>,
+    tx: &mut Transaction,
+) -> WalletResult<()> {
+    let pubkey = caller_pubkey(caller, subaccount).await?;
+    let derived_path = derivation_path(&caller, subaccount);
+    sign_with_key(pubkey, derived_path, Some(caller), source, config, tx).await?;
+    record_account(caller, subaccount, pubkey);
+    Ok(())
+}
+
+/// Adds the signature of a derived key to a transaction at the position of its public key.
+///
+/// Existing signatures are verified and left in place. The latest blockhash is fetched if the
+/// transaction does not have one set and has not been signed by anyone else yet. If a
+/// `policy_owner` is given, the transaction is checked against their spending policy.
+async fn sign_with_key(
+    pubkey: Pubkey,
+    derived_path: Vec<ByteBuf>,
+    policy_owner: Option<Principal>,
+    source: &RpcServices,
+    config: Option<RpcConfig>,
+    tx: &mut Transaction,
+) -> WalletResult<()> {
+    let position = tx
+        .message
+        .signer_keys()
+        .iter()
+        .position(|key| **key == pubkey)
+        .ok_or_else(|| {
+            WalletError::InvalidTransaction(format!("wallet address {pubkey} is not a signer of the transaction"))
+        })?;
+
+    let num_signers = tx.message.header.num_required_signatures as usize;
+    tx.signatures.resize(num_signers, Signature::default());
+
+    for (i, valid) in tx.verify_with_results().into_iter().enumerate() {
+        if !valid && tx.signatures[i] != Signature::default() {
+            return Err(WalletError::InvalidTransaction(format!(
+                "invalid signature for {}",
+                tx.message.account_keys[i]
+            )));
+        }
+    }
+
+    // Fetch the recent blockhash if it's not set
+    if tx.message.recent_blockhash == BlockHash::default() {
+        if tx.signatures.iter().any(|signature| *signature != Signature::default()) {
+            return Err(WalletError::InvalidTransaction(
+                "partially signed transaction is missing a recent blockhash".to_string(),
+            ));
+        }
+        let sol_canister = read_state(|s| s.sol_canister);
+        let cycles = latest_blockhash_cost(sol_canister, source, config.as_ref()).await?;
+        let (response,) = call_with_cycles::<_, (RpcResult<RpcBlockhash>,)>(
+            sol_canister,
+            "sol_getLatestBlockhash",
+            (source, config, Option::<RpcContextConfig>::None),
+            cycles,
+        )
+        .await?;
+        tx.message.recent_blockhash = BlockHash::from_str(&response?.blockhash)
+            .map_err(|_| RpcError::ParseError("base58 encoded blockhash".to_string()))?;
+    }
+
+    // The spending is reserved before signing, so that concurrent calls can't exceed the limit, and
+    // released if signing fails
+    let mut spending = None;
+    if let Some(owner) = policy_owner {
+        if let Some(policy) = read_state(|s| s.policies.get(&owner).cloned()) {
+            let today = day(ic_cdk::api::time());
+            let spent = read_state(|s| s.spent_on(&owner, today));
+            let lamports = policy
+                .check(&tx.message, &pubkey, spent)
+                .map_err(WalletError::SpendingPolicyViolation)?;
+            if lamports > 0 {
+                mutate_state(|s| s.add_spending(owner, today, lamports));
+                spending = Some((owner, today, lamports));
+            }
+        }
+    }
+
+    let key_name = read_state(|s| s.schnorr_key.to_owned());
+
+    let signature = match sign_with_eddsa(key_name, derived_path, tx.message_data()).await {
+        Ok(signature) => signature,
+        Err(err) => {
+            if let Some((owner, day, lamports)) = spending {
+                mutate_state(|s| s.remove_spending(owner, day, lamports));
+            }
+            return Err(err);
+        }
+    };
+    let signature = signature
+        .try_into()
+        .map_err(|_| WalletError::SigningFailed("invalid signature length".to_string()))?;
+
+    tx.add_signature(position, signature);
+
+    Ok(())
 }
 
 #[ic_cdk::init]
 fn init(args: InitArgs) {
-    State::init(args)
+    State::init(args);
+    schedule_init_eddsa_public_key(Duration::ZERO);
+    schedule_prune_proposals();
 }
 
 #[ic_cdk::pre_upgrade]
@@ -112,7 +915,9 @@ fn pre_upgrade() {
 
 #[ic_cdk::post_upgrade]
 fn post_upgrade(args: Option<InitArgs>) {
-    State::post_upgrade(args)
+    State::post_upgrade(args);
+    schedule_init_eddsa_public_key(Duration::ZERO);
+    schedule_prune_proposals();
 }
 
 fn main() {}
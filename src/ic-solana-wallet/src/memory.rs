@@ -0,0 +1,71 @@
+use std::cell::RefCell;
+
+use ic_stable_structures::{
+    memory_manager::{MemoryId, MemoryManager, VirtualMemory},
+    writer::Writer,
+    DefaultMemoryImpl, Memory, StableBTreeMap,
+};
+
+use crate::shared::{Proposal, SharedWallet};
+
+/// Magic bytes at the start of stable memory managed by a [MemoryManager].
+const MEMORY_MANAGER_MAGIC: &[u8; 3] = b"MGR";
+
+const UPGRADES_MEMORY_ID: MemoryId = MemoryId::new(0);
+const SHARED_WALLETS_MEMORY_ID: MemoryId = MemoryId::new(1);
+const PROPOSALS_MEMORY_ID: MemoryId = MemoryId::new(2);
+
+pub type StableMemory = VirtualMemory<DefaultMemoryImpl>;
+pub type SharedWalletsMemory = StableBTreeMap<String, SharedWallet, StableMemory>;
+pub type ProposalsMemory = StableBTreeMap<u64, Proposal, StableMemory>;
+
+thread_local! {
+    // Stable static data: these are preserved when the canister is upgraded.
+    pub static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+}
+
+pub fn get_memory(memory_id: MemoryId) -> StableMemory {
+    MEMORY_MANAGER.with_borrow(|m| m.get(memory_id))
+}
+
+pub fn init_shared_wallets_memory() -> SharedWalletsMemory {
+    SharedWalletsMemory::init(get_memory(SHARED_WALLETS_MEMORY_ID))
+}
+
+pub fn init_proposals_memory() -> ProposalsMemory {
+    ProposalsMemory::init(get_memory(PROPOSALS_MEMORY_ID))
+}
+
+/// Returns whether stable memory holds the state saved by releases that did not use a
+/// [MemoryManager] yet, in which case it must be restored before the memory manager is
+/// initialized and overwrites it.
+pub fn is_legacy_layout() -> bool {
+    let memory = DefaultMemoryImpl::default();
+    if memory.size() == 0 {
+        return false;
+    }
+    let mut magic = [0; 3];
+    memory.read(0, &mut magic);
+    &magic != MEMORY_MANAGER_MAGIC
+}
+
+/// Saves the heap state, prefixed with its length, so that it is restored after an upgrade.
+pub fn save_upgrade_state(bytes: &[u8]) {
+    let mut memory = get_memory(UPGRADES_MEMORY_ID);
+    let mut writer = Writer::new(&mut memory, 0);
+    writer
+        .write(&(bytes.len() as u64).to_le_bytes())
+        .expect("failed to save state");
+    writer.write(bytes).expect("failed to save state");
+}
+
+/// Returns the heap state saved by [save_upgrade_state].
+pub fn load_upgrade_state() -> Vec<u8> {
+    let memory = get_memory(UPGRADES_MEMORY_ID);
+    let mut len = [0; 8];
+    memory.read(0, &mut len);
+    let mut bytes = vec![0; u64::from_le_bytes(len) as usize];
+    memory.read(len.len() as u64, &mut bytes);
+    bytes
+}
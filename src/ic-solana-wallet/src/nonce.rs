@@ -0,0 +1,45 @@
+use candid::Principal;
+use ic_solana::{
+    nonce,
+    rpc_client::{RpcConfig, RpcError, RpcResult, RpcServices},
+    system_instruction::SYSTEM_PROGRAM_ID,
+    types::{BlockHash, Pubkey, RpcAccountInfoConfig, UiAccount, UiAccountEncoding},
+};
+
+/// Seed used to derive the caller's durable nonce account from their wallet address.
+pub const NONCE_ACCOUNT_SEED: &str = "nonce";
+
+/// Returns the address of the durable nonce account owned by the given wallet address.
+pub fn nonce_account_address(authority: &Pubkey) -> Pubkey {
+    Pubkey::create_with_seed(authority, NONCE_ACCOUNT_SEED, &SYSTEM_PROGRAM_ID).expect("Invalid nonce account seed")
+}
+
+/// Fetches the current value of a durable nonce account.
+pub async fn get_nonce(
+    sol_canister: Principal,
+    source: &RpcServices,
+    config: Option<RpcConfig>,
+    nonce_account: &Pubkey,
+) -> RpcResult<BlockHash> {
+    let params = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        ..Default::default()
+    };
+
+    let response = ic_cdk::call::<_, (RpcResult<Option<UiAccount>>,)>(
+        sol_canister,
+        "sol_getAccountInfo",
+        (source, config, nonce_account.to_string(), Some(params)),
+    )
+    .await?;
+
+    let account = response
+        .0?
+        .ok_or_else(|| RpcError::Text(format!("Nonce account {nonce_account} does not exist")))?
+        .decode()
+        .ok_or_else(|| RpcError::ParseError("base64 encoded account data".to_string()))?;
+
+    nonce::data_from_account(&account)
+        .map(|data| data.blockhash())
+        .map_err(|e| RpcError::Text(e.to_string()))
+}
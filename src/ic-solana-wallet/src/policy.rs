@@ -0,0 +1,191 @@
+use std::str::FromStr;
+
+use candid::{CandidType, Deserialize};
+use ic_solana::{
+    compute_budget::COMPUTE_BUDGET_PROGRAM_ID,
+    spl_token::{TokenInstruction, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID},
+    system_instruction::{SystemInstruction, SYSTEM_PROGRAM_ID},
+    types::{CompiledInstruction, Message, Pubkey},
+};
+use serde::Serialize;
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Limits on the transactions the wallet signs for a caller.
+///
+/// Lamports sent by the caller's account with the system program's `Transfer`, `TransferWithSeed`,
+/// `WithdrawNonceAccount`, `CreateAccount` and `CreateAccountWithSeed` instructions count towards
+/// the daily limit. The recipients of these instructions, and the recipients, delegates and close
+/// destinations of SPL token `Transfer`, `TransferChecked`, `Approve` and `CloseAccount`
+/// instructions authorized by the caller's account, must be allowlisted if `allowed_destinations`
+/// is set.
+///
+/// Instructions of the system, SPL token and compute budget programs that the policy can't account
+/// for are rejected. Instructions of other programs are rejected unless the program is listed in
+/// `allowed_programs`.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpendingPolicy {
+    /// The maximum amount of lamports that can be sent per UTC day.
+    pub daily_lamports_limit: Option<u64>,
+    /// The addresses that transfers can be sent to.
+    pub allowed_destinations: Option<Vec<String>>,
+    /// The programs that instructions can invoke.
+    pub allowed_programs: Option<Vec<String>>,
+}
+
+/// Lamports sent by a caller during a day.
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DailySpending {
+    /// Days since the Unix epoch.
+    pub day: u64,
+    pub lamports: u64,
+}
+
+impl DailySpending {
+    /// Returns the lamports sent on the given day.
+    pub fn spent_on(&self, day: u64) -> u64 {
+        if self.day == day {
+            self.lamports
+        } else {
+            0
+        }
+    }
+}
+
+/// Returns the number of days since the Unix epoch of a timestamp in nanoseconds.
+pub fn day(timestamp_nanos: u64) -> u64 {
+    timestamp_nanos / NANOS_PER_DAY
+}
+
+impl SpendingPolicy {
+    /// Checks that all allowlisted addresses are valid.
+    pub fn validate(&self) -> Result<(), String> {
+        for address in self.allowed_destinations.iter().chain(&self.allowed_programs).flatten() {
+            Pubkey::from_str(address).map_err(|_| format!("Invalid address: {address}"))?;
+        }
+        Ok(())
+    }
+
+    /// Checks a message signed by `signer` against the policy.
+    ///
+    /// Returns the lamports the message sends from the signer's account.
+    pub fn check(&self, message: &Message, signer: &Pubkey, spent_today: u64) -> Result<u64, String> {
+        let mut lamports = 0u64;
+
+        for instruction in &message.instructions {
+            let program_id = account_key(message, instruction, None)?;
+            if !is_allowed(&self.allowed_programs, &program_id) {
+                return Err(format!("Program {program_id} is not allowed"));
+            }
+            if !is_supported_program(&program_id)
+                && !self
+                    .allowed_programs
+                    .as_ref()
+                    .is_some_and(|programs| programs.contains(&program_id.to_string()))
+            {
+                return Err(format!("Program {program_id} is not supported by the spending policy"));
+            }
+
+            if let Some((amount, destination)) = outgoing_transfer(message, instruction, &program_id, signer)? {
+                if !is_allowed(&self.allowed_destinations, &destination) {
+                    return Err(format!("Destination {destination} is not allowed"));
+                }
+                lamports = lamports.saturating_add(amount);
+            }
+        }
+
+        if let Some(limit) = self.daily_lamports_limit {
+            if spent_today.saturating_add(lamports) > limit {
+                return Err(format!(
+                    "Daily limit of {limit} lamports exceeded, {spent_today} lamports already spent today"
+                ));
+            }
+        }
+
+        Ok(lamports)
+    }
+}
+
+fn is_allowed(allowlist: &Option<Vec<String>>, pubkey: &Pubkey) -> bool {
+    allowlist
+        .as_ref()
+        .is_none_or(|allowlist| allowlist.contains(&pubkey.to_string()))
+}
+
+/// Returns `true` if the policy can account for the instructions of the program.
+fn is_supported_program(program_id: &Pubkey) -> bool {
+    [
+        SYSTEM_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+        TOKEN_2022_PROGRAM_ID,
+        COMPUTE_BUDGET_PROGRAM_ID,
+    ]
+    .contains(program_id)
+}
+
+/// Returns the program id of the instruction, or the key of one of its accounts.
+fn account_key(
+    message: &Message,
+    instruction: &CompiledInstruction,
+    position: Option<usize>,
+) -> Result<Pubkey, String> {
+    let index = match position {
+        Some(position) => instruction.accounts.get(position).map(|&index| index as usize),
+        None => Some(instruction.program_id_index as usize),
+    };
+    index
+        .and_then(|index| message.account_keys.get(index))
+        .copied()
+        .ok_or_else(|| "Invalid instruction account index".to_string())
+}
+
+/// Decodes a transfer authorized by `signer`.
+///
+/// Returns the lamports sent, which are zero for token instructions, and the recipient. Token
+/// delegates and the destinations of closed token accounts count as recipients. Fails for
+/// instructions of supported programs that the policy can't account for.
+fn outgoing_transfer(
+    message: &Message,
+    instruction: &CompiledInstruction,
+    program_id: &Pubkey,
+    signer: &Pubkey,
+) -> Result<Option<(u64, Pubkey)>, String> {
+    // Positions of the authority and the recipient in the instruction accounts
+    let unsupported = || format!("Instruction of program {program_id} is not supported by the spending policy");
+    let (lamports, authority, recipient) = if *program_id == SYSTEM_PROGRAM_ID {
+        match bincode::deserialize::<SystemInstruction>(&instruction.data) {
+            Ok(SystemInstruction::Transfer { lamports }) => (lamports, 0, 1),
+            Ok(SystemInstruction::TransferWithSeed { lamports, .. }) => (lamports, 1, 2),
+            Ok(SystemInstruction::WithdrawNonceAccount(lamports)) => (lamports, 4, 1),
+            Ok(SystemInstruction::CreateAccount { lamports, .. }) => (lamports, 0, 1),
+            Ok(SystemInstruction::CreateAccountWithSeed { lamports, .. }) => (lamports, 0, 1),
+            Ok(
+                SystemInstruction::AdvanceNonceAccount
+                | SystemInstruction::InitializeNonceAccount(_)
+                | SystemInstruction::Allocate { .. },
+            ) => return Ok(None),
+            _ => return Err(unsupported()),
+        }
+    } else if *program_id == TOKEN_PROGRAM_ID || *program_id == TOKEN_2022_PROGRAM_ID {
+        match TokenInstruction::unpack(&instruction.data) {
+            Ok(TokenInstruction::Transfer { .. }) => (0, 2, 1),
+            Ok(TokenInstruction::TransferChecked { .. }) => (0, 3, 2),
+            Ok(TokenInstruction::Approve { .. }) => (0, 2, 1),
+            Ok(TokenInstruction::CloseAccount) => (0, 2, 1),
+            Ok(
+                TokenInstruction::InitializeMint { .. }
+                | TokenInstruction::InitializeAccount
+                | TokenInstruction::MintTo { .. }
+                | TokenInstruction::Burn { .. },
+            ) => return Ok(None),
+            Err(_) => return Err(unsupported()),
+        }
+    } else {
+        return Ok(None);
+    };
+
+    if account_key(message, instruction, Some(authority))? != *signer {
+        return Ok(None);
+    }
+    Ok(Some((lamports, account_key(message, instruction, Some(recipient))?)))
+}
@@ -0,0 +1,160 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_solana::types::{Instruction, Transaction};
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::Serialize;
+use serde_bytes::ByteBuf;
+
+/// First derivation path component of shared wallets.
+///
+/// It is not a valid principal, so it never collides with the derivation path of a caller's
+/// account.
+pub const SHARED_WALLET_SEED: &[u8] = b"\xffshared-wallet";
+
+/// Default time a proposal can be approved for, in seconds.
+pub const DEFAULT_PROPOSAL_TTL: u64 = 7 * 24 * 60 * 60;
+
+/// Time between two removals of expired proposals, in seconds.
+pub const PRUNE_PROPOSALS_INTERVAL: u64 = 60 * 60;
+
+/// A wallet controlled by a group of principals.
+///
+/// Transactions of a shared wallet are signed once `threshold` of its `approvers` approve them.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SharedWallet {
+    pub approvers: Vec<Principal>,
+    pub threshold: u8,
+    pub address: String,
+}
+
+impl SharedWallet {
+    pub fn is_approver(&self, principal: &Principal) -> bool {
+        self.approvers.contains(principal)
+    }
+}
+
+impl Storable for SharedWallet {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Returns the key derivation path of a shared wallet.
+pub fn shared_wallet_derivation_path(wallet_id: &str) -> Vec<ByteBuf> {
+    vec![ByteBuf::from(SHARED_WALLET_SEED), ByteBuf::from(wallet_id.as_bytes())]
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum ProposalStatus {
+    /// Waiting for approvals.
+    Pending,
+    /// The transaction is being signed.
+    Executing,
+    /// The transaction was sent to the network.
+    Executed { signature: String },
+    /// The transaction was signed but could not be sent yet.
+    Signed { transaction: String, signature: String },
+}
+
+/// A transaction proposed for a shared wallet.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Proposal {
+    pub id: u64,
+    pub wallet_id: String,
+    pub proposer: Principal,
+    /// The proposed unsigned transaction.
+    pub transaction: String,
+    pub approvals: Vec<Principal>,
+    /// Creation time in nanoseconds since the Unix epoch.
+    pub created_at: u64,
+    /// Time in nanoseconds since the Unix epoch after which the proposal can no longer be approved.
+    pub expires_at: u64,
+    pub status: ProposalStatus,
+}
+
+impl Proposal {
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.expires_at
+    }
+
+    /// Whether the proposal can be removed at `now`: it has expired and its transaction was not
+    /// signed, so it can't be sent anymore.
+    pub fn is_prunable(&self, now: u64) -> bool {
+        self.is_expired(now) && !matches!(self.status, ProposalStatus::Executing | ProposalStatus::Signed { .. })
+    }
+}
+
+impl Storable for Proposal {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// An account referenced by a proposed instruction.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProposalAccount {
+    pub pubkey: String,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+/// An instruction of a proposed transaction, for approvers to inspect.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProposalInstruction {
+    pub program_id: String,
+    pub accounts: Vec<ProposalAccount>,
+    pub data: ByteBuf,
+}
+
+impl From<Instruction> for ProposalInstruction {
+    fn from(instruction: Instruction) -> Self {
+        Self {
+            program_id: instruction.program_id.to_string(),
+            accounts: instruction
+                .accounts
+                .into_iter()
+                .map(|meta| ProposalAccount {
+                    pubkey: meta.pubkey.to_string(),
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: ByteBuf::from(instruction.data),
+        }
+    }
+}
+
+/// A proposal with its decoded instructions.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProposalInfo {
+    pub proposal: Proposal,
+    pub instructions: Vec<ProposalInstruction>,
+}
+
+impl From<Proposal> for ProposalInfo {
+    fn from(proposal: Proposal) -> Self {
+        let instructions = proposal
+            .transaction
+            .parse::<Transaction>()
+            .ok()
+            .and_then(|tx| tx.message.decompile_instructions())
+            .unwrap_or_default()
+            .into_iter()
+            .map(ProposalInstruction::from)
+            .collect();
+        Self { proposal, instructions }
+    }
+}
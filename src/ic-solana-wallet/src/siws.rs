@@ -0,0 +1,41 @@
+use std::str::FromStr;
+
+use candid::{CandidType, Deserialize};
+use ic_solana::{siws::SiwsMessage, types::Pubkey};
+use serde_bytes::ByteBuf;
+
+use crate::error::{WalletError, WalletResult};
+
+/// A Sign-In With Solana message signed by the wallet.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SignedSiwsMessage {
+    /// The signed message text.
+    pub message: String,
+    pub signature: ByteBuf,
+}
+
+/// Completes a sign-in request with the wallet address and the issue time, and returns the text
+/// to sign.
+///
+/// The address may be left empty. The request is rejected if it can't be serialized to a message
+/// that parses back to the same request, e.g. if the statement contains empty lines.
+pub fn prepare_siws_message(mut message: SiwsMessage, address: &Pubkey, now: u64) -> WalletResult<String> {
+    let address = address.to_string();
+    if !message.address.is_empty() && message.address != address {
+        return Err(WalletError::InvalidMessage(format!(
+            "message address {} is not the wallet address {address}",
+            message.address
+        )));
+    }
+    message.address = address;
+    if message.issued_at.is_none() {
+        message.issued_at = Some(ic_solana::siws::format_rfc3339(now));
+    }
+
+    let text = message.to_string();
+    match SiwsMessage::from_str(&text) {
+        Ok(parsed) if parsed == message => Ok(text),
+        Ok(_) => Err(WalletError::InvalidMessage("ambiguous message fields".to_string())),
+        Err(err) => Err(WalletError::InvalidMessage(err.to_string())),
+    }
+}
@@ -1,13 +1,20 @@
-use std::{cell::RefCell, str::FromStr};
+use std::{cell::RefCell, collections::BTreeMap, str::FromStr};
 
-use candid::{CandidType, Deserialize};
-use ic_cdk::{
-    api::management_canister::main::CanisterId,
-    storage::{stable_restore, stable_save},
-};
-use serde::Serialize;
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_cdk::{api::management_canister::main::CanisterId, storage::stable_restore};
+use ic_solana::types::Pubkey;
 
-use crate::eddsa::SchnorrKey;
+use crate::{
+    account::{Subaccount, WalletAccount, MAX_ACCOUNTS},
+    eddsa::{EddsaPublicKey, SchnorrKey},
+    error::{WalletError, WalletResult},
+    memory::{
+        init_proposals_memory, init_shared_wallets_memory, is_legacy_layout, load_upgrade_state, save_upgrade_state,
+        ProposalsMemory, SharedWalletsMemory,
+    },
+    policy::{DailySpending, SpendingPolicy},
+    shared::{Proposal, SharedWallet},
+};
 
 thread_local! {
     pub static STATE: RefCell<Option<State>> = const { RefCell::new(None) };
@@ -19,10 +26,80 @@ pub struct InitArgs {
     pub schnorr_key: Option<String>,
 }
 
-#[derive(CandidType, Clone, Debug, Deserialize, Serialize)]
 pub struct State {
     pub sol_canister: CanisterId,
     pub schnorr_key: SchnorrKey,
+    /// The canister's public key, fetched once to derive account keys locally.
+    pub eddsa_public_key: Option<EddsaPublicKey>,
+    /// Accounts used by each principal, keyed by subaccount, with their cached public keys.
+    pub accounts: BTreeMap<Principal, BTreeMap<Option<Subaccount>, Pubkey>>,
+    /// Spending policies of principals, applied to all of their accounts.
+    pub policies: BTreeMap<Principal, SpendingPolicy>,
+    /// Lamports sent by principals with a daily limit in their policy.
+    pub spending: BTreeMap<Principal, DailySpending>,
+    /// Shared wallets by id, kept in stable memory.
+    pub shared_wallets: SharedWalletsMemory,
+    /// Transactions proposed for shared wallets, by proposal id, kept in stable memory.
+    pub proposals: ProposalsMemory,
+    pub next_proposal_id: u64,
+}
+
+/// Layout of the heap part of the [State] saved on upgrade.
+///
+/// Candid can't decode a missing field into a non-optional one, so fields added since the first
+/// release are optional, and the state saved by any earlier release can be restored. Shared
+/// wallets and proposals are only saved here by releases that kept them on the heap.
+#[derive(CandidType, Deserialize)]
+struct StableState {
+    sol_canister: CanisterId,
+    schnorr_key: SchnorrKey,
+    eddsa_public_key: Option<EddsaPublicKey>,
+    accounts: Option<BTreeMap<Principal, BTreeMap<Option<Subaccount>, Pubkey>>>,
+    policies: Option<BTreeMap<Principal, SpendingPolicy>>,
+    spending: Option<BTreeMap<Principal, DailySpending>>,
+    shared_wallets: Option<BTreeMap<String, SharedWallet>>,
+    proposals: Option<BTreeMap<u64, Proposal>>,
+    next_proposal_id: Option<u64>,
+}
+
+impl From<StableState> for State {
+    fn from(state: StableState) -> Self {
+        let mut shared_wallets = init_shared_wallets_memory();
+        for (wallet_id, wallet) in state.shared_wallets.unwrap_or_default() {
+            shared_wallets.insert(wallet_id, wallet);
+        }
+        let mut proposals = init_proposals_memory();
+        for (proposal_id, proposal) in state.proposals.unwrap_or_default() {
+            proposals.insert(proposal_id, proposal);
+        }
+        Self {
+            sol_canister: state.sol_canister,
+            schnorr_key: state.schnorr_key,
+            eddsa_public_key: state.eddsa_public_key,
+            accounts: state.accounts.unwrap_or_default(),
+            policies: state.policies.unwrap_or_default(),
+            spending: state.spending.unwrap_or_default(),
+            shared_wallets,
+            proposals,
+            next_proposal_id: state.next_proposal_id.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<State> for StableState {
+    fn from(state: State) -> Self {
+        Self {
+            sol_canister: state.sol_canister,
+            schnorr_key: state.schnorr_key,
+            eddsa_public_key: state.eddsa_public_key,
+            accounts: Some(state.accounts),
+            policies: Some(state.policies),
+            spending: Some(state.spending),
+            shared_wallets: None,
+            proposals: None,
+            next_proposal_id: Some(state.next_proposal_id),
+        }
+    }
 }
 
 impl State {
@@ -33,25 +110,137 @@ impl State {
                 .schnorr_key
                 .and_then(|s| SchnorrKey::from_str(&s).ok())
                 .unwrap_or(SchnorrKey::TestKey1),
+            eddsa_public_key: None,
+            accounts: Default::default(),
+            policies: Default::default(),
+            spending: Default::default(),
+            shared_wallets: init_shared_wallets_memory(),
+            proposals: init_proposals_memory(),
+            next_proposal_id: 0,
         });
     }
 
     pub fn pre_upgrade() {
-        take_state(|state| stable_save((state,)).expect("failed to save state"))
+        let bytes = take_state(|state| Encode!(&StableState::from(state)).expect("failed to save state"));
+        save_upgrade_state(&bytes);
     }
 
     pub fn post_upgrade(args: Option<InitArgs>) {
-        let (mut state,): (State,) = stable_restore().expect("failed to restore state");
+        let state = if is_legacy_layout() {
+            // Earlier releases saved the state to stable memory directly
+            let (state,): (StableState,) = stable_restore().expect("failed to restore state");
+            state
+        } else {
+            Decode!(&load_upgrade_state(), StableState).expect("failed to restore state")
+        };
+        let mut state = State::from(state);
         if let Some(args) = args {
             if let Some(sol_canister) = args.sol_canister {
                 state.sol_canister = sol_canister;
             }
             if let Some(schnorr_key) = args.schnorr_key {
-                state.schnorr_key = SchnorrKey::from_str(&schnorr_key).expect("Invalid schnorr key");
+                let schnorr_key = SchnorrKey::from_str(&schnorr_key).expect("Invalid schnorr key");
+                if schnorr_key.to_string() != state.schnorr_key.to_string() {
+                    // Cached keys belong to the previous schnorr key
+                    state.eddsa_public_key = None;
+                    state.accounts.clear();
+                }
+                state.schnorr_key = schnorr_key;
             }
         }
         replace_state(state);
     }
+
+    /// Checks that the owner can use an account, which fails if it is a new account and the owner
+    /// already uses [`MAX_ACCOUNTS`] accounts.
+    pub fn validate_account(&self, owner: &Principal, subaccount: Option<&Subaccount>) -> WalletResult<()> {
+        let accounts = self.accounts.get(owner);
+        let is_new = accounts.is_none_or(|accounts| !accounts.contains_key(&subaccount.cloned()));
+        if is_new && accounts.map_or(0, |accounts| accounts.len()) >= MAX_ACCOUNTS {
+            return Err(WalletError::InvalidArgument(format!(
+                "at most {MAX_ACCOUNTS} accounts can be used"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Records an account used by the owner. See [`State::validate_account`].
+    pub fn add_account(
+        &mut self,
+        owner: Principal,
+        subaccount: Option<Subaccount>,
+        pubkey: Pubkey,
+    ) -> WalletResult<()> {
+        self.validate_account(&owner, subaccount.as_ref())?;
+        self.accounts.entry(owner).or_default().insert(subaccount, pubkey);
+        Ok(())
+    }
+
+    /// Returns the cached public key of an owner's account.
+    pub fn account(&self, owner: &Principal, subaccount: Option<&Subaccount>) -> Option<Pubkey> {
+        self.accounts
+            .get(owner)
+            .and_then(|accounts| accounts.get(&subaccount.cloned()))
+            .copied()
+    }
+
+    /// Returns the lamports sent by the owner on the given day.
+    pub fn spent_on(&self, owner: &Principal, day: u64) -> u64 {
+        self.spending.get(owner).map_or(0, |spending| spending.spent_on(day))
+    }
+
+    /// Records lamports sent by the owner on the given day.
+    pub fn add_spending(&mut self, owner: Principal, day: u64, lamports: u64) {
+        let spent = self.spent_on(&owner, day).saturating_add(lamports);
+        self.spending.insert(owner, DailySpending { day, lamports: spent });
+    }
+
+    /// Releases lamports recorded for the owner on the given day that were not sent.
+    pub fn remove_spending(&mut self, owner: Principal, day: u64, lamports: u64) {
+        if let Some(spending) = self.spending.get_mut(&owner) {
+            if spending.day == day {
+                spending.lamports = spending.lamports.saturating_sub(lamports);
+            }
+        }
+    }
+
+    /// Stores a new proposal under the next proposal id.
+    pub fn add_proposal(&mut self, mut proposal: Proposal) -> u64 {
+        let id = self.next_proposal_id;
+        self.next_proposal_id += 1;
+        proposal.id = id;
+        self.proposals.insert(id, proposal);
+        id
+    }
+
+    /// Removes the proposals that can be pruned at `now`. See [`Proposal::is_prunable`].
+    pub fn prune_proposals(&mut self, now: u64) {
+        let prunable = self
+            .proposals
+            .iter()
+            .filter(|(_, proposal)| proposal.is_prunable(now))
+            .map(|(proposal_id, _)| proposal_id)
+            .collect::<Vec<_>>();
+        for proposal_id in prunable {
+            self.proposals.remove(&proposal_id);
+        }
+    }
+
+    /// Returns the accounts used by the owner.
+    pub fn accounts(&self, owner: &Principal) -> Vec<WalletAccount> {
+        self.accounts
+            .get(owner)
+            .map(|accounts| {
+                accounts
+                    .iter()
+                    .map(|(subaccount, pubkey)| WalletAccount {
+                        subaccount: subaccount.clone(),
+                        address: pubkey.to_string(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
 
 impl std::fmt::Display for State {
@@ -96,3 +285,133 @@ pub fn replace_state(state: State) {
         *s.borrow_mut() = Some(state);
     });
 }
+
+#[cfg(test)]
+mod test {
+    use serde::Serialize;
+
+    use super::*;
+    use crate::shared::ProposalStatus;
+
+    fn proposal(expires_at: u64, status: ProposalStatus) -> Proposal {
+        Proposal {
+            id: 0,
+            wallet_id: "wallet".to_string(),
+            proposer: Principal::anonymous(),
+            transaction: String::new(),
+            approvals: vec![],
+            created_at: 0,
+            expires_at,
+            status,
+        }
+    }
+
+    fn empty_state() -> StableState {
+        StableState {
+            sol_canister: Principal::management_canister(),
+            schnorr_key: SchnorrKey::TestKey1,
+            eddsa_public_key: None,
+            accounts: None,
+            policies: None,
+            spending: None,
+            shared_wallets: None,
+            proposals: None,
+            next_proposal_id: None,
+        }
+    }
+
+    #[test]
+    fn should_restore_baseline_state() {
+        #[derive(CandidType, Serialize)]
+        struct BaselineState {
+            sol_canister: CanisterId,
+            schnorr_key: SchnorrKey,
+        }
+
+        let bytes = Encode!(&BaselineState {
+            sol_canister: Principal::management_canister(),
+            schnorr_key: SchnorrKey::ProductionKey1,
+        })
+        .unwrap();
+        let state = State::from(Decode!(&bytes, StableState).unwrap());
+        assert_eq!(state.sol_canister, Principal::management_canister());
+        assert_eq!(state.schnorr_key.to_string(), "key_1");
+        assert!(state.eddsa_public_key.is_none());
+        assert!(state.accounts.is_empty());
+        assert!(state.policies.is_empty());
+        assert!(state.spending.is_empty());
+        assert!(state.shared_wallets.is_empty());
+        assert!(state.proposals.is_empty());
+        assert_eq!(state.next_proposal_id, 0);
+    }
+
+    #[test]
+    fn should_limit_accounts() {
+        let mut state = State::from(empty_state());
+        let owner = Principal::anonymous();
+        for index in 0..MAX_ACCOUNTS as u32 {
+            state
+                .add_account(owner, Some(Subaccount::Index(index)), Pubkey::default())
+                .unwrap();
+        }
+        assert!(matches!(
+            state.add_account(owner, None, Pubkey::default()),
+            Err(WalletError::InvalidArgument(_))
+        ));
+
+        // Used accounts can still be used
+        assert!(state
+            .add_account(owner, Some(Subaccount::Index(0)), Pubkey::default())
+            .is_ok());
+        assert_eq!(state.accounts(&owner).len(), MAX_ACCOUNTS);
+    }
+
+    #[test]
+    fn should_move_heap_shared_wallets_to_stable_memory() {
+        let wallet = SharedWallet {
+            approvers: vec![Principal::anonymous()],
+            threshold: 1,
+            address: Pubkey::default().to_string(),
+        };
+        let pending = proposal(1, ProposalStatus::Pending);
+        let state = State::from(StableState {
+            shared_wallets: Some(BTreeMap::from([("wallet".to_string(), wallet.clone())])),
+            proposals: Some(BTreeMap::from([(0, pending.clone())])),
+            next_proposal_id: Some(1),
+            ..empty_state()
+        });
+        assert_eq!(state.shared_wallets.get(&"wallet".to_string()), Some(wallet));
+        assert_eq!(state.proposals.get(&0), Some(pending));
+
+        let saved = StableState::from(state);
+        assert!(saved.shared_wallets.is_none());
+        assert!(saved.proposals.is_none());
+        assert_eq!(saved.next_proposal_id, Some(1));
+    }
+
+    #[test]
+    fn should_prune_expired_proposals() {
+        let mut state = State::from(empty_state());
+        for status in [
+            ProposalStatus::Pending,
+            ProposalStatus::Executing,
+            ProposalStatus::Executed {
+                signature: String::new(),
+            },
+            ProposalStatus::Signed {
+                transaction: String::new(),
+                signature: String::new(),
+            },
+        ] {
+            state.add_proposal(proposal(10, status));
+        }
+        state.add_proposal(proposal(20, ProposalStatus::Pending));
+
+        state.prune_proposals(10);
+        assert_eq!(state.proposals.len(), 5);
+
+        // Signed transactions can still be sent
+        state.prune_proposals(11);
+        assert_eq!(state.proposals.iter().map(|(id, _)| id).collect::<Vec<_>>(), [1, 3, 4]);
+    }
+}
@@ -1,9 +1,19 @@
 use candid::Principal;
 
-pub fn validate_caller_not_anonymous() -> Principal {
+use crate::error::{WalletError, WalletResult};
+
+pub fn validate_caller_not_anonymous() -> WalletResult<Principal> {
     let caller = ic_cdk::caller();
     if caller == Principal::anonymous() {
-        panic!("Anonymous principal not allowed to make calls.")
+        return Err(WalletError::Unauthorized);
+    }
+    Ok(caller)
+}
+
+pub fn require_controller() -> Result<(), String> {
+    if ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        Ok(())
+    } else {
+        Err("Unauthorized".to_string())
     }
-    caller
 }
@@ -8,6 +8,8 @@ use ic_test_utilities_load_wasm::load_wasm;
 use serde::de::DeserializeOwned;
 use test_utils::{CallFlow, TestSetup};
 
+const MAX_TICKS: usize = 10;
+
 thread_local! {
      static RPC_WASM: Vec<u8> = load_wasm(get_root(), "ic-solana-rpc", &[]);
      static WASM: Vec<u8> = load_wasm(env!("CARGO_MANIFEST_DIR"), env!("CARGO_PKG_NAME"), &[]);
@@ -37,16 +39,20 @@ impl SolanaWalletSetup {
 
         let sol_canister = Some(rpc_setup.canister_id);
 
-        Self {
-            rpc_setup,
-            setup: TestSetup::new(
-                WASM.with(|wasm| wasm.clone()),
-                InitArgs {
-                    sol_canister,
-                    schnorr_key: None,
-                },
-            ),
+        let setup = TestSetup::new(
+            WASM.with(|wasm| wasm.clone()),
+            InitArgs {
+                sol_canister,
+                schnorr_key: None,
+            },
+        );
+
+        // Let the wallet fetch its public key
+        for _ in 0..MAX_TICKS {
+            setup.env.tick();
         }
+
+        Self { rpc_setup, setup }
     }
 
     #[allow(clippy::wrong_self_convention)]
@@ -1,7 +1,23 @@
 use std::str::FromStr;
 
-use ic_solana::{rpc_client::RpcServices, types::Pubkey};
-use test_utils::MockOutcallBuilder;
+use candid::Principal;
+use ic_solana::{
+    offchain_message::OffchainMessage,
+    rpc_client::RpcServices,
+    siws::{SiwsMessage, SiwsVerifyOptions},
+    system_instruction::{create_account, transfer, SYSTEM_PROGRAM_ID},
+    types::{AccountMeta, BlockHash, Instruction, Message, Pubkey, Transaction},
+};
+use ic_solana_wallet::{
+    account::{Subaccount, WalletAccount, MAX_LABEL_LENGTH},
+    eddsa::EDDSA_SIGN_COST,
+    error::{WalletError, WalletResult},
+    policy::SpendingPolicy,
+    shared::{ProposalInfo, ProposalStatus, SharedWallet},
+    siws::SignedSiwsMessage,
+    state::InitArgs,
+};
+use test_utils::{MockOutcallBuilder, TestSetup};
 
 mod setup;
 
@@ -10,21 +26,514 @@ use crate::setup::SolanaWalletSetup;
 #[test]
 fn test_address() {
     let setup = SolanaWalletSetup::new();
-    let addr = setup.call_update::<_, String>("address", ()).wait();
+    let addr = setup.call_query::<_, WalletResult<String>>("address", ()).unwrap();
     assert_eq!(addr, "F57BD4FrpkM49idKyws2WBBjyR8W8dRsepLJ4EqLP3Qb");
-    let addr = setup.as_controller().call_update::<_, String>("address", ()).wait();
+    let addr = setup
+        .clone()
+        .as_controller()
+        .call_query::<_, WalletResult<String>>("address", ())
+        .unwrap();
     assert_eq!(addr, "8GU8W7fAAy2trcy36fjVJuJhEY5uA3EYTvA7jupM72wG");
+    assert_eq!(
+        setup
+            .as_caller(Principal::anonymous())
+            .call_query::<_, WalletResult<String>>("address", ()),
+        Err(WalletError::Unauthorized)
+    );
+}
+
+#[test]
+fn test_subaccounts() {
+    let setup = SolanaWalletSetup::new().as_controller();
+    let main = setup.call_query::<_, WalletResult<String>>("address", ()).unwrap();
+    let trading = setup
+        .call_query::<_, WalletResult<String>>("address", (Some(Subaccount::Index(1)),))
+        .unwrap();
+    let escrow = setup
+        .call_query::<_, WalletResult<String>>("address", (Some(Subaccount::Label("escrow".to_string())),))
+        .unwrap();
+    assert_eq!(main, "8GU8W7fAAy2trcy36fjVJuJhEY5uA3EYTvA7jupM72wG");
+    assert_ne!(trading, main);
+    assert_ne!(escrow, main);
+    assert_ne!(escrow, trading);
+
+    // Accounts are recorded once they have signed
+    assert!(setup.call_query::<_, Vec<WalletAccount>>("accounts", ()).is_empty());
+    for subaccount in [
+        None,
+        Some(Subaccount::Index(1)),
+        Some(Subaccount::Label("escrow".to_string())),
+    ] {
+        setup
+            .call_update::<_, WalletResult<Vec<u8>>>("signMessage", ("test123", subaccount))
+            .wait()
+            .unwrap();
+    }
+
+    let accounts = setup.call_query::<_, Vec<WalletAccount>>("accounts", ());
+    assert_eq!(
+        accounts,
+        vec![
+            WalletAccount {
+                subaccount: None,
+                address: main,
+            },
+            WalletAccount {
+                subaccount: Some(Subaccount::Index(1)),
+                address: trading,
+            },
+            WalletAccount {
+                subaccount: Some(Subaccount::Label("escrow".to_string())),
+                address: escrow,
+            },
+        ]
+    );
+    assert!(setup
+        .as_caller(TestSetup::caller_id())
+        .call_query::<_, Vec<WalletAccount>>("accounts", ())
+        .is_empty());
+}
+
+#[test]
+fn should_reject_long_subaccount_labels() {
+    let setup = SolanaWalletSetup::new().as_controller();
+    let label = Subaccount::Label("a".repeat(MAX_LABEL_LENGTH + 1));
+    let error = WalletError::InvalidArgument(format!(
+        "subaccount label must be at most {MAX_LABEL_LENGTH} bytes long"
+    ));
+    assert_eq!(
+        setup.call_query::<_, WalletResult<String>>("address", (Some(label.clone()),)),
+        Err(error.clone())
+    );
+    assert_eq!(
+        setup
+            .call_update::<_, WalletResult<Vec<u8>>>("signMessage", ("test123", Some(label)))
+            .wait(),
+        Err(error)
+    );
+    assert!(setup.call_query::<_, Vec<WalletAccount>>("accounts", ()).is_empty());
 }
 
 #[test]
 fn test_sign_message() {
-    let setup = SolanaWalletSetup::new();
+    let setup = SolanaWalletSetup::new().as_controller();
     let message = "test123";
-    let address = setup.call_update::<_, String>("address", ()).wait();
+    let address = setup
+        .call_update::<_, WalletResult<String>>("address", ())
+        .wait()
+        .unwrap();
     let pubkey = Pubkey::from_str(&address).unwrap();
-    let signature = setup.call_update::<_, Vec<u8>>("signMessage", (message,)).wait();
-    let is_valid = pubkey.verify_signature(message.as_bytes(), &signature);
-    assert!(is_valid)
+    let signature = setup
+        .call_update::<_, WalletResult<Vec<u8>>>("signMessage", (message,))
+        .wait()
+        .unwrap();
+    let offchain_message = OffchainMessage::new(0, message.as_bytes()).unwrap();
+    assert!(pubkey.verify_signature(&offchain_message.serialize(), &signature));
+    assert!(!pubkey.verify_signature(message.as_bytes(), &signature));
+
+    let raw_signature = setup
+        .call_update::<_, WalletResult<Vec<u8>>>("signMessage", (message, None::<Subaccount>, Some(true)))
+        .wait()
+        .unwrap();
+    assert!(pubkey.verify_signature(message.as_bytes(), &raw_signature));
+}
+
+#[test]
+fn test_sign_in_with_solana() {
+    let setup = SolanaWalletSetup::new().as_controller();
+    let address = setup
+        .call_update::<_, WalletResult<String>>("address", ())
+        .wait()
+        .unwrap();
+    let request = SiwsMessage {
+        domain: "example.com".to_string(),
+        statement: Some("Sign in to Example".to_string()),
+        nonce: Some("32891756".to_string()),
+        ..Default::default()
+    };
+
+    let signed = setup
+        .call_update::<_, WalletResult<SignedSiwsMessage>>("signInWithSolana", (request,))
+        .wait()
+        .unwrap();
+
+    let message = SiwsMessage::from_str(&signed.message).unwrap();
+    assert_eq!(message.address, address);
+    assert!(message.issued_at.is_some());
+    let options = SiwsVerifyOptions {
+        domain: Some("example.com".to_string()),
+        nonce: Some("32891756".to_string()),
+        time: None,
+    };
+    assert_eq!(
+        message.verify(&signed.signature, &options),
+        Ok(Pubkey::from_str(&address).unwrap())
+    );
+}
+
+#[test]
+fn test_nonce_account() {
+    let setup = SolanaWalletSetup::new();
+    let address = setup
+        .call_update::<_, WalletResult<String>>("address", ())
+        .wait()
+        .unwrap();
+    let nonce_account = setup
+        .call_update::<_, WalletResult<String>>("nonceAccount", ())
+        .wait()
+        .unwrap();
+    let expected = Pubkey::create_with_seed(&Pubkey::from_str(&address).unwrap(), "nonce", &SYSTEM_PROGRAM_ID).unwrap();
+    assert_eq!(nonce_account, expected.to_string());
+}
+
+#[test]
+fn test_sign_transaction() {
+    let setup = SolanaWalletSetup::new().as_controller();
+    let address = setup
+        .call_update::<_, WalletResult<String>>("address", ())
+        .wait()
+        .unwrap();
+    let wallet = Pubkey::from_str(&address).unwrap();
+
+    // The wallet is the second signer, the fee payer signs elsewhere.
+    let payer = Pubkey::from_str("83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri").unwrap();
+    let mut message = Message::new(&[transfer(&wallet, &payer, 1000)], Some(&payer));
+    message.recent_blockhash = BlockHash::from_str("EkSnNWid2cvwEVnVx9aBqawnmiCNiDgp3gUdkDPTKN1N").unwrap();
+    let raw_tx = Transaction::new_unsigned(message).to_string();
+
+    let signed_tx = setup
+        .call_update::<_, WalletResult<String>>("signTransaction", (RpcServices::Mainnet, (), raw_tx))
+        .wait()
+        .unwrap();
+
+    let tx = Transaction::from_str(&signed_tx).unwrap();
+    assert_eq!(tx.verify_with_results(), vec![false, true]);
+}
+
+#[test]
+fn should_reject_calls_without_attached_cycles() {
+    let setup = SolanaWalletSetup::new();
+    assert_eq!(
+        setup
+            .call_update::<_, WalletResult<Vec<u8>>>("signMessage", ("test123",))
+            .wait(),
+        Err(WalletError::InsufficientCycles {
+            available: 0,
+            required: EDDSA_SIGN_COST,
+        })
+    );
+
+    let address = setup
+        .call_update::<_, WalletResult<String>>("address", ())
+        .wait()
+        .unwrap();
+    let wallet = Pubkey::from_str(&address).unwrap();
+    let mut message = Message::new(&[transfer(&wallet, &wallet, 1000)], Some(&wallet));
+    message.recent_blockhash = BlockHash::from_str("EkSnNWid2cvwEVnVx9aBqawnmiCNiDgp3gUdkDPTKN1N").unwrap();
+    let raw_tx = Transaction::new_unsigned(message).to_string();
+    assert_eq!(
+        setup
+            .call_update::<_, WalletResult<String>>("sendTransaction", (RpcServices::Mainnet, (), raw_tx))
+            .wait(),
+        Err(WalletError::InsufficientCycles {
+            available: 0,
+            required: EDDSA_SIGN_COST,
+        })
+    );
+
+    // Accounts are only recorded once signing is paid for
+    assert!(setup.call_query::<_, Vec<WalletAccount>>("accounts", ()).is_empty());
+}
+
+#[test]
+fn test_estimate_send_cost() {
+    let setup = SolanaWalletSetup::new();
+    let signing_cost = setup.call_query::<_, u128>("getSigningCost", ());
+    assert_eq!(signing_cost, EDDSA_SIGN_COST);
+
+    let address = setup
+        .call_update::<_, WalletResult<String>>("address", ())
+        .wait()
+        .unwrap();
+    let wallet = Pubkey::from_str(&address).unwrap();
+    let message = Message::new(&[transfer(&wallet, &wallet, 1000)], Some(&wallet));
+    let raw_tx = Transaction::new_unsigned(message).to_string();
+
+    // RPC requests are free in demo mode
+    let cost = setup
+        .call_update::<_, WalletResult<u128>>("estimateSendCost", (RpcServices::Mainnet, (), raw_tx))
+        .wait()
+        .unwrap();
+    assert_eq!(cost, signing_cost);
+}
+
+fn sign_transfer(setup: &SolanaWalletSetup, to: &str, lamports: u64) -> WalletResult<Transaction> {
+    sign_instruction(setup, |wallet| {
+        transfer(wallet, &Pubkey::from_str(to).unwrap(), lamports)
+    })
+}
+
+fn sign_instruction(
+    setup: &SolanaWalletSetup,
+    instruction: impl FnOnce(&Pubkey) -> Instruction,
+) -> WalletResult<Transaction> {
+    let address = setup.call_query::<_, WalletResult<String>>("address", ()).unwrap();
+    let wallet = Pubkey::from_str(&address).unwrap();
+    let mut message = Message::new(&[instruction(&wallet)], Some(&wallet));
+    message.recent_blockhash = BlockHash::from_str("EkSnNWid2cvwEVnVx9aBqawnmiCNiDgp3gUdkDPTKN1N").unwrap();
+    let raw_tx = Transaction::new_unsigned(message).to_string();
+
+    setup
+        .call_update::<_, WalletResult<String>>("signTransaction", (RpcServices::Mainnet, (), raw_tx))
+        .wait()
+        .map(|signed_tx| Transaction::from_str(&signed_tx).unwrap())
+}
+
+fn set_spending_policy(setup: &SolanaWalletSetup, policy: SpendingPolicy) {
+    setup
+        .clone()
+        .as_controller()
+        .call_update::<_, WalletResult<()>>("setSpendingPolicy", (TestSetup::controller_id(), Some(policy)))
+        .wait()
+        .unwrap();
+}
+
+#[test]
+fn test_spending_policy() {
+    let setup = SolanaWalletSetup::new().as_controller();
+    let policy = SpendingPolicy {
+        daily_lamports_limit: Some(1500),
+        allowed_destinations: Some(vec!["83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri".to_string()]),
+        allowed_programs: None,
+    };
+    set_spending_policy(&setup, policy.clone());
+    assert_eq!(
+        setup.call_query::<_, WalletResult<Option<SpendingPolicy>>>("getSpendingPolicy", (TestSetup::controller_id(),)),
+        Ok(Some(policy))
+    );
+    assert_eq!(
+        setup
+            .clone()
+            .as_caller(TestSetup::caller_id())
+            .call_query::<_, WalletResult<Option<SpendingPolicy>>>("getSpendingPolicy", (TestSetup::controller_id(),)),
+        Err(WalletError::Unauthorized)
+    );
+
+    let tx = sign_transfer(&setup, "83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri", 1000).unwrap();
+    assert!(tx.verify().is_ok());
+}
+
+#[test]
+fn should_reject_transfer_over_daily_limit() {
+    let setup = SolanaWalletSetup::new().as_controller();
+    set_spending_policy(
+        &setup,
+        SpendingPolicy {
+            daily_lamports_limit: Some(1500),
+            ..Default::default()
+        },
+    );
+    assert!(sign_transfer(&setup, "83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri", 1000).is_ok());
+    assert!(matches!(
+        sign_transfer(&setup, "83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri", 1000),
+        Err(WalletError::SpendingPolicyViolation(err)) if err.starts_with("Daily limit of 1500 lamports exceeded")
+    ));
+}
+
+#[test]
+fn should_reject_transfer_to_unknown_destination() {
+    let setup = SolanaWalletSetup::new().as_controller();
+    set_spending_policy(
+        &setup,
+        SpendingPolicy {
+            allowed_destinations: Some(vec!["83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri".to_string()]),
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        sign_transfer(&setup, "11111111111111111111111111111112", 1000),
+        Err(WalletError::SpendingPolicyViolation(
+            "Destination 11111111111111111111111111111112 is not allowed".to_string()
+        ))
+    );
+}
+
+#[test]
+fn should_reject_instructions_bypassing_policy() {
+    let setup = SolanaWalletSetup::new().as_controller();
+    set_spending_policy(
+        &setup,
+        SpendingPolicy {
+            daily_lamports_limit: Some(1500),
+            ..Default::default()
+        },
+    );
+    let to = Pubkey::from_str("83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri").unwrap();
+    assert!(matches!(
+        sign_instruction(&setup, |wallet| create_account(wallet, &to, 2000, 0, &SYSTEM_PROGRAM_ID)),
+        Err(WalletError::SpendingPolicyViolation(err)) if err.starts_with("Daily limit of 1500 lamports exceeded")
+    ));
+
+    let program = Pubkey::from_str("11111111111111111111111111111112").unwrap();
+    assert_eq!(
+        sign_instruction(&setup, |wallet| Instruction::new_with_bytes(
+            program,
+            &[],
+            vec![AccountMeta::new(*wallet, true)]
+        )),
+        Err(WalletError::SpendingPolicyViolation(
+            "Program 11111111111111111111111111111112 is not supported by the spending policy".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_shared_wallet() {
+    let setup = SolanaWalletSetup::new();
+    let approvers = vec![TestSetup::caller_id(), TestSetup::controller_id()];
+    let address = setup
+        .clone()
+        .as_controller()
+        .call_update::<_, WalletResult<String>>("createSharedWallet", ("treasury", approvers.clone(), 2u8))
+        .wait()
+        .unwrap();
+    assert_ne!(
+        address,
+        setup
+            .call_update::<_, WalletResult<String>>("address", ())
+            .wait()
+            .unwrap()
+    );
+    assert_eq!(
+        setup.call_query::<_, Option<SharedWallet>>("getSharedWallet", ("treasury",)),
+        Some(SharedWallet {
+            approvers,
+            threshold: 2,
+            address: address.clone(),
+        })
+    );
+
+    let wallet = Pubkey::from_str(&address).unwrap();
+    let to = Pubkey::from_str("83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri").unwrap();
+    let message = Message::new(&[transfer(&wallet, &to, 1000)], Some(&wallet));
+    let raw_tx = Transaction::new_unsigned(message).to_string();
+    let proposal_id = setup
+        .call_update::<_, WalletResult<u64>>("proposeTransaction", ("treasury", raw_tx, ()))
+        .wait()
+        .unwrap();
+
+    let approved = setup
+        .call_update::<_, WalletResult<Option<String>>>("approveProposal", (RpcServices::Mainnet, (), proposal_id, ()))
+        .wait()
+        .unwrap();
+    assert_eq!(approved, None);
+    assert!(matches!(
+        setup
+            .call_update::<_, WalletResult<Option<String>>>(
+                "approveProposal",
+                (RpcServices::Mainnet, (), proposal_id, ())
+            )
+            .wait(),
+        Err(WalletError::InvalidProposal(_))
+    ));
+
+    let info = setup
+        .call_query::<_, WalletResult<Option<ProposalInfo>>>("getProposal", (proposal_id,))
+        .unwrap();
+    assert_eq!(info.proposal.status, ProposalStatus::Pending);
+    assert_eq!(info.proposal.approvals, vec![TestSetup::caller_id()]);
+    assert_eq!(info.instructions.len(), 1);
+    assert_eq!(info.instructions[0].program_id, SYSTEM_PROGRAM_ID.to_string());
+    assert_eq!(info.instructions[0].accounts[0].pubkey, address);
+    assert_eq!(info.instructions[0].accounts[1].pubkey, to.to_string());
+}
+
+#[test]
+fn should_keep_shared_wallets_on_upgrade() {
+    let setup = SolanaWalletSetup::new();
+    let address = setup
+        .clone()
+        .as_controller()
+        .call_update::<_, WalletResult<String>>("createSharedWallet", ("treasury", vec![TestSetup::caller_id()], 1u8))
+        .wait()
+        .unwrap();
+    let wallet = Pubkey::from_str(&address).unwrap();
+    let message = Message::new(&[transfer(&wallet, &wallet, 1000)], Some(&wallet));
+    let raw_tx = Transaction::new_unsigned(message).to_string();
+    let propose = || {
+        setup
+            .call_update::<_, WalletResult<u64>>("proposeTransaction", ("treasury", raw_tx.clone(), ()))
+            .wait()
+            .unwrap()
+    };
+    let proposal_id = propose();
+
+    setup.upgrade_canister(InitArgs {
+        sol_canister: None,
+        schnorr_key: None,
+    });
+
+    assert_eq!(
+        setup
+            .call_query::<_, Option<SharedWallet>>("getSharedWallet", ("treasury",))
+            .map(|wallet| wallet.address),
+        Some(address)
+    );
+    let info = setup
+        .call_query::<_, WalletResult<Option<ProposalInfo>>>("getProposal", (proposal_id,))
+        .unwrap()
+        .unwrap();
+    assert_eq!(info.proposal.status, ProposalStatus::Pending);
+    assert_eq!(propose(), proposal_id + 1);
+}
+
+#[test]
+fn should_reject_invalid_shared_wallet() {
+    let setup = SolanaWalletSetup::new().as_controller();
+    assert_eq!(
+        setup
+            .call_update::<_, WalletResult<String>>(
+                "createSharedWallet",
+                ("treasury", vec![TestSetup::controller_id()], 2u8)
+            )
+            .wait(),
+        Err(WalletError::InvalidArgument(
+            "threshold must be between 1 and the number of approvers".to_string()
+        ))
+    );
+    assert_eq!(
+        setup
+            .call_update::<_, WalletResult<u64>>("proposeTransaction", ("treasury", "", ()))
+            .wait(),
+        Err(WalletError::NotFound("shared wallet treasury".to_string()))
+    );
+    assert_eq!(
+        setup
+            .call_update::<_, WalletResult<Option<String>>>("approveProposal", (RpcServices::Mainnet, (), 0u64, ()))
+            .wait(),
+        Err(WalletError::NotFound("proposal 0".to_string()))
+    );
+}
+
+#[test]
+fn should_reject_proposal_from_non_approver() {
+    let setup = SolanaWalletSetup::new();
+    let address = setup
+        .clone()
+        .as_controller()
+        .call_update::<_, WalletResult<String>>(
+            "createSharedWallet",
+            ("treasury", vec![TestSetup::controller_id()], 1u8),
+        )
+        .wait()
+        .unwrap();
+    let wallet = Pubkey::from_str(&address).unwrap();
+    let message = Message::new(&[transfer(&wallet, &wallet, 1000)], Some(&wallet));
+    let raw_tx = Transaction::new_unsigned(message).to_string();
+    let result = setup
+        .call_update::<_, WalletResult<u64>>("proposeTransaction", ("treasury", raw_tx, ()))
+        .wait();
+    assert_eq!(result, Err(WalletError::Unauthorized));
 }
 
 // TODO: fix
@@ -36,10 +545,10 @@ fn test_send_transaction() {
     let raw_tx ="4hXTCkRzt9WyecNzV1XPgCDfGAZzQKNxLXgynz5QDuWWPSAZBZSHptvWRL3BjCvzUXRdKvHL2b7yGrRQcWyaqsaBCncVG7BFggS8w9snUts67BSh3EqKpXLUm5UMHfD7ZBe9GhARjbNQMLJ1QD3Spr6oMTBU6EhdB4RD8CP2xUxr2u3d6fos36PD98XS6oX8TQjLpsMwncs5DAMiD4nNnR8NBfyghGCWvCVifVwvA8B8TJxE1aiyiv2L429BCWfyzAme5sZW8rDb14NeCQHhZbtNqfXhcp2tAnaAT";
 
     let signature = setup
-        .call_update::<_, String>("sendTransaction", (RpcServices::Mainnet, (), raw_tx))
+        .call_update::<_, WalletResult<String>>("sendTransaction", (RpcServices::Mainnet, (), raw_tx))
         .mock_http_once(MockOutcallBuilder::new(200, r#"{"jsonrpc":"2.0","result":{"context":{"slot":2792},"value":{"blockhash":"EkSnNWid2cvwEVnVx9aBqawnmiCNiDgp3gUdkDPTKN1N","lastValidBlockHeight":3090}},"id":1}"#))
         .mock_http_once(MockOutcallBuilder::new(200, r#"{"jsonrpc":"2.0","result":"2EanSSkn5cjv9DVKik5gtBkN1wwbV1TAXQQ5yu2RTPGwgrhEywVAQR2veu895uCDzvYwWZe6vD1Bcn8s7r22W17w","id":2}"#))
-        .wait();
+        .wait().unwrap();
 
     println!("signature: {}", signature);
 }
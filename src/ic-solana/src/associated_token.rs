@@ -0,0 +1,176 @@
+//! Derivation of associated token accounts and instructions to create them.
+//!
+//! See https://spl.solana.com/associated-token-account
+
+use crate::{
+    spl_token::TOKEN_PROGRAM_ID,
+    system_instruction::SYSTEM_PROGRAM_ID,
+    types::{AccountMeta, Instruction, Pubkey},
+};
+
+/// The Associated Token Account program id.
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = Pubkey::from_str_const("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// Instructions supported by the Associated Token Account program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AssociatedTokenAccountInstruction {
+    /// Creates an associated token account for the given wallet address and
+    /// token mint. Returns an error if the account exists.
+    ///
+    ///   0. `[writeable,signer]` Funding account (must be a system account)
+    ///   1. `[writeable]` Associated token account address to be created
+    ///   2. `[]` Wallet address for the new associated token account
+    ///   3. `[]` The token mint for the new associated token account
+    ///   4. `[]` System program
+    ///   5. `[]` SPL Token program
+    Create,
+    /// Creates an associated token account for the given wallet address and
+    /// token mint, if it doesn't already exist. Returns an error if the
+    /// account exists, but with a different owner.
+    ///
+    /// Accounts are the same as for [`AssociatedTokenAccountInstruction::Create`].
+    CreateIdempotent,
+}
+
+/// Derives the associated token account address for the given wallet address and token mint,
+/// owned by the original SPL Token program.
+pub fn get_associated_token_address(wallet_address: &Pubkey, token_mint_address: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(wallet_address, token_mint_address, &TOKEN_PROGRAM_ID)
+}
+
+/// Derives the associated token account address for the given wallet address, token mint and
+/// token program id (Token or Token-2022).
+pub fn get_associated_token_address_with_program_id(
+    wallet_address: &Pubkey,
+    token_mint_address: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Pubkey {
+    get_associated_token_address_and_bump_seed(wallet_address, token_mint_address, token_program_id).0
+}
+
+/// Derives the associated token account address and its bump seed.
+pub fn get_associated_token_address_and_bump_seed(
+    wallet_address: &Pubkey,
+    token_mint_address: &Pubkey,
+    token_program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            wallet_address.as_ref(),
+            token_program_id.as_ref(),
+            token_mint_address.as_ref(),
+        ],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+}
+
+/// Creates a `Create` instruction, which fails if the associated token account already exists.
+pub fn create_associated_token_account(
+    funding_address: &Pubkey,
+    wallet_address: &Pubkey,
+    token_mint_address: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Instruction {
+    build_associated_token_account_instruction(
+        funding_address,
+        wallet_address,
+        token_mint_address,
+        token_program_id,
+        AssociatedTokenAccountInstruction::Create,
+    )
+}
+
+/// Creates a `CreateIdempotent` instruction, which succeeds if the associated token account
+/// already exists with the expected owner.
+pub fn create_associated_token_account_idempotent(
+    funding_address: &Pubkey,
+    wallet_address: &Pubkey,
+    token_mint_address: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Instruction {
+    build_associated_token_account_instruction(
+        funding_address,
+        wallet_address,
+        token_mint_address,
+        token_program_id,
+        AssociatedTokenAccountInstruction::CreateIdempotent,
+    )
+}
+
+fn build_associated_token_account_instruction(
+    funding_address: &Pubkey,
+    wallet_address: &Pubkey,
+    token_mint_address: &Pubkey,
+    token_program_id: &Pubkey,
+    instruction: AssociatedTokenAccountInstruction,
+) -> Instruction {
+    let associated_account_address =
+        get_associated_token_address_with_program_id(wallet_address, token_mint_address, token_program_id);
+
+    Instruction::new_with_bytes(
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+        &[instruction as u8],
+        vec![
+            AccountMeta::new(*funding_address, true),
+            AccountMeta::new(associated_account_address, false),
+            AccountMeta::new_readonly(*wallet_address, false),
+            AccountMeta::new_readonly(*token_mint_address, false),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            AccountMeta::new_readonly(*token_program_id, false),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::spl_token::TOKEN_2022_PROGRAM_ID;
+
+    fn pubkey(s: &str) -> Pubkey {
+        Pubkey::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_get_associated_token_address() {
+        let wallet = pubkey("9ri4mUToddwCc6jg1GTL5sobkkFxjUzjZ6CZ6L91LzAR");
+        let usdc_mint = pubkey("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+        let pyusd_mint = pubkey("2b1kV6DkPAnxd5ixfnxCpjxmKwqjjaYmCZfHsFu24GXo");
+
+        assert_eq!(
+            get_associated_token_address(&wallet, &usdc_mint),
+            pubkey("FdhE4EQMqKar7eWbQTZRJKTqBmqKwuq6W1M9rJMW5316")
+        );
+        assert_eq!(
+            get_associated_token_address_and_bump_seed(&wallet, &pyusd_mint, &TOKEN_2022_PROGRAM_ID),
+            (pubkey("7FvKwLukvgKnVbcKMZgU5gepMkdLyzpYdxKwuhfcvBpS"), 253)
+        );
+    }
+
+    #[test]
+    fn test_create_associated_token_account() {
+        let funder = Pubkey::from([1; 32]);
+        let wallet = pubkey("9ri4mUToddwCc6jg1GTL5sobkkFxjUzjZ6CZ6L91LzAR");
+        let usdc_mint = pubkey("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+
+        let instruction = create_associated_token_account(&funder, &wallet, &usdc_mint, &TOKEN_PROGRAM_ID);
+        assert_eq!(instruction.program_id, ASSOCIATED_TOKEN_PROGRAM_ID);
+        assert_eq!(instruction.data, vec![0]);
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(funder, true),
+                AccountMeta::new(pubkey("FdhE4EQMqKar7eWbQTZRJKTqBmqKwuq6W1M9rJMW5316"), false),
+                AccountMeta::new_readonly(wallet, false),
+                AccountMeta::new_readonly(usdc_mint, false),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            ]
+        );
+
+        let instruction = create_associated_token_account_idempotent(&funder, &wallet, &usdc_mint, &TOKEN_PROGRAM_ID);
+        assert_eq!(instruction.data, vec![1]);
+    }
+}
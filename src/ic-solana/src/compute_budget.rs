@@ -0,0 +1,115 @@
+//! Instructions for the compute budget program and priority fee estimation.
+//!
+//! See https://solana.com/docs/core/fees#prioritization-fees
+
+use crate::types::{Instruction, Pubkey, RpcPrioritizationFee};
+
+/// The compute budget program id.
+pub const COMPUTE_BUDGET_PROGRAM_ID: Pubkey = Pubkey::from_str_const("ComputeBudget111111111111111111111111111111");
+
+/// Maximum number of compute units a transaction may request.
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Maximum heap frame size a transaction may request, in bytes.
+pub const MAX_HEAP_FRAME_BYTES: u32 = 256 * 1024;
+
+/// Instructions supported by the compute budget program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComputeBudgetInstruction {
+    /// Request a specific transaction-wide program heap region size in bytes.
+    /// The value requested must be a multiple of 1024. This new heap region
+    /// size applies to each program executed in the transaction, including all
+    /// calls to CPIs.
+    RequestHeapFrame(u32),
+    /// Set a specific compute unit limit that the transaction is allowed to consume.
+    SetComputeUnitLimit(u32),
+    /// Set a compute unit price in "micro-lamports" to pay a higher transaction
+    /// fee for higher transaction prioritization.
+    SetComputeUnitPrice(u64),
+}
+
+impl ComputeBudgetInstruction {
+    /// Packs the instruction using the program's borsh layout: a one byte tag followed by the
+    /// value.
+    pub fn pack(&self) -> Vec<u8> {
+        match self {
+            Self::RequestHeapFrame(bytes) => [&[1], &bytes.to_le_bytes()[..]].concat(),
+            Self::SetComputeUnitLimit(units) => [&[2], &units.to_le_bytes()[..]].concat(),
+            Self::SetComputeUnitPrice(micro_lamports) => [&[3], &micro_lamports.to_le_bytes()[..]].concat(),
+        }
+    }
+
+    fn into_instruction(self) -> Instruction {
+        Instruction::new_with_bytes(COMPUTE_BUDGET_PROGRAM_ID, &self.pack(), vec![])
+    }
+}
+
+/// Create a `RequestHeapFrame` instruction.
+pub fn request_heap_frame(bytes: u32) -> Instruction {
+    ComputeBudgetInstruction::RequestHeapFrame(bytes).into_instruction()
+}
+
+/// Create a `SetComputeUnitLimit` instruction.
+pub fn set_compute_unit_limit(units: u32) -> Instruction {
+    ComputeBudgetInstruction::SetComputeUnitLimit(units).into_instruction()
+}
+
+/// Create a `SetComputeUnitPrice` instruction.
+pub fn set_compute_unit_price(micro_lamports: u64) -> Instruction {
+    ComputeBudgetInstruction::SetComputeUnitPrice(micro_lamports).into_instruction()
+}
+
+/// Returns the given percentile (clamped to 100) of the sampled prioritization fees,
+/// in micro-lamports per compute unit, or zero if there are no samples.
+///
+/// The nearest-rank method is used, so the result is always one of the sampled fees.
+pub fn estimate_prioritization_fee(samples: &[RpcPrioritizationFee], percentile: u8) -> u64 {
+    if samples.is_empty() {
+        return 0;
+    }
+    let mut fees: Vec<u64> = samples.iter().map(|sample| sample.prioritization_fee).collect();
+    fees.sort_unstable();
+
+    let percentile = percentile.min(100) as usize;
+    let rank = (percentile * fees.len()).div_ceil(100);
+    fees[rank.saturating_sub(1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(fees: &[u64]) -> Vec<RpcPrioritizationFee> {
+        fees.iter()
+            .enumerate()
+            .map(|(slot, fee)| RpcPrioritizationFee {
+                slot: slot as u64,
+                prioritization_fee: *fee,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compute_budget_instructions() {
+        let instruction = set_compute_unit_limit(200_000);
+        assert_eq!(instruction.program_id, COMPUTE_BUDGET_PROGRAM_ID);
+        assert!(instruction.accounts.is_empty());
+        assert_eq!(instruction.data, vec![2, 64, 13, 3, 0]);
+
+        assert_eq!(set_compute_unit_price(1_000).data, vec![3, 232, 3, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(request_heap_frame(MAX_HEAP_FRAME_BYTES).data, vec![1, 0, 0, 4, 0]);
+    }
+
+    #[test]
+    fn test_estimate_prioritization_fee() {
+        let samples = samples(&[0, 500, 100, 0, 10_000, 200, 0, 300, 50, 1_000]);
+
+        assert_eq!(estimate_prioritization_fee(&samples, 0), 0);
+        assert_eq!(estimate_prioritization_fee(&samples, 50), 100);
+        assert_eq!(estimate_prioritization_fee(&samples, 75), 500);
+        assert_eq!(estimate_prioritization_fee(&samples, 90), 1_000);
+        assert_eq!(estimate_prioritization_fee(&samples, 100), 10_000);
+        assert_eq!(estimate_prioritization_fee(&samples, 255), 10_000);
+        assert_eq!(estimate_prioritization_fee(&[], 50), 0);
+    }
+}
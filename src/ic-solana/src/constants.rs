@@ -37,6 +37,13 @@ pub const GET_EPOCH_SCHEDULE_SIZE_ESTIMATE: u64 = 128;
 pub const GET_TOKEN_ACCOUNTS_SIZE_ESTIMATE: u64 = 1400;
 pub const GET_TOKEN_LARGEST_ACCOUNTS_SIZE_ESTIMATE: u64 = 256 * 20;
 pub const GET_VOTE_ACCOUNTS_SIZE_ESTIMATE: u64 = 10000;
+/// The RPC node returns up to 150 entries like `{"slot":348125,"prioritizationFee":1000}`.
+pub const GET_RECENT_PRIORITIZATION_FEES_SIZE_ESTIMATE: u64 = 150 * 60;
+
+/// Maximum number of times the status of a transaction is polled while awaiting its confirmation.
+/// Every attempt makes two outcalls, each taking a few seconds, so this covers well over the
+/// ~150 blocks (about a minute) a blockhash stays valid.
+pub const CONFIRM_TRANSACTION_MAX_ATTEMPTS: u32 = 30;
 
 pub const MAX_GET_BLOCKS_RANGE: u64 = 500_000;
 pub const MAX_GET_SLOT_LEADERS: u64 = 5000;
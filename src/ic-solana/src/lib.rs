@@ -1,8 +1,15 @@
+pub mod associated_token;
+pub mod compute_budget;
 pub mod constants;
 pub mod logs;
 pub mod metrics;
+pub mod nonce;
+pub mod offchain_message;
 pub mod request;
 pub mod rpc_client;
+pub mod siws;
+pub mod spl_token;
+pub mod system_instruction;
 pub mod types;
 pub mod utils;
 
@@ -28,6 +28,18 @@ pub struct Metrics {
     pub err_http_outcall: HashMap<(MetricRpcMethod, MetricRpcHost), u64>,
     #[serde(rename = "errHostNotAllowed")]
     pub err_host_not_allowed: HashMap<MetricRpcHost, u64>,
+    #[serde(rename = "cacheHits")]
+    pub cache_hits: HashMap<MetricRpcMethod, u64>,
+    #[serde(rename = "cacheMisses")]
+    pub cache_misses: HashMap<MetricRpcMethod, u64>,
+    #[serde(rename = "freeRpcRequests")]
+    pub free_rpc_requests: HashMap<MetricPrincipal, u64>,
+    #[serde(rename = "freeRpcBytes")]
+    pub free_rpc_bytes: HashMap<MetricPrincipal, u64>,
+    #[serde(rename = "errFreeRpcQuotaExceeded")]
+    pub err_free_rpc_quota_exceeded: HashMap<MetricPrincipal, u64>,
+    #[serde(rename = "errRateLimited")]
+    pub err_rate_limited: HashMap<MetricMethod, u64>,
 }
 
 pub fn encode_metrics(w: &mut ic_metrics_encoder::MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
@@ -85,6 +97,36 @@ pub fn encode_metrics(w: &mut ic_metrics_encoder::MetricsEncoder<Vec<u8>>) -> st
             &m.err_host_not_allowed,
             "Number of HostNotAllowed errors",
         );
+        w.counter_entries(
+            "sol_cache_hits",
+            &m.cache_hits,
+            "Number of responses served from the cache",
+        );
+        w.counter_entries(
+            "sol_cache_misses",
+            &m.cache_misses,
+            "Number of cacheable requests not found in the cache",
+        );
+        w.counter_entries(
+            "sol_free_rpc_requests",
+            &m.free_rpc_requests,
+            "Number of free RPC requests",
+        );
+        w.counter_entries(
+            "sol_free_rpc_bytes",
+            &m.free_rpc_bytes,
+            "Number of response bytes received for free RPC requests",
+        );
+        w.counter_entries(
+            "sol_err_free_rpc_quota_exceeded",
+            &m.err_free_rpc_quota_exceeded,
+            "Number of requests charged because the free RPC quota was exceeded",
+        );
+        w.counter_entries(
+            "sol_err_rate_limited",
+            &m.err_rate_limited,
+            "Number of calls rejected by rate limits",
+        );
         w.encode_counter(
             "sol_err_no_permission",
             m.err_no_permission.metric_value(),
@@ -155,6 +197,15 @@ impl MetricLabels for MetricAuth {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash, CandidType, Deserialize)]
+pub struct MetricPrincipal(pub String);
+
+impl MetricLabels for MetricPrincipal {
+    fn metric_labels(&self) -> Vec<(&str, &str)> {
+        vec![("principal", &self.0)]
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, CandidType, Deserialize)]
 pub struct MetricRpcMethod(pub String);
 
@@ -0,0 +1,139 @@
+//! Durable transaction nonce account state.
+//!
+//! A nonce account stores a value that can be used in place of a recent blockhash, so that a
+//! transaction does not expire until the nonce is advanced.
+//!
+//! See https://solana.com/developers/guides/advanced/introduction-to-durable-nonces
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    system_instruction::{NONCE_STATE_SIZE, SYSTEM_PROGRAM_ID},
+    types::{Account, BlockHash, FeeCalculator, Pubkey},
+};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum NonceError {
+    #[error("Nonce account is not owned by the system program: {0}")]
+    InvalidAccountOwner(Pubkey),
+    #[error("Invalid nonce account data")]
+    InvalidAccountData,
+    #[error("Nonce account is not initialized")]
+    Uninitialized,
+}
+
+/// Initialized data of a durable transaction nonce account.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Data {
+    /// Address of the account that signs transactions using the nonce account.
+    pub authority: Pubkey,
+    /// Durable nonce value derived from a valid previous blockhash.
+    pub durable_nonce: BlockHash,
+    /// The fee calculator associated with the blockhash.
+    pub fee_calculator: FeeCalculator,
+}
+
+impl Data {
+    /// The value to use as the recent blockhash of a transaction that consumes this nonce.
+    pub fn blockhash(&self) -> BlockHash {
+        self.durable_nonce
+    }
+}
+
+/// The state of a durable transaction nonce account.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum State {
+    #[default]
+    Uninitialized,
+    Initialized(Data),
+}
+
+/// Versioned wrapper of the nonce [`State`], as stored in the account data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Versions {
+    Legacy(Box<State>),
+    /// Current variants have durable nonce and blockhash domains separated.
+    Current(Box<State>),
+}
+
+impl Versions {
+    pub fn state(&self) -> &State {
+        match self {
+            Self::Legacy(state) | Self::Current(state) => state,
+        }
+    }
+}
+
+/// Decodes the nonce [`State`] of an account.
+pub fn state_from_account(account: &Account) -> Result<State, NonceError> {
+    if account.owner != SYSTEM_PROGRAM_ID {
+        return Err(NonceError::InvalidAccountOwner(account.owner));
+    }
+    if account.data.len() != NONCE_STATE_SIZE as usize {
+        return Err(NonceError::InvalidAccountData);
+    }
+    let versions: Versions = bincode::deserialize(&account.data).map_err(|_| NonceError::InvalidAccountData)?;
+    Ok(versions.state().clone())
+}
+
+/// Decodes the nonce [`Data`] of an initialized nonce account.
+pub fn data_from_account(account: &Account) -> Result<Data, NonceError> {
+    match state_from_account(account)? {
+        State::Initialized(data) => Ok(data),
+        State::Uninitialized => Err(NonceError::Uninitialized),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nonce_account(data: Vec<u8>) -> Account {
+        Account {
+            lamports: 1_447_680,
+            data,
+            owner: SYSTEM_PROGRAM_ID,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_data_from_account() {
+        let mut data = vec![1, 0, 0, 0, 1, 0, 0, 0];
+        data.extend([1; 32]);
+        data.extend([2; 32]);
+        data.extend(5000u64.to_le_bytes());
+
+        assert_eq!(
+            data_from_account(&nonce_account(data)),
+            Ok(Data {
+                authority: Pubkey::from([1; 32]),
+                durable_nonce: BlockHash([2; 32]),
+                fee_calculator: FeeCalculator {
+                    lamports_per_signature: 5000
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_uninitialized_account() {
+        let account = nonce_account(vec![1, 0, 0, 0, 0, 0, 0, 0].into_iter().chain([0; 72]).collect());
+        assert_eq!(state_from_account(&account), Ok(State::Uninitialized));
+        assert_eq!(data_from_account(&account), Err(NonceError::Uninitialized));
+    }
+
+    #[test]
+    fn test_invalid_account() {
+        let mut account = nonce_account(vec![0; NONCE_STATE_SIZE as usize]);
+        account.owner = Pubkey::from([1; 32]);
+        assert_eq!(
+            state_from_account(&account),
+            Err(NonceError::InvalidAccountOwner(account.owner))
+        );
+
+        let account = nonce_account(vec![1, 0, 0, 0]);
+        assert_eq!(state_from_account(&account), Err(NonceError::InvalidAccountData));
+    }
+}
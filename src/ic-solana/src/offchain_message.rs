@@ -0,0 +1,236 @@
+//! Off-chain messages.
+//!
+//! An off-chain message is wrapped in an envelope starting with the `\xffsolana offchain` signing
+//! domain before it is signed. The leading `0xff` byte can't start a valid transaction message,
+//! so a signature of an off-chain message can never be replayed as a transaction signature.
+//!
+//! The version 0 envelope is laid out as follows:
+//!
+//! | Field          | Size | Value                              |
+//! |----------------|------|------------------------------------|
+//! | Signing domain | 16   | `\xffsolana offchain`              |
+//! | Version        | 1    | `0`                                |
+//! | Format         | 1    | [`MessageFormat`]                  |
+//! | Length         | 2    | Message length, little endian      |
+//! | Message        | *    | The message bytes                  |
+//!
+//! See https://docs.solanalabs.com/proposals/off-chain-message-signing
+
+use thiserror::Error;
+
+use crate::types::{Pubkey, Signature};
+
+/// Prefix of every serialized off-chain message.
+pub const SIGNING_DOMAIN: &[u8] = b"\xffsolana offchain";
+
+/// Maximum size of a transaction packet, which bounds messages meant to be signed by hardware
+/// wallets.
+const PACKET_DATA_SIZE: usize = 1232;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum OffchainMessageError {
+    #[error("Unsupported off-chain message version: {0}")]
+    UnsupportedVersion(u8),
+    #[error("Invalid off-chain message format: {0}")]
+    InvalidFormat(u8),
+    #[error("Message is empty")]
+    Empty,
+    #[error("Message is too long: {0} bytes")]
+    TooLong(usize),
+    #[error("Message is not valid UTF-8")]
+    InvalidUtf8,
+    #[error("Invalid off-chain message data")]
+    InvalidData,
+}
+
+/// The character set and size class of an off-chain message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MessageFormat {
+    /// Printable ASCII characters, short enough for hardware wallets.
+    RestrictedAscii = 0,
+    /// UTF-8, short enough for hardware wallets.
+    LimitedUtf8 = 1,
+    /// UTF-8 of any supported length.
+    ExtendedUtf8 = 2,
+}
+
+impl TryFrom<u8> for MessageFormat {
+    type Error = OffchainMessageError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::RestrictedAscii),
+            1 => Ok(Self::LimitedUtf8),
+            2 => Ok(Self::ExtendedUtf8),
+            _ => Err(OffchainMessageError::InvalidFormat(value)),
+        }
+    }
+}
+
+/// A version 0 off-chain message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffchainMessage {
+    format: MessageFormat,
+    message: Vec<u8>,
+}
+
+impl OffchainMessage {
+    /// Size of the envelope preceding the message.
+    pub const HEADER_LEN: usize = SIGNING_DOMAIN.len() + 4;
+    /// Maximum length of a message.
+    pub const MAX_LEN: usize = u16::MAX as usize - Self::HEADER_LEN;
+    /// Maximum length of a message that hardware wallets can sign.
+    pub const MAX_LEN_LEDGER: usize = PACKET_DATA_SIZE - Self::HEADER_LEN;
+
+    /// Creates an off-chain message, picking the most restrictive format the message fits in.
+    pub fn new(version: u8, message: &[u8]) -> Result<Self, OffchainMessageError> {
+        if version != 0 {
+            return Err(OffchainMessageError::UnsupportedVersion(version));
+        }
+        if message.is_empty() {
+            return Err(OffchainMessageError::Empty);
+        }
+        if message.len() > Self::MAX_LEN {
+            return Err(OffchainMessageError::TooLong(message.len()));
+        }
+
+        let format = if message.len() <= Self::MAX_LEN_LEDGER {
+            if is_printable_ascii(message) {
+                MessageFormat::RestrictedAscii
+            } else if std::str::from_utf8(message).is_ok() {
+                MessageFormat::LimitedUtf8
+            } else {
+                return Err(OffchainMessageError::InvalidUtf8);
+            }
+        } else if std::str::from_utf8(message).is_ok() {
+            MessageFormat::ExtendedUtf8
+        } else {
+            return Err(OffchainMessageError::InvalidUtf8);
+        };
+
+        Ok(Self {
+            format,
+            message: message.to_vec(),
+        })
+    }
+
+    pub fn format(&self) -> MessageFormat {
+        self.format
+    }
+
+    pub fn message(&self) -> &[u8] {
+        &self.message
+    }
+
+    /// Serializes the message with its envelope. These are the bytes that get signed.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::HEADER_LEN + self.message.len());
+        data.extend_from_slice(SIGNING_DOMAIN);
+        data.push(0);
+        data.push(self.format as u8);
+        data.extend_from_slice(&(self.message.len() as u16).to_le_bytes());
+        data.extend_from_slice(&self.message);
+        data
+    }
+
+    /// Deserializes a message with its envelope, checking that the message matches its format.
+    pub fn deserialize(data: &[u8]) -> Result<Self, OffchainMessageError> {
+        let (header, message) = data
+            .split_at_checked(Self::HEADER_LEN)
+            .ok_or(OffchainMessageError::InvalidData)?;
+        let (domain, header) = header.split_at(SIGNING_DOMAIN.len());
+        if domain != SIGNING_DOMAIN {
+            return Err(OffchainMessageError::InvalidData);
+        }
+
+        let format = MessageFormat::try_from(header[1])?;
+        let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+        if len != message.len() {
+            return Err(OffchainMessageError::InvalidData);
+        }
+
+        let parsed = Self::new(header[0], message)?;
+        if parsed.format != format {
+            return Err(OffchainMessageError::InvalidFormat(format as u8));
+        }
+        Ok(parsed)
+    }
+
+    /// Checks that the message was signed by `signer`.
+    pub fn verify(&self, signer: &Pubkey, signature: &Signature) -> bool {
+        signature.verify(signer.as_ref(), &self.serialize())
+    }
+}
+
+fn is_printable_ascii(data: &[u8]) -> bool {
+    data.iter().all(|&byte| (0x20..=0x7e).contains(&byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_crypto_ed25519::PrivateKey;
+
+    use super::*;
+
+    #[test]
+    fn test_offchain_message() {
+        let message = OffchainMessage::new(0, b"Test Message").unwrap();
+        assert_eq!(message.format(), MessageFormat::RestrictedAscii);
+        assert_eq!(
+            message.serialize(),
+            [
+                255, 115, 111, 108, 97, 110, 97, 32, 111, 102, 102, 99, 104, 97, 105, 110, 0, 0, 12, 0, 84, 101, 115,
+                116, 32, 77, 101, 115, 115, 97, 103, 101
+            ]
+        );
+        assert_eq!(OffchainMessage::deserialize(&message.serialize()), Ok(message));
+
+        let utf8 = OffchainMessage::new(0, "Тестовое сообщение".as_bytes()).unwrap();
+        assert_eq!(utf8.format(), MessageFormat::LimitedUtf8);
+        let extended = OffchainMessage::new(0, &[b'a'; OffchainMessage::MAX_LEN_LEDGER + 1]).unwrap();
+        assert_eq!(extended.format(), MessageFormat::ExtendedUtf8);
+
+        assert_eq!(
+            OffchainMessage::new(1, b"Test Message"),
+            Err(OffchainMessageError::UnsupportedVersion(1))
+        );
+        assert_eq!(OffchainMessage::new(0, b""), Err(OffchainMessageError::Empty));
+        assert_eq!(
+            OffchainMessage::new(0, &[0xff, 0xfe]),
+            Err(OffchainMessageError::InvalidUtf8)
+        );
+        assert_eq!(
+            OffchainMessage::new(0, &[b'a'; OffchainMessage::MAX_LEN + 1]),
+            Err(OffchainMessageError::TooLong(OffchainMessage::MAX_LEN + 1))
+        );
+
+        let mut data = message_data();
+        data[17] = 1;
+        assert_eq!(
+            OffchainMessage::deserialize(&data),
+            Err(OffchainMessageError::InvalidFormat(1))
+        );
+        assert_eq!(
+            OffchainMessage::deserialize(&message_data()[1..]),
+            Err(OffchainMessageError::InvalidData)
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let key = PrivateKey::deserialize_raw(&[7; 32]).unwrap();
+        let signer = Pubkey::from(key.public_key().serialize_raw());
+        let message = OffchainMessage::new(0, b"Test Message").unwrap();
+
+        let signature = Signature::try_from(&key.sign_message(&message.serialize())[..]).unwrap();
+        assert!(message.verify(&signer, &signature));
+
+        let raw_signature = Signature::try_from(&key.sign_message(message.message())[..]).unwrap();
+        assert!(!message.verify(&signer, &raw_signature));
+    }
+
+    fn message_data() -> Vec<u8> {
+        OffchainMessage::new(0, b"Test Message").unwrap().serialize()
+    }
+}
@@ -1,25 +1,29 @@
 use std::{
     cell::RefCell,
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     fmt::Debug,
     str::FromStr,
 };
 
 use base64::{prelude::BASE64_STANDARD, Engine};
 use ic_canister_log::log;
-use ic_cdk::api::management_canister::http_request::{
-    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, TransformContext,
+use ic_cdk::api::{
+    call::RejectionCode,
+    management_canister::http_request::{
+        http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, TransformContext,
+    },
 };
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::{json, Value};
 
 use crate::{
     add_metric_entry,
+    compute_budget::estimate_prioritization_fee,
     constants::*,
     request::RpcRequest,
     rpc_client::multi_call::{MultiCallError, MultiCallResults},
     types::{
-        CommitmentConfig, EncodedConfirmedTransactionWithStatusMeta, Epoch, EpochInfo, EpochSchedule, Pubkey,
+        CommitmentConfig, EncodedConfirmedTransactionWithStatusMeta, Epoch, EpochInfo, EpochSchedule, Message, Pubkey,
         RpcAccountInfoConfig, RpcBlockConfig, RpcBlockProductionConfig, RpcContextConfig, RpcEpochConfig,
         RpcGetVoteAccountsConfig, RpcLargestAccountsConfig, RpcLeaderScheduleConfig, RpcProgramAccountsConfig,
         RpcSendTransactionConfig, RpcSignatureStatusConfig, RpcSignaturesForAddressConfig,
@@ -61,25 +65,39 @@ pub struct RpcClientConfig {
     pub response_size_estimate: Option<u64>,
     pub request_cost_calculator: Option<RequestCostCalculator>,
     pub host_validator: Option<HostValidator>,
+    pub call_observer: Option<CallObserver>,
     pub transform_context: Option<TransformContext>,
     pub use_compression: bool,
     pub is_demo_active: bool,
+    pub free_call_reserver: Option<FreeCallReserver>,
 }
 
 #[derive(Clone, Debug)]
 pub struct RpcClient {
-    pub providers: BTreeSet<RpcApi>,
+    /// Providers called for every request, in the given order without duplicates.
+    pub providers: Vec<RpcApi>,
+    /// IDs of the registered providers behind `providers`, reported to the call observer.
+    pub provider_ids: BTreeMap<RpcApi, String>,
     pub config: RpcClientConfig,
 }
 
 impl RpcClient {
     pub fn new<T: Into<Vec<RpcApi>>>(providers: T, config: Option<RpcClientConfig>) -> Self {
+        let mut providers = providers.into();
+        let mut seen = BTreeSet::new();
+        providers.retain(|provider| seen.insert(provider.clone()));
         Self {
-            providers: providers.into().into_iter().collect(),
+            providers,
+            provider_ids: BTreeMap::new(),
             config: config.unwrap_or_default(),
         }
     }
 
+    pub fn with_provider_ids(mut self, provider_ids: BTreeMap<RpcApi, String>) -> Self {
+        self.provider_ids = provider_ids;
+        self
+    }
+
     fn response_size_estimate(&self, estimate: u64) -> u64 {
         self.config
             .response_size_estimate
@@ -178,8 +196,10 @@ impl RpcClient {
             }
         }
 
-        // Handle cycle accounting if not in demo mode
-        if !self.config.is_demo_active {
+        // Handle cycle accounting if not in demo mode and the call is not free for the caller
+        let mut cycles_charged = 0;
+        let is_free = !self.config.is_demo_active && self.config.free_call_reserver.is_some_and(|reserve| reserve());
+        if !self.config.is_demo_active && !is_free {
             let cycles_available = ic_cdk::api::call::msg_cycles_available128();
             if cycles_available < cycles_cost_with_collateral {
                 return Err(RpcError::Text(format!(
@@ -187,7 +207,7 @@ impl RpcClient {
                     cycles_available, cycles_cost_with_collateral
                 )));
             }
-            ic_cdk::api::call::msg_cycles_accept128(cycles_cost);
+            cycles_charged = ic_cdk::api::call::msg_cycles_accept128(cycles_cost);
             add_metric_entry!(cycles_charged, (rpc_method.clone(), rpc_host.clone()), cycles_cost);
         }
 
@@ -198,7 +218,31 @@ impl RpcClient {
 
         add_metric_entry!(requests, (rpc_method.clone(), rpc_host.clone()), 1);
 
-        match http_request(request, cycles_cost).await {
+        let started_at = ic_cdk::api::time();
+        let result = http_request(request, cycles_cost).await;
+
+        if let Some(observe) = self.config.call_observer {
+            let is_success = matches!(&result, Ok((response,))
+                if u16::try_from(response.status.0.clone()).is_ok_and(|status| (200..300).contains(&status)));
+            let is_too_large = matches!(&result, Err((code, message)) if is_response_too_large(code, message));
+            let response_size = match &result {
+                Ok((response,)) => response.body.len() as u64,
+                Err(_) if is_too_large => max_response_bytes.unwrap_or(HTTP_MAX_SIZE),
+                Err(_) => 0,
+            };
+            let outcome = CallOutcome {
+                provider_id: self.provider_ids.get(provider).cloned(),
+                is_success,
+                latency: ic_cdk::api::time().saturating_sub(started_at),
+                cycles_charged,
+                is_free,
+                is_too_large,
+                response_size,
+            };
+            observe(provider, &outcome);
+        }
+
+        match result {
             Ok((response,)) => {
                 let bytes = if self.config.use_compression {
                     decompress_if_needed(response.body)?
@@ -237,11 +281,40 @@ impl RpcClient {
         }
     }
 
+    /// Calls a provider, retrying with a doubled response size estimate while the response is
+    /// too large, up to `HTTP_MAX_SIZE`. Cycles are charged for every attempt.
+    async fn call_with_retry(
+        &self,
+        provider: &RpcApi,
+        payload: &Value,
+        max_response_bytes: Option<u64>,
+    ) -> RpcResult<Vec<u8>> {
+        let Some(max_response_bytes) = max_response_bytes else {
+            return self.call_internal(provider, payload, None).await;
+        };
+        let mut estimate = ResponseSizeEstimate::new(max_response_bytes);
+        loop {
+            match self.call_internal(provider, payload, Some(estimate.get())).await {
+                Err(RpcError::HttpOutcallError { code, message })
+                    if is_response_too_large(&code, &message) && !estimate.is_max() =>
+                {
+                    let adjusted = estimate.adjust();
+                    log!(
+                        DEBUG,
+                        "Response from {provider:?} exceeds {estimate} bytes, retrying with {adjusted} bytes"
+                    );
+                    estimate = adjusted;
+                }
+                result => return result,
+            }
+        }
+    }
+
     /// Calls multiple providers in parallel and returns the results.
     async fn parallel_call(&self, payload: &Value, max_response_bytes: Option<u64>) -> Vec<RpcResult<Vec<u8>>> {
         futures::future::join_all(self.providers.iter().map(|provider| {
             log!(DEBUG, "[parallel_call]: will call provider: {:?}", provider);
-            async { self.call_internal(provider, payload, max_response_bytes).await }
+            async { self.call_with_retry(provider, payload, max_response_bytes).await }
         }))
         .await
     }
@@ -652,15 +725,27 @@ impl RpcClient {
     /// Method relies on the `getRecentPrioritizationFees` RPC call to get the prioritization fees:
     ///   https://solana.com/docs/rpc/http/getRecentPrioritizationFees
     pub async fn get_recent_prioritization_fees(&self, addresses: &[Pubkey]) -> RpcResult<Vec<RpcPrioritizationFee>> {
+        let addresses = addresses.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
         self.call(
             RpcRequest::GetRecentPrioritizationFees,
             (addresses,),
-            Some(128 * addresses.len() as u64),
+            Some(GET_RECENT_PRIORITIZATION_FEES_SIZE_ESTIMATE),
         )
         .await?
         .into()
     }
 
+    /// Estimates a compute unit price, in micro-lamports, for a transaction with the given message.
+    ///
+    /// The estimate is the given percentile of the prioritization fees paid in recent blocks
+    /// by transactions that write-locked any of the accounts the message writes to.
+    /// See [`estimate_prioritization_fee`] for details.
+    pub async fn get_priority_fee_estimate(&self, message: &Message, percentile: u8) -> RpcResult<u64> {
+        let fees = self.get_recent_prioritization_fees(&message.writable_keys()).await?;
+        Ok(estimate_prioritization_fee(&fees, percentile))
+    }
+
     /// Returns the token balance of an SPL Token account.
     ///
     /// Method relies on the `getTokenAccountBalance` RPC call to get the token balance:
@@ -1078,6 +1163,93 @@ impl RpcClient {
             .map_err(|_| RpcError::ParseError("Failed to parse signature".to_string()))
     }
 
+    /// Submits a signed transaction to the cluster and waits until it reaches the given commitment.
+    ///
+    /// `last_valid_block_height` must be the one returned with the transaction's recent blockhash
+    /// by `getLatestBlockhash`, so that the expiry of the transaction is detected in time.
+    ///
+    /// See [`RpcClient::confirm_transaction`] for details on how the confirmation is awaited.
+    pub async fn send_and_confirm_transaction(
+        &self,
+        tx: Transaction,
+        config: RpcSendTransactionConfig,
+        commitment_config: Option<CommitmentConfig>,
+        last_valid_block_height: u64,
+    ) -> RpcResult<TransactionStatus> {
+        let signature = self.send_transaction(tx, config).await?;
+
+        self.confirm_transaction(
+            &signature,
+            commitment_config.unwrap_or_default(),
+            last_valid_block_height,
+        )
+        .await
+    }
+
+    /// Polls the status of a transaction until it reaches the given commitment or fails.
+    ///
+    /// Every attempt makes two outcalls, `getBlockHeight` and `getSignatureStatuses`, which take
+    /// a few seconds each and so space out the attempts: a canister can't sleep in the middle of
+    /// a call. Polling stops once the block height exceeds `last_valid_block_height` and the
+    /// transaction has not been seen, since it can no longer be processed, with
+    /// [`RpcError::TransactionExpired`]. It also stops with [`RpcError::TransactionNotConfirmed`],
+    /// which carries the signature of the transaction, after
+    /// [`CONFIRM_TRANSACTION_MAX_ATTEMPTS`] attempts or if one of them fails.
+    pub async fn confirm_transaction(
+        &self,
+        signature: &Signature,
+        commitment_config: CommitmentConfig,
+        last_valid_block_height: u64,
+    ) -> RpcResult<TransactionStatus> {
+        let not_confirmed = |error: Option<RpcError>| RpcError::TransactionNotConfirmed {
+            signature: signature.to_string(),
+            error: error.map(Box::new),
+        };
+        let context_config = RpcContextConfig {
+            commitment: Some(commitment_config.commitment),
+            ..Default::default()
+        };
+
+        for _ in 0..CONFIRM_TRANSACTION_MAX_ATTEMPTS {
+            // The block height must be fetched before the status, so that a transaction
+            // processed in between is not reported as expired.
+            let block_height = self
+                .get_block_height(Some(context_config))
+                .await
+                .map_err(|err| not_confirmed(Some(err)))?;
+
+            let status = self
+                .get_signature_statuses(&[*signature], None)
+                .await
+                .map_err(|err| not_confirmed(Some(err)))?
+                .parse_value()
+                .pop()
+                .flatten();
+
+            match status {
+                Some(status) if status.err.is_some() || status.satisfies_commitment(commitment_config) => {
+                    return Ok(status);
+                }
+                Some(_) => {}
+                None if block_height > last_valid_block_height => {
+                    return Err(RpcError::TransactionExpired {
+                        signature: signature.to_string(),
+                        last_valid_block_height,
+                    });
+                }
+                None => {}
+            }
+
+            log!(
+                DEBUG,
+                "[confirm_transaction]: {signature} is not yet {:?}, block height: {block_height}",
+                commitment_config.commitment
+            );
+        }
+
+        Err(not_confirmed(None))
+    }
+
     /// Simulates sending a transaction.
     ///
     /// Method relies on the `simulateTransaction` RPC call to simulate the transaction:
@@ -1159,35 +1331,83 @@ impl RpcClient {
     }
 }
 
-// TODO:
-// pub fn is_response_too_large(code: &RejectionCode, message: &str) -> bool {
-//     code == &RejectionCode::SysFatal && (message.contains("size limit") ||
-// message.contains("length limit")) }
-//
-// #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-// pub struct ResponseSizeEstimate(u64);
-//
-// impl ResponseSizeEstimate {
-//     pub fn new(num_bytes: u64) -> Self {
-//         assert!(num_bytes > 0);
-//         assert!(num_bytes <= MAX_PAYLOAD_SIZE);
-//         Self(num_bytes)
-//     }
-//
-//     /// Describes the expected (90th percentile) number of bytes in the HTTP response body.
-//     /// This number should be lower than `MAX_PAYLOAD_SIZE`.
-//     pub fn get(self) -> u64 {
-//         self.0
-//     }
-//
-//     /// Returns a higher estimate for the payload size.
-//     pub fn adjust(self) -> Self {
-//         Self(self.0.max(1024).saturating_mul(2).min(MAX_PAYLOAD_SIZE))
-//     }
-// }
-//
-// impl std::fmt::Display for ResponseSizeEstimate {
-//     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-//         write!(f, "{}", self.0)
-//     }
-// }
+/// Returns whether an HTTP outcall failed because the response exceeded `max_response_bytes`.
+pub fn is_response_too_large(code: &RejectionCode, message: &str) -> bool {
+    code == &RejectionCode::SysFatal && (message.contains("size limit") || message.contains("length limit"))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ResponseSizeEstimate(u64);
+
+impl ResponseSizeEstimate {
+    pub fn new(num_bytes: u64) -> Self {
+        assert!(num_bytes > 0);
+        Self(num_bytes.min(HTTP_MAX_SIZE))
+    }
+
+    /// Describes the expected (90th percentile) number of bytes in the HTTP response, headers
+    /// included. This number is at most `HTTP_MAX_SIZE`.
+    pub fn get(self) -> u64 {
+        self.0
+    }
+
+    /// Returns a higher estimate for the response size.
+    pub fn adjust(self) -> Self {
+        Self(self.0.max(1024).saturating_mul(2).min(HTTP_MAX_SIZE))
+    }
+
+    /// Returns whether the estimate can't be adjusted any further.
+    pub fn is_max(self) -> bool {
+        self.0 >= HTTP_MAX_SIZE
+    }
+}
+
+impl std::fmt::Display for ResponseSizeEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_size_estimate() {
+        assert_eq!(ResponseSizeEstimate::new(100).adjust().get(), 2048);
+        assert_eq!(ResponseSizeEstimate::new(4096).adjust().get(), 8192);
+        assert_eq!(
+            ResponseSizeEstimate::new(HTTP_MAX_SIZE - 1).adjust().get(),
+            HTTP_MAX_SIZE
+        );
+        assert_eq!(ResponseSizeEstimate::new(HTTP_MAX_SIZE + 1).get(), HTTP_MAX_SIZE);
+        assert!(!ResponseSizeEstimate::new(HTTP_MAX_SIZE - 1).is_max());
+        assert!(ResponseSizeEstimate::new(HTTP_MAX_SIZE).is_max());
+    }
+
+    #[test]
+    fn test_is_response_too_large() {
+        assert!(is_response_too_large(
+            &RejectionCode::SysFatal,
+            "Http body exceeds size limit of 1024 bytes."
+        ));
+        assert!(is_response_too_large(
+            &RejectionCode::SysFatal,
+            "Header length limit exceeded"
+        ));
+        assert!(!is_response_too_large(&RejectionCode::SysTransient, "size limit"));
+        assert!(!is_response_too_large(&RejectionCode::SysFatal, "Connection refused"));
+    }
+
+    #[test]
+    fn should_keep_the_order_of_providers() {
+        let apis = [
+            "https://b.example.com",
+            "https://a.example.com",
+            "https://b.example.com",
+        ]
+        .map(RpcApi::new);
+        let client = RpcClient::new(apis.to_vec(), None);
+        assert_eq!(client.providers, apis[..2]);
+    }
+}
@@ -88,6 +88,30 @@ impl Debug for RpcApi {
 
 pub type RequestCostCalculator = fn(&CanisterHttpRequestArgument) -> (u128, u128);
 pub type HostValidator = fn(&str) -> bool;
+/// Called after every call to a provider.
+pub type CallObserver = fn(&RpcApi, &CallOutcome);
+/// Called before every call to a provider to reserve a free call for the caller. Returns `false` if
+/// the caller has to pay for the call.
+pub type FreeCallReserver = fn() -> bool;
+
+/// Outcome of a call to a provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallOutcome {
+    /// ID of the registered provider that was called, `None` for custom APIs.
+    pub provider_id: Option<String>,
+    /// Whether the HTTP outcall succeeded with a 2xx status.
+    pub is_success: bool,
+    /// Latency in nanoseconds.
+    pub latency: u64,
+    pub cycles_charged: u128,
+    /// Whether the call was free for the caller.
+    pub is_free: bool,
+    /// Whether the response exceeded the maximum response size.
+    pub is_too_large: bool,
+    /// Size of the response body in bytes, or the maximum response size if the response exceeded
+    /// it.
+    pub response_size: u64,
+}
 
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Error, Deserialize, CandidType)]
 pub enum RpcError {
@@ -108,6 +132,24 @@ pub enum RpcError {
 
     #[error("{0}")]
     Text(String),
+
+    #[error("Transaction {signature} was not confirmed before block height {last_valid_block_height}")]
+    TransactionExpired {
+        signature: String,
+        last_valid_block_height: u64,
+    },
+
+    /// The transaction was sent, but its confirmation could not be awaited because of `error`, or
+    /// because the maximum number of attempts was reached if there is no error.
+    #[error("Transaction {signature} was not confirmed: {}", .error.as_ref().map_or_else(|| "maximum number of attempts reached".to_string(), ToString::to_string))]
+    TransactionNotConfirmed {
+        signature: String,
+        error: Option<Box<RpcError>>,
+    },
+
+    /// The caller exceeded a rate limit and may retry after `retry_after` nanoseconds.
+    #[error("Rate limit exceeded, retry after {retry_after} ns")]
+    RateLimited { retry_after: u64 },
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, CandidType, Serialize, Deserialize, Error)]
@@ -144,6 +186,31 @@ pub enum RpcServices {
     Localnet,
     Provider(Vec<String>),
     Custom(Vec<RpcApi>),
+    /// The `count` healthiest providers registered for `cluster`.
+    Healthiest {
+        cluster: RpcCluster,
+        count: u8,
+    },
+}
+
+/// A Solana cluster that RPC providers serve.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum RpcCluster {
+    Mainnet,
+    Testnet,
+    Devnet,
+    Localnet,
+}
+
+impl From<RpcCluster> for Cluster {
+    fn from(cluster: RpcCluster) -> Self {
+        match cluster {
+            RpcCluster::Mainnet => Cluster::Mainnet,
+            RpcCluster::Testnet => Cluster::Testnet,
+            RpcCluster::Devnet => Cluster::Devnet,
+            RpcCluster::Localnet => Cluster::Localnet,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Default, CandidType, Deserialize)]
@@ -0,0 +1,531 @@
+//! Sign-In With Solana (SIWS) messages.
+//!
+//! A SIWS message is a human readable text that a wallet signs to prove control of its address to
+//! an application, in the format produced by the `solana:signIn` feature of Solana wallets:
+//!
+//! ```text
+//! example.com wants you to sign in with your Solana account:
+//! 83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri
+//!
+//! Sign in to Example
+//!
+//! URI: https://example.com
+//! Version: 1
+//! Nonce: 32891756
+//! Issued At: 2024-01-01T00:00:00.000Z
+//! Expiration Time: 2024-01-01T00:10:00.000Z
+//! ```
+//!
+//! See https://github.com/phantom/sign-in-with-solana
+
+use std::{fmt, str::FromStr};
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::types::Pubkey;
+
+const HEADER_SUFFIX: &str = " wants you to sign in with your Solana account:";
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SiwsError {
+    #[error("Invalid SIWS message: {0}")]
+    InvalidMessage(String),
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+    #[error("Invalid timestamp: {0}")]
+    InvalidTimestamp(String),
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error("Domain mismatch: expected {expected}, got {actual}")]
+    DomainMismatch { expected: String, actual: String },
+    #[error("Nonce mismatch")]
+    NonceMismatch,
+    #[error("Message has expired")]
+    Expired,
+    #[error("Message is not valid yet")]
+    NotYetValid,
+}
+
+/// A Sign-In With Solana message.
+///
+/// Timestamps are RFC 3339 strings, as wallets display them to the user.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SiwsMessage {
+    /// The domain requesting the sign-in.
+    pub domain: String,
+    /// The base58 encoded address signing in.
+    pub address: String,
+    pub statement: Option<String>,
+    pub uri: Option<String>,
+    pub version: Option<String>,
+    pub chain_id: Option<String>,
+    pub nonce: Option<String>,
+    pub issued_at: Option<String>,
+    pub expiration_time: Option<String>,
+    pub not_before: Option<String>,
+    pub request_id: Option<String>,
+    pub resources: Option<Vec<String>>,
+}
+
+/// The checks [`SiwsMessage::verify`] performs besides the signature.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SiwsVerifyOptions {
+    /// The domain the message must be issued for.
+    pub domain: Option<String>,
+    /// The nonce the message must contain.
+    pub nonce: Option<String>,
+    /// The current time in nanoseconds since the Unix epoch, checked against the expiration and
+    /// not-before times of the message.
+    pub time: Option<u64>,
+}
+
+impl SiwsMessage {
+    /// Verifies that the message was signed by its address and passes the given checks.
+    ///
+    /// Returns the address that signed in.
+    pub fn verify(&self, signature: &[u8], options: &SiwsVerifyOptions) -> Result<Pubkey, SiwsError> {
+        let pubkey = Pubkey::from_str(&self.address).map_err(|_| SiwsError::InvalidAddress(self.address.clone()))?;
+
+        if let Some(domain) = &options.domain {
+            if *domain != self.domain {
+                return Err(SiwsError::DomainMismatch {
+                    expected: domain.clone(),
+                    actual: self.domain.clone(),
+                });
+            }
+        }
+
+        if let Some(nonce) = &options.nonce {
+            if self.nonce.as_ref() != Some(nonce) {
+                return Err(SiwsError::NonceMismatch);
+            }
+        }
+
+        if let Some(time) = options.time {
+            if let Some(expiration_time) = &self.expiration_time {
+                if time >= parse_rfc3339(expiration_time)? {
+                    return Err(SiwsError::Expired);
+                }
+            }
+            if let Some(not_before) = &self.not_before {
+                if time < parse_rfc3339(not_before)? {
+                    return Err(SiwsError::NotYetValid);
+                }
+            }
+        }
+
+        if signature.len() != 64
+            || !pubkey.is_on_curve()
+            || !pubkey.verify_signature(self.to_string().as_bytes(), signature)
+        {
+            return Err(SiwsError::InvalidSignature);
+        }
+
+        Ok(pubkey)
+    }
+}
+
+impl fmt::Display for SiwsMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{HEADER_SUFFIX}\n{}", self.domain, self.address)?;
+
+        if let Some(statement) = &self.statement {
+            write!(f, "\n\n{statement}")?;
+        }
+
+        let mut fields = vec![];
+        let optional_fields = [
+            ("URI", &self.uri),
+            ("Version", &self.version),
+            ("Chain ID", &self.chain_id),
+            ("Nonce", &self.nonce),
+            ("Issued At", &self.issued_at),
+            ("Expiration Time", &self.expiration_time),
+            ("Not Before", &self.not_before),
+            ("Request ID", &self.request_id),
+        ];
+        for (name, value) in optional_fields {
+            if let Some(value) = value {
+                fields.push(format!("{name}: {value}"));
+            }
+        }
+        if let Some(resources) = &self.resources {
+            fields.push("Resources:".to_string());
+            fields.extend(resources.iter().map(|resource| format!("- {resource}")));
+        }
+
+        if !fields.is_empty() {
+            write!(f, "\n\n{}", fields.join("\n"))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SiwsMessage {
+    type Err = SiwsError;
+
+    /// Parses a SIWS message.
+    ///
+    /// Only messages in the canonical format are accepted, so that the parsed message serializes
+    /// back to the exact text that was signed.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| SiwsError::InvalidMessage(reason.to_string());
+
+        let mut lines = s.split('\n').peekable();
+        let domain = lines
+            .next()
+            .and_then(|line| line.strip_suffix(HEADER_SUFFIX))
+            .filter(|domain| !domain.is_empty())
+            .ok_or_else(|| invalid("missing header"))?;
+        let address = lines.next().ok_or_else(|| invalid("missing address"))?;
+        Pubkey::from_str(address).map_err(|_| SiwsError::InvalidAddress(address.to_string()))?;
+
+        let mut message = SiwsMessage {
+            domain: domain.to_string(),
+            address: address.to_string(),
+            ..Default::default()
+        };
+
+        // The statement and the fields are each preceded by an empty line
+        let mut sections = vec![];
+        while lines.peek().is_some() {
+            if lines.next() != Some("") {
+                return Err(invalid("expected an empty line"));
+            }
+            let mut section = vec![];
+            while let Some(line) = lines.next_if(|line| !line.is_empty()) {
+                section.push(line);
+            }
+            if section.is_empty() {
+                return Err(invalid("unexpected empty line"));
+            }
+            sections.push(section);
+        }
+
+        let fields = match sections.as_slice() {
+            [] => None,
+            [fields] if is_field(fields[0]) => Some(fields),
+            [statement] => {
+                message.statement = Some(statement.join("\n"));
+                None
+            }
+            [statement, fields] if is_field(fields[0]) => {
+                message.statement = Some(statement.join("\n"));
+                Some(fields)
+            }
+            _ => return Err(invalid("unexpected section")),
+        };
+
+        let mut fields = fields.into_iter().flatten().peekable();
+        let mut parse_field = |name: &str| -> Option<String> {
+            fields
+                .next_if(|line| line.starts_with(&format!("{name}: ")))
+                .map(|line| line[name.len() + 2..].to_string())
+        };
+        message.uri = parse_field("URI");
+        message.version = parse_field("Version");
+        message.chain_id = parse_field("Chain ID");
+        message.nonce = parse_field("Nonce");
+        message.issued_at = parse_field("Issued At");
+        message.expiration_time = parse_field("Expiration Time");
+        message.not_before = parse_field("Not Before");
+        message.request_id = parse_field("Request ID");
+
+        if fields.next_if(|line| **line == "Resources:").is_some() {
+            let mut resources = vec![];
+            while let Some(line) = fields.next_if(|line| line.starts_with("- ")) {
+                resources.push(line[2..].to_string());
+            }
+            message.resources = Some(resources);
+        }
+        if let Some(line) = fields.next() {
+            return Err(SiwsError::InvalidMessage(format!("unexpected line: {line}")));
+        }
+
+        for timestamp in [&message.issued_at, &message.expiration_time, &message.not_before]
+            .into_iter()
+            .flatten()
+        {
+            parse_rfc3339(timestamp)?;
+        }
+
+        Ok(message)
+    }
+}
+
+fn is_field(line: &str) -> bool {
+    [
+        "URI: ",
+        "Version: ",
+        "Chain ID: ",
+        "Nonce: ",
+        "Issued At: ",
+        "Expiration Time: ",
+        "Not Before: ",
+        "Request ID: ",
+    ]
+    .iter()
+    .any(|prefix| line.starts_with(prefix))
+        || line == "Resources:"
+}
+
+/// Parses an RFC 3339 timestamp into nanoseconds since the Unix epoch.
+pub fn parse_rfc3339(timestamp: &str) -> Result<u64, SiwsError> {
+    let invalid = || SiwsError::InvalidTimestamp(timestamp.to_string());
+    // Byte offsets are only char boundaries in ASCII strings
+    if !timestamp.is_ascii() {
+        return Err(invalid());
+    }
+    let bytes = timestamp.as_bytes();
+    if bytes.len() < 20 || bytes[4] != b'-' || bytes[7] != b'-' || !matches!(bytes[10], b'T' | b't') {
+        return Err(invalid());
+    }
+    if bytes[13] != b':' || bytes[16] != b':' {
+        return Err(invalid());
+    }
+    let number = |range: std::ops::Range<usize>| -> Result<i64, SiwsError> {
+        let digits = &timestamp[range];
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        digits.parse().map_err(|_| invalid())
+    };
+
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return Err(invalid());
+    }
+    if hour > 23 || minute > 59 || second > 60 {
+        return Err(invalid());
+    }
+
+    let mut rest = &timestamp[19..];
+    let mut nanos = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if len == 0 {
+            return Err(invalid());
+        }
+        let digits = &fraction[..len.min(9)];
+        nanos = digits.parse::<i64>().map_err(|_| invalid())? * 10_i64.pow(9 - digits.len() as u32);
+        rest = &fraction[len..];
+    }
+
+    let offset_seconds = match rest {
+        "Z" | "z" => 0,
+        _ if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+            let sign = match rest.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return Err(invalid()),
+            };
+            let (hours, minutes) = (
+                number(timestamp.len() - 5..timestamp.len() - 3)?,
+                number(timestamp.len() - 2..timestamp.len())?,
+            );
+            if hours > 23 || minutes > 59 {
+                return Err(invalid());
+            }
+            sign * (hours * 3600 + minutes * 60)
+        }
+        _ => return Err(invalid()),
+    };
+
+    let seconds =
+        days_from_civil(year, month, day) * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second - offset_seconds;
+    if seconds < 0 {
+        return Err(invalid());
+    }
+    Ok(seconds as u64 * NANOS_PER_SECOND + nanos as u64)
+}
+
+/// Formats nanoseconds since the Unix epoch as an RFC 3339 timestamp with millisecond precision,
+/// like JavaScript's `Date.prototype.toISOString`.
+pub fn format_rfc3339(timestamp_nanos: u64) -> String {
+    let seconds = (timestamp_nanos / NANOS_PER_SECOND) as i64;
+    let millis = (timestamp_nanos % NANOS_PER_SECOND) / 1_000_000;
+    let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+    let seconds_of_day = seconds.rem_euclid(SECONDS_PER_DAY);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{millis:03}Z",
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Conversions between civil dates and days since the Unix epoch, from
+// http://howardhinnant.github.io/date_algorithms.html
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_crypto_ed25519::PrivateKey;
+
+    use super::*;
+
+    const MESSAGE: &str = concat!(
+        "example.com wants you to sign in with your Solana account:\n",
+        "83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri\n",
+        "\n",
+        "Sign in to Example\n",
+        "\n",
+        "URI: https://example.com\n",
+        "Version: 1\n",
+        "Chain ID: mainnet\n",
+        "Nonce: 32891756\n",
+        "Issued At: 2024-01-01T00:00:00.000Z\n",
+        "Expiration Time: 2024-01-01T00:10:00.000Z\n",
+        "Resources:\n",
+        "- https://example.com/terms\n",
+        "- ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq"
+    );
+
+    #[test]
+    fn test_parse_and_format() {
+        let message = SiwsMessage::from_str(MESSAGE).unwrap();
+        assert_eq!(message.domain, "example.com");
+        assert_eq!(message.address, "83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri");
+        assert_eq!(message.statement.as_deref(), Some("Sign in to Example"));
+        assert_eq!(message.nonce.as_deref(), Some("32891756"));
+        assert_eq!(message.not_before, None);
+        assert_eq!(message.resources.as_ref().map(Vec::len), Some(2));
+        assert_eq!(message.to_string(), MESSAGE);
+
+        let minimal =
+            "example.com wants you to sign in with your Solana account:\n83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri";
+        assert_eq!(SiwsMessage::from_str(minimal).unwrap().to_string(), minimal);
+
+        let without_statement = concat!(
+            "example.com wants you to sign in with your Solana account:\n",
+            "83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri\n",
+            "\n",
+            "Nonce: 1"
+        );
+        let message = SiwsMessage::from_str(without_statement).unwrap();
+        assert_eq!(message.statement, None);
+        assert_eq!(message.to_string(), without_statement);
+
+        assert!(SiwsMessage::from_str("example.com wants you to sign in with your Ethereum account:").is_err());
+        assert!(SiwsMessage::from_str(&format!("{MESSAGE}\nUnknown: field")).is_err());
+        assert!(SiwsMessage::from_str(&MESSAGE.replace("2024-01-01T00:10", "2024-13-01T00:10")).is_err());
+    }
+
+    #[test]
+    fn test_rfc3339() {
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), Ok(0));
+        assert_eq!(
+            parse_rfc3339("2024-01-01T00:00:00.000Z"),
+            Ok(1_704_067_200 * NANOS_PER_SECOND)
+        );
+        assert_eq!(
+            parse_rfc3339("2024-01-01T02:00:00.5+02:00"),
+            Ok(1_704_067_200 * NANOS_PER_SECOND + 500_000_000)
+        );
+        assert_eq!(
+            parse_rfc3339("2024-02-29T12:30:00Z"),
+            Ok(1_709_209_800 * NANOS_PER_SECOND)
+        );
+        assert!(parse_rfc3339("2023-02-29T12:30:00Z").is_err());
+        assert!(parse_rfc3339("2024-01-01 00:00:00Z").is_err());
+        assert!(parse_rfc3339("2024-01-01T00:00:00").is_err());
+        assert!(parse_rfc3339("2024-01-01T00:00:0éZ").is_err());
+        assert!(parse_rfc3339("2024-01-01T00:00:00.5éZ").is_err());
+
+        assert_eq!(
+            format_rfc3339(1_709_209_800 * NANOS_PER_SECOND + 123_456_789),
+            "2024-02-29T12:30:00.123Z"
+        );
+        assert_eq!(format_rfc3339(0), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn test_verify() {
+        let key = PrivateKey::deserialize_raw(&[7; 32]).unwrap();
+        let address = Pubkey::from(key.public_key().serialize_raw());
+        let message = SiwsMessage {
+            address: address.to_string(),
+            ..SiwsMessage::from_str(MESSAGE).unwrap()
+        };
+        let signature = key.sign_message(message.to_string().as_bytes());
+
+        let issued_at = parse_rfc3339("2024-01-01T00:00:00Z").unwrap();
+        let options = SiwsVerifyOptions {
+            domain: Some("example.com".to_string()),
+            nonce: Some("32891756".to_string()),
+            time: Some(issued_at),
+        };
+        assert_eq!(message.verify(&signature, &options), Ok(address));
+        assert_eq!(message.verify(&[0; 64], &options), Err(SiwsError::InvalidSignature));
+
+        let tampered = SiwsMessage {
+            nonce: Some("1".to_string()),
+            ..message.clone()
+        };
+        assert_eq!(
+            tampered.verify(&signature, &SiwsVerifyOptions::default()),
+            Err(SiwsError::InvalidSignature)
+        );
+
+        let wrong_domain = SiwsVerifyOptions {
+            domain: Some("evil.com".to_string()),
+            ..options.clone()
+        };
+        assert!(matches!(
+            message.verify(&[0; 64], &wrong_domain),
+            Err(SiwsError::DomainMismatch { .. })
+        ));
+
+        let wrong_nonce = SiwsVerifyOptions {
+            nonce: Some("1".to_string()),
+            ..options.clone()
+        };
+        assert_eq!(message.verify(&[0; 64], &wrong_nonce), Err(SiwsError::NonceMismatch));
+
+        let expired = SiwsVerifyOptions {
+            time: Some(issued_at + 600 * NANOS_PER_SECOND),
+            ..options
+        };
+        assert_eq!(message.verify(&[0; 64], &expired), Err(SiwsError::Expired));
+    }
+}
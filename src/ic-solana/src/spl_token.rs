@@ -0,0 +1,45 @@
+//! Instruction builders and account decoders for the SPL Token and Token-2022 programs.
+//!
+//! See https://spl.solana.com/token and https://spl.solana.com/token-2022
+
+pub mod extension;
+pub mod instruction;
+pub mod state;
+
+pub use extension::*;
+pub use instruction::*;
+pub use state::*;
+use thiserror::Error;
+
+use crate::types::Pubkey;
+
+/// The SPL Token program id.
+pub const TOKEN_PROGRAM_ID: Pubkey = Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// The SPL Token-2022 program id.
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = Pubkey::from_str_const("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Maximum number of multisignature signers (max N)
+pub const MAX_SIGNERS: usize = 11;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TokenError {
+    #[error("Incorrect token program id: {0}")]
+    IncorrectProgramId(Pubkey),
+    #[error("Too many multisig signers: {0}")]
+    TooManySigners(usize),
+    #[error("Invalid account data")]
+    InvalidAccountData,
+    #[error("Account is not initialized")]
+    UninitializedAccount,
+    #[error("Invalid instruction")]
+    InvalidInstruction,
+}
+
+/// Checks that the supplied program id is one of the token programs.
+pub fn check_program_account(token_program_id: &Pubkey) -> Result<(), TokenError> {
+    if token_program_id != &TOKEN_PROGRAM_ID && token_program_id != &TOKEN_2022_PROGRAM_ID {
+        return Err(TokenError::IncorrectProgramId(*token_program_id));
+    }
+    Ok(())
+}
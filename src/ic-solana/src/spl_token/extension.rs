@@ -0,0 +1,395 @@
+use crate::{
+    spl_token::{state::Reader, BaseState, TokenError, MULTISIG_LEN},
+    types::Pubkey,
+};
+
+/// Length of the base account in Token-2022, after which the account type
+/// and the extensions are stored. Mints are padded up to this length.
+const BASE_ACCOUNT_LENGTH: usize = 165;
+
+/// Bytes taken by the type and length fields of each TLV entry.
+const TLV_HEADER_LENGTH: usize = 4;
+
+/// Extensions that can be applied to mints or accounts by Token-2022.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExtensionType {
+    /// Used as padding if the account size would otherwise be 355, same as a multisig
+    Uninitialized,
+    /// Includes transfer fee rate info and accompanying authorities to withdraw
+    /// and set the fee
+    TransferFeeConfig,
+    /// Includes withheld transfer fees
+    TransferFeeAmount,
+    /// Includes an optional mint close authority
+    MintCloseAuthority,
+    /// Auditor configuration for confidential transfers
+    ConfidentialTransferMint,
+    /// State for confidential transfers
+    ConfidentialTransferAccount,
+    /// Specifies the default Account::state for new Accounts
+    DefaultAccountState,
+    /// Indicates that the Account owner authority cannot be changed
+    ImmutableOwner,
+    /// Require inbound transfers to have memo
+    MemoTransfer,
+    /// Indicates that the tokens from this mint can't be transferred
+    NonTransferable,
+    /// Tokens accrue interest over time,
+    InterestBearingConfig,
+    /// Locks privileged token operations from happening via CPI
+    CpiGuard,
+    /// Includes an optional permanent delegate
+    PermanentDelegate,
+    /// Indicates that the tokens in this account belong to a non-transferable
+    /// mint
+    NonTransferableAccount,
+    /// Mint requires a CPI to a program implementing the "transfer hook"
+    /// interface
+    TransferHook,
+    /// Indicates that the tokens in this account belong to a mint with a
+    /// transfer hook
+    TransferHookAccount,
+    /// Includes encrypted withheld fees and the encryption public that they are
+    /// encrypted under
+    ConfidentialTransferFeeConfig,
+    /// Includes confidential withheld transfer fees
+    ConfidentialTransferFeeAmount,
+    /// Mint contains a pointer to another account (or the same account) that
+    /// holds metadata
+    MetadataPointer,
+    /// Mint contains token-metadata
+    TokenMetadata,
+    /// Mint contains a pointer to another account (or the same account) that
+    /// holds group configurations
+    GroupPointer,
+    /// Mint contains token group configurations
+    TokenGroup,
+    /// Mint contains a pointer to another account (or the same account) that
+    /// holds group member configurations
+    GroupMemberPointer,
+    /// Mint contains token group member configurations
+    TokenGroupMember,
+    /// Mint allowing the minting and burning of confidential tokens
+    ConfidentialMintBurn,
+    /// Tokens whose UI amount is scaled by a given amount
+    ScaledUiAmount,
+    /// Tokens where minting / burning / transferring can be paused
+    Pausable,
+    /// Indicates that the account belongs to a pausable mint
+    PausableAccount,
+    /// An extension type not known to this library
+    Unknown(u16),
+}
+
+impl From<u16> for ExtensionType {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => Self::Uninitialized,
+            1 => Self::TransferFeeConfig,
+            2 => Self::TransferFeeAmount,
+            3 => Self::MintCloseAuthority,
+            4 => Self::ConfidentialTransferMint,
+            5 => Self::ConfidentialTransferAccount,
+            6 => Self::DefaultAccountState,
+            7 => Self::ImmutableOwner,
+            8 => Self::MemoTransfer,
+            9 => Self::NonTransferable,
+            10 => Self::InterestBearingConfig,
+            11 => Self::CpiGuard,
+            12 => Self::PermanentDelegate,
+            13 => Self::NonTransferableAccount,
+            14 => Self::TransferHook,
+            15 => Self::TransferHookAccount,
+            16 => Self::ConfidentialTransferFeeConfig,
+            17 => Self::ConfidentialTransferFeeAmount,
+            18 => Self::MetadataPointer,
+            19 => Self::TokenMetadata,
+            20 => Self::GroupPointer,
+            21 => Self::TokenGroup,
+            22 => Self::GroupMemberPointer,
+            23 => Self::TokenGroupMember,
+            24 => Self::ConfidentialMintBurn,
+            25 => Self::ScaledUiAmount,
+            26 => Self::Pausable,
+            27 => Self::PausableAccount,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+/// A single type-length-value entry of a Token-2022 account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    pub extension_type: ExtensionType,
+    pub data: Vec<u8>,
+}
+
+/// A token account or mint together with its raw Token-2022 extensions.
+///
+/// Accounts owned by the original token program never have extensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateWithExtensions<S> {
+    pub base: S,
+    pub extensions: Vec<Extension>,
+}
+
+impl<S: BaseState> StateWithExtensions<S> {
+    /// Unpacks the base state and the TLV-encoded extensions that follow it.
+    pub fn unpack(data: &[u8]) -> Result<Self, TokenError> {
+        let extensions = if data.len() == S::LEN {
+            vec![]
+        } else if data.len() > BASE_ACCOUNT_LENGTH && data.len() != MULTISIG_LEN {
+            // Mints are padded with zeroes up to the length of a base account.
+            if data[S::LEN..BASE_ACCOUNT_LENGTH].iter().any(|b| *b != 0) {
+                return Err(TokenError::InvalidAccountData);
+            }
+            if data[BASE_ACCOUNT_LENGTH] != S::ACCOUNT_TYPE as u8 {
+                return Err(TokenError::InvalidAccountData);
+            }
+            unpack_extensions(&data[BASE_ACCOUNT_LENGTH + 1..])?
+        } else {
+            return Err(TokenError::InvalidAccountData);
+        };
+
+        let base = S::unpack_from_slice(&data[..S::LEN])?;
+        if !base.is_initialized() {
+            return Err(TokenError::UninitializedAccount);
+        }
+
+        Ok(Self { base, extensions })
+    }
+
+    /// Returns the types of all extensions present on the account.
+    pub fn get_extension_types(&self) -> Vec<ExtensionType> {
+        self.extensions.iter().map(|ext| ext.extension_type).collect()
+    }
+
+    /// Returns the raw bytes of the given extension, if present.
+    pub fn get_extension_bytes(&self, extension_type: ExtensionType) -> Option<&[u8]> {
+        self.extensions
+            .iter()
+            .find(|ext| ext.extension_type == extension_type)
+            .map(|ext| ext.data.as_slice())
+    }
+
+    /// Returns the transfer fee configuration of a Token-2022 mint, if present.
+    pub fn get_transfer_fee_config(&self) -> Result<Option<TransferFeeConfig>, TokenError> {
+        self.get_extension_bytes(ExtensionType::TransferFeeConfig)
+            .map(TransferFeeConfig::unpack)
+            .transpose()
+    }
+}
+
+fn unpack_extensions(tlv_data: &[u8]) -> Result<Vec<Extension>, TokenError> {
+    let mut extensions = vec![];
+    let mut reader = Reader::new(tlv_data);
+    while reader.remaining() >= TLV_HEADER_LENGTH {
+        let extension_type = ExtensionType::from(reader.read_u16()?);
+        // An uninitialized type marks the end of the extensions, the rest is padding.
+        if extension_type == ExtensionType::Uninitialized {
+            break;
+        }
+        let length = reader.read_u16()? as usize;
+        let data = reader.read_bytes(length)?.to_vec();
+        extensions.push(Extension { extension_type, data });
+    }
+    Ok(extensions)
+}
+
+/// Basis points are 1/100th of a percent.
+pub const ONE_IN_BASIS_POINTS: u128 = 10_000;
+
+/// Transfer fee information
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TransferFee {
+    /// First epoch where the transfer fee takes effect
+    pub epoch: u64,
+    /// Maximum fee assessed on transfers, expressed as an amount of tokens
+    pub maximum_fee: u64,
+    /// Amount of transfer collected as fees, expressed as basis points of the
+    /// transfer amount, ie. increments of 0.01%
+    pub transfer_fee_basis_points: u16,
+}
+
+impl TransferFee {
+    /// Calculate the transfer fee
+    pub fn calculate_fee(&self, pre_fee_amount: u64) -> Option<u64> {
+        let transfer_fee_basis_points = self.transfer_fee_basis_points as u128;
+        if transfer_fee_basis_points == 0 || pre_fee_amount == 0 {
+            return Some(0);
+        }
+        let numerator = (pre_fee_amount as u128).checked_mul(transfer_fee_basis_points)?;
+        // Fees are rounded up, so that transfers never pay less than the configured rate.
+        let raw_fee = numerator.div_ceil(ONE_IN_BASIS_POINTS);
+        let fee = u64::try_from(raw_fee).ok()?;
+        Some(fee.min(self.maximum_fee))
+    }
+
+    fn unpack(reader: &mut Reader) -> Result<Self, TokenError> {
+        Ok(Self {
+            epoch: reader.read_u64()?,
+            maximum_fee: reader.read_u64()?,
+            transfer_fee_basis_points: reader.read_u16()?,
+        })
+    }
+}
+
+/// Transfer fee extension data for mints.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TransferFeeConfig {
+    /// Optional authority to set the fee
+    pub transfer_fee_config_authority: Option<Pubkey>,
+    /// Withdraw from mint instructions must be signed by this key
+    pub withdraw_withheld_authority: Option<Pubkey>,
+    /// Withheld transfer fee tokens that have been moved to the mint for
+    /// withdrawal
+    pub withheld_amount: u64,
+    /// Older transfer fee, used if the current epoch < new_transfer_fee.epoch
+    pub older_transfer_fee: TransferFee,
+    /// Newer transfer fee, used if the current epoch >= new_transfer_fee.epoch
+    pub newer_transfer_fee: TransferFee,
+}
+
+impl TransferFeeConfig {
+    pub fn unpack(data: &[u8]) -> Result<Self, TokenError> {
+        let mut reader = Reader::new(data);
+        Ok(Self {
+            transfer_fee_config_authority: reader.read_optional_non_zero_pubkey()?,
+            withdraw_withheld_authority: reader.read_optional_non_zero_pubkey()?,
+            withheld_amount: reader.read_u64()?,
+            older_transfer_fee: TransferFee::unpack(&mut reader)?,
+            newer_transfer_fee: TransferFee::unpack(&mut reader)?,
+        })
+    }
+
+    /// Get the fee for the given epoch
+    pub fn get_epoch_fee(&self, epoch: u64) -> &TransferFee {
+        if epoch >= self.newer_transfer_fee.epoch {
+            &self.newer_transfer_fee
+        } else {
+            &self.older_transfer_fee
+        }
+    }
+
+    /// Calculate the fee for the given epoch and input amount
+    pub fn calculate_epoch_fee(&self, epoch: u64, pre_fee_amount: u64) -> Option<u64> {
+        self.get_epoch_fee(epoch).calculate_fee(pre_fee_amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spl_token::{Account, AccountState, AccountType, Mint};
+
+    fn pack_mint() -> Vec<u8> {
+        let mut data = vec![1, 0, 0, 0];
+        data.extend([1; 32]);
+        data.extend(1_000_000u64.to_le_bytes());
+        data.extend([6, 1]);
+        data.extend([0; 36]);
+        data
+    }
+
+    fn pack_account(state: AccountState) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend([1; 32]);
+        data.extend([2; 32]);
+        data.extend(42u64.to_le_bytes());
+        data.extend([0; 36]);
+        data.push(state as u8);
+        data.extend([1, 0, 0, 0]);
+        data.extend(7u64.to_le_bytes());
+        data.extend(0u64.to_le_bytes());
+        data.extend([1, 0, 0, 0]);
+        data.extend([3; 32]);
+        data
+    }
+
+    #[test]
+    fn test_unpack_token_state() {
+        let mint = Mint::unpack(&pack_mint()).unwrap();
+        assert_eq!(
+            mint,
+            Mint {
+                mint_authority: Some(Pubkey::from([1; 32])),
+                supply: 1_000_000,
+                decimals: 6,
+                is_initialized: true,
+                freeze_authority: None,
+            }
+        );
+
+        let account = Account::unpack(&pack_account(AccountState::Frozen)).unwrap();
+        assert_eq!(
+            account,
+            Account {
+                mint: Pubkey::from([1; 32]),
+                owner: Pubkey::from([2; 32]),
+                amount: 42,
+                delegate: None,
+                state: AccountState::Frozen,
+                is_native: Some(7),
+                delegated_amount: 0,
+                close_authority: Some(Pubkey::from([3; 32])),
+            }
+        );
+        assert!(account.is_frozen());
+
+        assert_eq!(
+            Account::unpack(&pack_account(AccountState::Uninitialized)),
+            Err(TokenError::UninitializedAccount)
+        );
+        assert_eq!(Mint::unpack(&[0; 81]), Err(TokenError::InvalidAccountData));
+        // A mint must not be accepted as a token account, and vice versa.
+        assert_eq!(Account::unpack(&pack_mint()), Err(TokenError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_unpack_token_2022_mint_with_transfer_fee() {
+        let mut data = pack_mint();
+        data.resize(BASE_ACCOUNT_LENGTH, 0);
+        data.push(AccountType::Mint as u8);
+        data.extend([1, 0, 108, 0]);
+        data.extend([1; 32]);
+        data.extend([0; 32]);
+        data.extend(5u64.to_le_bytes());
+        data.extend(0u64.to_le_bytes());
+        data.extend(1000u64.to_le_bytes());
+        data.extend(50u16.to_le_bytes());
+        data.extend(100u64.to_le_bytes());
+        data.extend(2000u64.to_le_bytes());
+        data.extend(100u16.to_le_bytes());
+
+        let state = StateWithExtensions::<Mint>::unpack(&data).unwrap();
+        assert_eq!(state.base, Mint::unpack(&pack_mint()).unwrap());
+        assert_eq!(state.get_extension_types(), vec![ExtensionType::TransferFeeConfig]);
+
+        let config = state.get_transfer_fee_config().unwrap().unwrap();
+        assert_eq!(config.transfer_fee_config_authority, Some(Pubkey::from([1; 32])));
+        assert_eq!(config.withdraw_withheld_authority, None);
+        assert_eq!(config.withheld_amount, 5);
+        assert_eq!(config.calculate_epoch_fee(10, 1_001), Some(6));
+        assert_eq!(config.calculate_epoch_fee(10, 1_000_000), Some(1000));
+        assert_eq!(config.calculate_epoch_fee(100, 1_000), Some(10));
+    }
+
+    #[test]
+    fn test_unpack_token_2022_account_with_immutable_owner() {
+        let mut data = pack_account(AccountState::Initialized);
+        data.push(AccountType::Account as u8);
+        data.extend([7, 0, 0, 0]);
+
+        let state = StateWithExtensions::<Account>::unpack(&data).unwrap();
+        assert_eq!(state.base.amount, 42);
+        assert_eq!(state.get_extension_types(), vec![ExtensionType::ImmutableOwner]);
+        assert_eq!(state.get_extension_bytes(ExtensionType::ImmutableOwner), Some(&[][..]));
+        assert_eq!(state.get_transfer_fee_config(), Ok(None));
+
+        data[BASE_ACCOUNT_LENGTH] = AccountType::Mint as u8;
+        assert_eq!(
+            StateWithExtensions::<Account>::unpack(&data),
+            Err(TokenError::InvalidAccountData)
+        );
+    }
+}
@@ -0,0 +1,537 @@
+use crate::{
+    spl_token::{check_program_account, TokenError, MAX_SIGNERS},
+    system_instruction::SYSVAR_RENT_ID,
+    types::{AccountMeta, Instruction, Pubkey},
+};
+
+/// Instructions supported by the token programs.
+///
+/// Token-2022 shares the layout of these instructions with the original
+/// token program, so the same builders are used for both program ids.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenInstruction {
+    /// Initializes a new mint and optionally deposits all the newly minted
+    /// tokens in an account.
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[writable]` The mint to initialize.
+    ///   1. `[]` Rent sysvar
+    InitializeMint {
+        /// Number of base 10 digits to the right of the decimal place.
+        decimals: u8,
+        /// The authority/multisignature to mint tokens.
+        mint_authority: Pubkey,
+        /// The freeze authority/multisignature of the mint.
+        freeze_authority: Option<Pubkey>,
+    },
+    /// Initializes a new account to hold tokens.
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[writable]`  The account to initialize.
+    ///   1. `[]` The mint this account will be associated with.
+    ///   2. `[]` The new account's owner/multisignature.
+    ///   3. `[]` Rent sysvar
+    InitializeAccount,
+    /// Transfers tokens from one account to another either directly or via a
+    /// delegate.
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[writable]` The source account.
+    ///   1. `[writable]` The destination account.
+    ///   2. `[signer]` The source account's owner/delegate, or its multisignature account.
+    ///   3. ..3+M `[signer]` M signer accounts when the owner is a multisignature.
+    Transfer {
+        /// The amount of tokens to transfer.
+        amount: u64,
+    },
+    /// Approves a delegate. A delegate is given the authority over tokens on
+    /// behalf of the source account's owner.
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[writable]` The source account.
+    ///   1. `[]` The delegate.
+    ///   2. `[signer]` The source account owner, or its multisignature account.
+    ///   3. ..3+M `[signer]` M signer accounts when the owner is a multisignature.
+    Approve {
+        /// The amount of tokens the delegate is approved for.
+        amount: u64,
+    },
+    /// Mints new tokens to an account.
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[writable]` The mint.
+    ///   1. `[writable]` The account to mint tokens to.
+    ///   2. `[signer]` The mint's minting authority, or its multisignature account.
+    ///   3. ..3+M `[signer]` M signer accounts when the authority is a multisignature.
+    MintTo {
+        /// The amount of new tokens to mint.
+        amount: u64,
+    },
+    /// Burns tokens by removing them from an account.
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[writable]` The account to burn from.
+    ///   1. `[writable]` The token mint.
+    ///   2. `[signer]` The account's owner/delegate, or its multisignature account.
+    ///   3. ..3+M `[signer]` M signer accounts when the authority is a multisignature.
+    Burn {
+        /// The amount of tokens to burn.
+        amount: u64,
+    },
+    /// Close an account by transferring all its SOL to the destination account.
+    /// Non-native accounts may only be closed if its token amount is zero.
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[writable]` The account to close.
+    ///   1. `[writable]` The destination account.
+    ///   2. `[signer]` The account's owner, or its multisignature account.
+    ///   3. ..3+M `[signer]` M signer accounts when the owner is a multisignature.
+    CloseAccount,
+    /// Transfers tokens from one account to another either directly or via a
+    /// delegate, asserting the token mint and decimals.
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[writable]` The source account.
+    ///   1. `[]` The token mint.
+    ///   2. `[writable]` The destination account.
+    ///   3. `[signer]` The source account's owner/delegate, or its multisignature account.
+    ///   4. ..4+M `[signer]` M signer accounts when the authority is a multisignature.
+    TransferChecked {
+        /// The amount of tokens to transfer.
+        amount: u64,
+        /// Expected number of base 10 digits to the right of the decimal place.
+        decimals: u8,
+    },
+}
+
+impl TokenInstruction {
+    /// Unpacks a byte buffer into a [`TokenInstruction`].
+    pub fn unpack(input: &[u8]) -> Result<Self, TokenError> {
+        let (&tag, rest) = input.split_first().ok_or(TokenError::InvalidInstruction)?;
+        Ok(match tag {
+            0 => {
+                let (&decimals, rest) = rest.split_first().ok_or(TokenError::InvalidInstruction)?;
+                let (mint_authority, rest) = unpack_pubkey(rest)?;
+                let freeze_authority = match rest.split_first() {
+                    Some((0, _)) => None,
+                    Some((1, rest)) => Some(unpack_pubkey(rest)?.0),
+                    _ => return Err(TokenError::InvalidInstruction),
+                };
+                Self::InitializeMint {
+                    decimals,
+                    mint_authority,
+                    freeze_authority,
+                }
+            }
+            1 => Self::InitializeAccount,
+            3 => Self::Transfer {
+                amount: unpack_amount(rest)?.0,
+            },
+            4 => Self::Approve {
+                amount: unpack_amount(rest)?.0,
+            },
+            7 => Self::MintTo {
+                amount: unpack_amount(rest)?.0,
+            },
+            8 => Self::Burn {
+                amount: unpack_amount(rest)?.0,
+            },
+            9 => Self::CloseAccount,
+            12 => {
+                let (amount, rest) = unpack_amount(rest)?;
+                let &decimals = rest.first().ok_or(TokenError::InvalidInstruction)?;
+                Self::TransferChecked { amount, decimals }
+            }
+            _ => return Err(TokenError::InvalidInstruction),
+        })
+    }
+
+    /// Packs a [`TokenInstruction`] into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match self {
+            Self::InitializeMint {
+                decimals,
+                mint_authority,
+                freeze_authority,
+            } => {
+                buf.push(0);
+                buf.push(*decimals);
+                buf.extend_from_slice(mint_authority.as_ref());
+                match freeze_authority {
+                    Some(freeze_authority) => {
+                        buf.push(1);
+                        buf.extend_from_slice(freeze_authority.as_ref());
+                    }
+                    None => buf.push(0),
+                }
+            }
+            Self::InitializeAccount => buf.push(1),
+            Self::Transfer { amount } => {
+                buf.push(3);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::Approve { amount } => {
+                buf.push(4);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::MintTo { amount } => {
+                buf.push(7);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::Burn { amount } => {
+                buf.push(8);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::CloseAccount => buf.push(9),
+            Self::TransferChecked { amount, decimals } => {
+                buf.push(12);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.push(*decimals);
+            }
+        }
+        buf
+    }
+}
+
+/// Creates an `InitializeMint` instruction.
+pub fn initialize_mint(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    freeze_authority_pubkey: Option<&Pubkey>,
+    decimals: u8,
+) -> Result<Instruction, TokenError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializeMint {
+        decimals,
+        mint_authority: *mint_authority_pubkey,
+        freeze_authority: freeze_authority_pubkey.cloned(),
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*mint_pubkey, false),
+        AccountMeta::new_readonly(SYSVAR_RENT_ID, false),
+    ];
+
+    Ok(Instruction::new_with_bytes(*token_program_id, &data, accounts))
+}
+
+/// Creates an `InitializeAccount` instruction.
+pub fn initialize_account(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+) -> Result<Instruction, TokenError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializeAccount.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*account_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, false),
+        AccountMeta::new_readonly(SYSVAR_RENT_ID, false),
+    ];
+
+    Ok(Instruction::new_with_bytes(*token_program_id, &data, accounts))
+}
+
+/// Creates a `Transfer` instruction.
+pub fn transfer(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, TokenError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::Transfer { amount }.pack();
+
+    let accounts = with_signers(
+        vec![
+            AccountMeta::new(*source_pubkey, false),
+            AccountMeta::new(*destination_pubkey, false),
+        ],
+        authority_pubkey,
+        signer_pubkeys,
+    )?;
+
+    Ok(Instruction::new_with_bytes(*token_program_id, &data, accounts))
+}
+
+/// Creates an `Approve` instruction.
+pub fn approve(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    delegate_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, TokenError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::Approve { amount }.pack();
+
+    let accounts = with_signers(
+        vec![
+            AccountMeta::new(*source_pubkey, false),
+            AccountMeta::new_readonly(*delegate_pubkey, false),
+        ],
+        owner_pubkey,
+        signer_pubkeys,
+    )?;
+
+    Ok(Instruction::new_with_bytes(*token_program_id, &data, accounts))
+}
+
+/// Creates a `MintTo` instruction.
+pub fn mint_to(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    account_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, TokenError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::MintTo { amount }.pack();
+
+    let accounts = with_signers(
+        vec![
+            AccountMeta::new(*mint_pubkey, false),
+            AccountMeta::new(*account_pubkey, false),
+        ],
+        owner_pubkey,
+        signer_pubkeys,
+    )?;
+
+    Ok(Instruction::new_with_bytes(*token_program_id, &data, accounts))
+}
+
+/// Creates a `Burn` instruction.
+pub fn burn(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, TokenError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::Burn { amount }.pack();
+
+    let accounts = with_signers(
+        vec![
+            AccountMeta::new(*account_pubkey, false),
+            AccountMeta::new(*mint_pubkey, false),
+        ],
+        authority_pubkey,
+        signer_pubkeys,
+    )?;
+
+    Ok(Instruction::new_with_bytes(*token_program_id, &data, accounts))
+}
+
+/// Creates a `CloseAccount` instruction.
+pub fn close_account(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, TokenError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::CloseAccount.pack();
+
+    let accounts = with_signers(
+        vec![
+            AccountMeta::new(*account_pubkey, false),
+            AccountMeta::new(*destination_pubkey, false),
+        ],
+        owner_pubkey,
+        signer_pubkeys,
+    )?;
+
+    Ok(Instruction::new_with_bytes(*token_program_id, &data, accounts))
+}
+
+/// Creates a `TransferChecked` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_checked(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, TokenError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::TransferChecked { amount, decimals }.pack();
+
+    let accounts = with_signers(
+        vec![
+            AccountMeta::new(*source_pubkey, false),
+            AccountMeta::new_readonly(*mint_pubkey, false),
+            AccountMeta::new(*destination_pubkey, false),
+        ],
+        authority_pubkey,
+        signer_pubkeys,
+    )?;
+
+    Ok(Instruction::new_with_bytes(*token_program_id, &data, accounts))
+}
+
+fn unpack_amount(input: &[u8]) -> Result<(u64, &[u8]), TokenError> {
+    let (amount, rest) = input.split_at_checked(8).ok_or(TokenError::InvalidInstruction)?;
+    Ok((u64::from_le_bytes(amount.try_into().unwrap()), rest))
+}
+
+fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), TokenError> {
+    let (pubkey, rest) = input.split_at_checked(32).ok_or(TokenError::InvalidInstruction)?;
+    Ok((Pubkey::try_from(pubkey).unwrap(), rest))
+}
+
+/// Appends the authority and, for multisig authorities, its signers to `accounts`.
+/// The authority itself only signs when no multisig signers are given.
+fn with_signers(
+    mut accounts: Vec<AccountMeta>,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Vec<AccountMeta>, TokenError> {
+    if signer_pubkeys.len() > MAX_SIGNERS {
+        return Err(TokenError::TooManySigners(signer_pubkeys.len()));
+    }
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+    Ok(accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spl_token::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+    #[test]
+    fn test_transfer_checked() {
+        let source = Pubkey::from([1; 32]);
+        let mint = Pubkey::from([2; 32]);
+        let destination = Pubkey::from([3; 32]);
+        let instruction = transfer_checked(
+            &TOKEN_PROGRAM_ID,
+            &source,
+            &mint,
+            &destination,
+            &source,
+            &[],
+            1_000_000,
+            6,
+        )
+        .unwrap();
+
+        assert_eq!(instruction.program_id, TOKEN_PROGRAM_ID);
+        assert_eq!(instruction.data, vec![12, 64, 66, 15, 0, 0, 0, 0, 0, 6]);
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(source, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unpack() {
+        let source = Pubkey::from([1; 32]);
+        let destination = Pubkey::from([2; 32]);
+        let instruction = transfer(&TOKEN_PROGRAM_ID, &source, &destination, &source, &[], 42).unwrap();
+        assert_eq!(instruction.data, vec![3, 42, 0, 0, 0, 0, 0, 0, 0]);
+
+        for token_instruction in [
+            TokenInstruction::Transfer { amount: 42 },
+            TokenInstruction::TransferChecked {
+                amount: 1_000_000,
+                decimals: 6,
+            },
+            TokenInstruction::InitializeMint {
+                decimals: 9,
+                mint_authority: source,
+                freeze_authority: Some(destination),
+            },
+            TokenInstruction::CloseAccount,
+        ] {
+            assert_eq!(
+                TokenInstruction::unpack(&token_instruction.pack()),
+                Ok(token_instruction)
+            );
+        }
+
+        assert_eq!(TokenInstruction::unpack(&[]), Err(TokenError::InvalidInstruction));
+        assert_eq!(TokenInstruction::unpack(&[3, 1]), Err(TokenError::InvalidInstruction));
+        assert_eq!(TokenInstruction::unpack(&[255]), Err(TokenError::InvalidInstruction));
+    }
+
+    #[test]
+    fn test_mint_to_multisig() {
+        let mint = Pubkey::from([1; 32]);
+        let account = Pubkey::from([2; 32]);
+        let multisig = Pubkey::from([3; 32]);
+        let instruction = mint_to(
+            &TOKEN_2022_PROGRAM_ID,
+            &mint,
+            &account,
+            &multisig,
+            &[&mint, &account],
+            5,
+        )
+        .unwrap();
+
+        assert_eq!(instruction.data, vec![7, 5, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(mint, false),
+                AccountMeta::new(account, false),
+                AccountMeta::new_readonly(multisig, false),
+                AccountMeta::new_readonly(mint, true),
+                AccountMeta::new_readonly(account, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_initialize_mint() {
+        let mint = Pubkey::from([1; 32]);
+        let mint_authority = Pubkey::from([2; 32]);
+        let freeze_authority = Pubkey::from([3; 32]);
+
+        let instruction = initialize_mint(&TOKEN_PROGRAM_ID, &mint, &mint_authority, None, 9).unwrap();
+        let mut expected = vec![0, 9];
+        expected.extend([2; 32]);
+        expected.push(0);
+        assert_eq!(instruction.data, expected);
+
+        let instruction =
+            initialize_mint(&TOKEN_PROGRAM_ID, &mint, &mint_authority, Some(&freeze_authority), 9).unwrap();
+        expected.pop();
+        expected.push(1);
+        expected.extend([3; 32]);
+        assert_eq!(instruction.data, expected);
+    }
+
+    #[test]
+    fn test_incorrect_program_id() {
+        let key = Pubkey::from([1; 32]);
+        assert_eq!(
+            close_account(&key, &key, &key, &key, &[]),
+            Err(TokenError::IncorrectProgramId(key))
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{
+    spl_token::{StateWithExtensions, TokenError},
+    types::{Pubkey, PUBKEY_BYTES},
+};
+
+/// Base state of a token program account, shared by Token and Token-2022.
+pub trait BaseState: Sized {
+    /// Length of the packed base state, excluding any Token-2022 extensions.
+    const LEN: usize;
+
+    /// Token-2022 account type stored right after the base state when extensions are present.
+    const ACCOUNT_TYPE: AccountType;
+
+    /// Unpacks the base state from exactly [`Self::LEN`] bytes.
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, TokenError>;
+
+    fn is_initialized(&self) -> bool;
+}
+
+/// Type of account stored by Token-2022 after the base state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AccountType {
+    Uninitialized,
+    Mint,
+    Account,
+}
+
+/// Mint data.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Mint {
+    /// Optional authority used to mint new tokens. The mint authority may only
+    /// be provided during mint creation. If no mint authority is present
+    /// then the mint has a fixed supply and no further tokens may be
+    /// minted.
+    pub mint_authority: Option<Pubkey>,
+    /// Total supply of tokens.
+    pub supply: u64,
+    /// Number of base 10 digits to the right of the decimal place.
+    pub decimals: u8,
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// Optional authority to freeze token accounts.
+    pub freeze_authority: Option<Pubkey>,
+}
+
+impl Mint {
+    /// Unpacks a mint owned by either token program, ignoring any Token-2022 extensions.
+    pub fn unpack(data: &[u8]) -> Result<Self, TokenError> {
+        StateWithExtensions::<Self>::unpack(data).map(|state| state.base)
+    }
+}
+
+impl BaseState for Mint {
+    const LEN: usize = 82;
+    const ACCOUNT_TYPE: AccountType = AccountType::Mint;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, TokenError> {
+        let mut reader = Reader::new(src);
+        let mint_authority = reader.read_option_pubkey()?;
+        let supply = reader.read_u64()?;
+        let decimals = reader.read_u8()?;
+        let is_initialized = reader.read_bool()?;
+        let freeze_authority = reader.read_option_pubkey()?;
+        Ok(Self {
+            mint_authority,
+            supply,
+            decimals,
+            is_initialized,
+            freeze_authority,
+        })
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+/// Account state.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AccountState {
+    /// Account is not yet initialized
+    #[default]
+    Uninitialized,
+    /// Account is initialized; the account owner and/or delegate may perform
+    /// permitted operations on this account
+    Initialized,
+    /// Account has been frozen by the mint freeze authority. Neither the
+    /// account owner nor the delegate are able to perform operations on
+    /// this account.
+    Frozen,
+}
+
+/// Token account data.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Account {
+    /// The mint associated with this account
+    pub mint: Pubkey,
+    /// The owner of this account.
+    pub owner: Pubkey,
+    /// The amount of tokens this account holds.
+    pub amount: u64,
+    /// If `delegate` is `Some` then `delegated_amount` represents
+    /// the amount authorized by the delegate
+    pub delegate: Option<Pubkey>,
+    /// The account's state
+    pub state: AccountState,
+    /// If `is_native` is `Some`, this is a native token, and the value logs the
+    /// rent-exempt reserve. An Account is required to be rent-exempt, so
+    /// the value is used by the Processor to ensure that wrapped SOL
+    /// accounts do not drop below this threshold.
+    pub is_native: Option<u64>,
+    /// The amount delegated
+    pub delegated_amount: u64,
+    /// Optional authority to close the account.
+    pub close_authority: Option<Pubkey>,
+}
+
+impl Account {
+    /// Unpacks a token account owned by either token program, ignoring any Token-2022 extensions.
+    pub fn unpack(data: &[u8]) -> Result<Self, TokenError> {
+        StateWithExtensions::<Self>::unpack(data).map(|state| state.base)
+    }
+
+    /// Checks if account is frozen
+    pub fn is_frozen(&self) -> bool {
+        self.state == AccountState::Frozen
+    }
+
+    /// Checks if account is native
+    pub fn is_native(&self) -> bool {
+        self.is_native.is_some()
+    }
+}
+
+impl BaseState for Account {
+    const LEN: usize = 165;
+    const ACCOUNT_TYPE: AccountType = AccountType::Account;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, TokenError> {
+        let mut reader = Reader::new(src);
+        let mint = reader.read_pubkey()?;
+        let owner = reader.read_pubkey()?;
+        let amount = reader.read_u64()?;
+        let delegate = reader.read_option_pubkey()?;
+        let state = match reader.read_u8()? {
+            0 => AccountState::Uninitialized,
+            1 => AccountState::Initialized,
+            2 => AccountState::Frozen,
+            _ => return Err(TokenError::InvalidAccountData),
+        };
+        let is_native = reader.read_option_u64()?;
+        let delegated_amount = reader.read_u64()?;
+        let close_authority = reader.read_option_pubkey()?;
+        Ok(Self {
+            mint,
+            owner,
+            amount,
+            delegate,
+            state,
+            is_native,
+            delegated_amount,
+            close_authority,
+        })
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.state != AccountState::Uninitialized
+    }
+}
+
+/// Length of a multisig account, which must never be mistaken for an account with extensions.
+pub(crate) const MULTISIG_LEN: usize = 355;
+
+/// Sequential little-endian reader over packed account data.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len()
+    }
+
+    pub(crate) fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], TokenError> {
+        if self.data.len() < len {
+            return Err(TokenError::InvalidAccountData);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, TokenError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, TokenError> {
+        Ok(u16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, TokenError> {
+        Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_bool(&mut self) -> Result<bool, TokenError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(TokenError::InvalidAccountData),
+        }
+    }
+
+    pub(crate) fn read_pubkey(&mut self) -> Result<Pubkey, TokenError> {
+        Ok(Pubkey::try_from(self.read_bytes(PUBKEY_BYTES)?).unwrap())
+    }
+
+    /// Reads a Token-2022 `OptionalNonZeroPubkey`, where the zero key means `None`.
+    pub(crate) fn read_optional_non_zero_pubkey(&mut self) -> Result<Option<Pubkey>, TokenError> {
+        let pubkey = self.read_pubkey()?;
+        Ok((pubkey != Pubkey::default()).then_some(pubkey))
+    }
+
+    /// Reads a `COption` tag, which is packed as a 4 byte little-endian integer.
+    fn read_option_tag(&mut self) -> Result<bool, TokenError> {
+        match self.read_bytes(4)? {
+            [0, 0, 0, 0] => Ok(false),
+            [1, 0, 0, 0] => Ok(true),
+            _ => Err(TokenError::InvalidAccountData),
+        }
+    }
+
+    pub(crate) fn read_option_pubkey(&mut self) -> Result<Option<Pubkey>, TokenError> {
+        let is_some = self.read_option_tag()?;
+        let pubkey = self.read_pubkey()?;
+        Ok(is_some.then_some(pubkey))
+    }
+
+    pub(crate) fn read_option_u64(&mut self) -> Result<Option<u64>, TokenError> {
+        let is_some = self.read_option_tag()?;
+        let value = self.read_u64()?;
+        Ok(is_some.then_some(value))
+    }
+}
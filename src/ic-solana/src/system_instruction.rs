@@ -0,0 +1,529 @@
+//! Instructions and constructors for the system program.
+//!
+//! The system program is responsible for creating accounts, allocating account data,
+//! assigning accounts to owning programs, transferring lamports and managing durable
+//! transaction nonces.
+//!
+//! See https://docs.rs/solana-program/latest/solana_program/system_instruction/index.html
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{AccountMeta, Instruction, Pubkey};
+
+/// The system program id.
+pub const SYSTEM_PROGRAM_ID: Pubkey = Pubkey::from_str_const("11111111111111111111111111111111");
+
+/// The `RecentBlockhashes` sysvar id, required by the nonce instructions.
+pub const SYSVAR_RECENT_BLOCKHASHES_ID: Pubkey = Pubkey::from_str_const("SysvarRecentB1ockHashes11111111111111111111");
+
+/// The `Rent` sysvar id.
+pub const SYSVAR_RENT_ID: Pubkey = Pubkey::from_str_const("SysvarRent111111111111111111111111111111111");
+
+/// Size in bytes of a serialized nonce account.
+pub const NONCE_STATE_SIZE: u64 = 80;
+
+/// An instruction to the system program.
+///
+/// The variant order and layout must match the on-chain program, since the
+/// instruction data is the bincode encoding of this enum.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum SystemInstruction {
+    /// Create a new account
+    ///
+    /// # Account references
+    ///   0. `[WRITE, SIGNER]` Funding account
+    ///   1. `[WRITE, SIGNER]` New account
+    CreateAccount {
+        /// Number of lamports to transfer to the new account
+        lamports: u64,
+        /// Number of bytes of memory to allocate
+        space: u64,
+        /// Address of program that will own the new account
+        owner: Pubkey,
+    },
+
+    /// Assign account to a program
+    ///
+    /// # Account references
+    ///   0. `[WRITE, SIGNER]` Assigned account public key
+    Assign {
+        /// Owner program account
+        owner: Pubkey,
+    },
+
+    /// Transfer lamports
+    ///
+    /// # Account references
+    ///   0. `[WRITE, SIGNER]` Funding account
+    ///   1. `[WRITE]` Recipient account
+    Transfer { lamports: u64 },
+
+    /// Create a new account at an address derived from a base pubkey and a seed
+    ///
+    /// # Account references
+    ///   0. `[WRITE, SIGNER]` Funding account
+    ///   1. `[WRITE]` Created account
+    ///   2. `[SIGNER]` (optional) Base account; the account matching the base Pubkey below must be
+    ///      provided as a signer, but may be the same as the funding account and provided as
+    ///      account 0
+    CreateAccountWithSeed {
+        /// Base public key
+        base: Pubkey,
+        /// String of ASCII chars, no longer than `pubkey::MAX_SEED_LEN`
+        seed: String,
+        /// Number of lamports to transfer to the new account
+        lamports: u64,
+        /// Number of bytes of memory to allocate
+        space: u64,
+        /// Owner program account address
+        owner: Pubkey,
+    },
+
+    /// Consumes a stored nonce, replacing it with a successor
+    ///
+    /// # Account references
+    ///   0. `[WRITE]` Nonce account
+    ///   1. `[]` RecentBlockhashes sysvar
+    ///   2. `[SIGNER]` Nonce authority
+    AdvanceNonceAccount,
+
+    /// Withdraw funds from a nonce account
+    ///
+    /// # Account references
+    ///   0. `[WRITE]` Nonce account
+    ///   1. `[WRITE]` Recipient account
+    ///   2. `[]` RecentBlockhashes sysvar
+    ///   3. `[]` Rent sysvar
+    ///   4. `[SIGNER]` Nonce authority
+    ///
+    /// The `u64` parameter is the lamports to withdraw, which must leave the
+    /// account balance above the rent exempt reserve or at zero.
+    WithdrawNonceAccount(u64),
+
+    /// Drive state of Uninitialized nonce account to Initialized, setting the nonce value
+    ///
+    /// # Account references
+    ///   0. `[WRITE]` Nonce account
+    ///   1. `[]` RecentBlockhashes sysvar
+    ///   2. `[]` Rent sysvar
+    ///
+    /// The `Pubkey` parameter specifies the entity authorized to execute nonce
+    /// instruction on the account
+    InitializeNonceAccount(Pubkey),
+
+    /// Change the entity authorized to execute nonce instructions on the account
+    ///
+    /// # Account references
+    ///   0. `[WRITE]` Nonce account
+    ///   1. `[SIGNER]` Nonce authority
+    ///
+    /// The `Pubkey` parameter identifies the entity to authorize
+    AuthorizeNonceAccount(Pubkey),
+
+    /// Allocate space in a (possibly new) account without funding
+    ///
+    /// # Account references
+    ///   0. `[WRITE, SIGNER]` New account
+    Allocate {
+        /// Number of bytes of memory to allocate
+        space: u64,
+    },
+
+    /// Allocate space for and assign an account at an address
+    /// derived from a base public key and a seed
+    ///
+    /// # Account references
+    ///   0. `[WRITE]` Allocated account
+    ///   1. `[SIGNER]` Base account
+    AllocateWithSeed {
+        /// Base public key
+        base: Pubkey,
+        /// String of ASCII chars, no longer than `pubkey::MAX_SEED_LEN`
+        seed: String,
+        /// Number of bytes of memory to allocate
+        space: u64,
+        /// Owner program account
+        owner: Pubkey,
+    },
+
+    /// Assign account to a program based on a seed
+    ///
+    /// # Account references
+    ///   0. `[WRITE]` Assigned account
+    ///   1. `[SIGNER]` Base account
+    AssignWithSeed {
+        /// Base public key
+        base: Pubkey,
+        /// String of ASCII chars, no longer than `pubkey::MAX_SEED_LEN`
+        seed: String,
+        /// Owner program account
+        owner: Pubkey,
+    },
+
+    /// Transfer lamports from a derived address
+    ///
+    /// # Account references
+    ///   0. `[WRITE]` Funding account
+    ///   1. `[SIGNER]` Base for funding account
+    ///   2. `[WRITE]` Recipient account
+    TransferWithSeed {
+        /// Amount to transfer
+        lamports: u64,
+        /// Seed to use to derive the funding account address
+        from_seed: String,
+        /// Owner to use to derive the funding account address
+        from_owner: Pubkey,
+    },
+
+    /// One-time idempotent upgrade of legacy nonce versions in order to bump
+    /// them out of chain blockhash domain.
+    ///
+    /// # Account references
+    ///   0. `[WRITE]` Nonce account
+    UpgradeNonceAccount,
+}
+
+/// Create an account.
+pub fn create_account(
+    from_pubkey: &Pubkey,
+    to_pubkey: &Pubkey,
+    lamports: u64,
+    space: u64,
+    owner: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![AccountMeta::new(*from_pubkey, true), AccountMeta::new(*to_pubkey, true)];
+    Instruction::new_with_bincode(
+        SYSTEM_PROGRAM_ID,
+        &SystemInstruction::CreateAccount {
+            lamports,
+            space,
+            owner: *owner,
+        },
+        account_metas,
+    )
+}
+
+/// Create an account at an address derived from a base pubkey and a seed.
+///
+/// `to_pubkey` is expected to be `Pubkey::create_with_seed(base, seed, owner)`.
+pub fn create_account_with_seed(
+    from_pubkey: &Pubkey,
+    to_pubkey: &Pubkey,
+    base: &Pubkey,
+    seed: &str,
+    lamports: u64,
+    space: u64,
+    owner: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*from_pubkey, true),
+        AccountMeta::new(*to_pubkey, false),
+        AccountMeta::new_readonly(*base, true),
+    ];
+    Instruction::new_with_bincode(
+        SYSTEM_PROGRAM_ID,
+        &SystemInstruction::CreateAccountWithSeed {
+            base: *base,
+            seed: seed.to_string(),
+            lamports,
+            space,
+            owner: *owner,
+        },
+        account_metas,
+    )
+}
+
+/// Assign ownership of an account to a program.
+pub fn assign(pubkey: &Pubkey, owner: &Pubkey) -> Instruction {
+    let account_metas = vec![AccountMeta::new(*pubkey, true)];
+    Instruction::new_with_bincode(
+        SYSTEM_PROGRAM_ID,
+        &SystemInstruction::Assign { owner: *owner },
+        account_metas,
+    )
+}
+
+/// Transfer lamports from an account owned by the system program.
+pub fn transfer(from_pubkey: &Pubkey, to_pubkey: &Pubkey, lamports: u64) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*from_pubkey, true),
+        AccountMeta::new(*to_pubkey, false),
+    ];
+    Instruction::new_with_bincode(
+        SYSTEM_PROGRAM_ID,
+        &SystemInstruction::Transfer { lamports },
+        account_metas,
+    )
+}
+
+/// Allocate space for an account.
+pub fn allocate(pubkey: &Pubkey, space: u64) -> Instruction {
+    let account_metas = vec![AccountMeta::new(*pubkey, true)];
+    Instruction::new_with_bincode(SYSTEM_PROGRAM_ID, &SystemInstruction::Allocate { space }, account_metas)
+}
+
+/// Initialize a nonce account that was previously created and funded.
+///
+/// Most callers want [`create_nonce_account`], which also creates the account.
+pub fn initialize_nonce_account(nonce_pubkey: &Pubkey, authority: &Pubkey) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*nonce_pubkey, false),
+        AccountMeta::new_readonly(SYSVAR_RECENT_BLOCKHASHES_ID, false),
+        AccountMeta::new_readonly(SYSVAR_RENT_ID, false),
+    ];
+    Instruction::new_with_bincode(
+        SYSTEM_PROGRAM_ID,
+        &SystemInstruction::InitializeNonceAccount(*authority),
+        account_metas,
+    )
+}
+
+/// Create and initialize a durable transaction nonce account.
+///
+/// `lamports` must cover the rent exemption of [`NONCE_STATE_SIZE`] bytes.
+pub fn create_nonce_account(
+    from_pubkey: &Pubkey,
+    nonce_pubkey: &Pubkey,
+    authority: &Pubkey,
+    lamports: u64,
+) -> Vec<Instruction> {
+    vec![
+        create_account(
+            from_pubkey,
+            nonce_pubkey,
+            lamports,
+            NONCE_STATE_SIZE,
+            &SYSTEM_PROGRAM_ID,
+        ),
+        initialize_nonce_account(nonce_pubkey, authority),
+    ]
+}
+
+/// Create and initialize a durable transaction nonce account at an address
+/// derived from a base pubkey and a seed.
+///
+/// Unlike [`create_nonce_account`], the nonce account does not need to sign,
+/// only `from_pubkey` and `base` do.
+pub fn create_nonce_account_with_seed(
+    from_pubkey: &Pubkey,
+    nonce_pubkey: &Pubkey,
+    base: &Pubkey,
+    seed: &str,
+    authority: &Pubkey,
+    lamports: u64,
+) -> Vec<Instruction> {
+    vec![
+        create_account_with_seed(
+            from_pubkey,
+            nonce_pubkey,
+            base,
+            seed,
+            lamports,
+            NONCE_STATE_SIZE,
+            &SYSTEM_PROGRAM_ID,
+        ),
+        initialize_nonce_account(nonce_pubkey, authority),
+    ]
+}
+
+/// Advance the value of a durable transaction nonce.
+///
+/// This must be the first instruction of a transaction that uses the nonce
+/// value as its recent blockhash.
+pub fn advance_nonce_account(nonce_pubkey: &Pubkey, authorized_pubkey: &Pubkey) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*nonce_pubkey, false),
+        AccountMeta::new_readonly(SYSVAR_RECENT_BLOCKHASHES_ID, false),
+        AccountMeta::new_readonly(*authorized_pubkey, true),
+    ];
+    Instruction::new_with_bincode(
+        SYSTEM_PROGRAM_ID,
+        &SystemInstruction::AdvanceNonceAccount,
+        account_metas,
+    )
+}
+
+/// Withdraw lamports from a durable transaction nonce account.
+pub fn withdraw_nonce_account(
+    nonce_pubkey: &Pubkey,
+    authorized_pubkey: &Pubkey,
+    to_pubkey: &Pubkey,
+    lamports: u64,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*nonce_pubkey, false),
+        AccountMeta::new(*to_pubkey, false),
+        AccountMeta::new_readonly(SYSVAR_RECENT_BLOCKHASHES_ID, false),
+        AccountMeta::new_readonly(SYSVAR_RENT_ID, false),
+        AccountMeta::new_readonly(*authorized_pubkey, true),
+    ];
+    Instruction::new_with_bincode(
+        SYSTEM_PROGRAM_ID,
+        &SystemInstruction::WithdrawNonceAccount(lamports),
+        account_metas,
+    )
+}
+
+/// Change the entity authorized to execute nonce instructions on the account.
+pub fn authorize_nonce_account(
+    nonce_pubkey: &Pubkey,
+    authorized_pubkey: &Pubkey,
+    new_authority: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*nonce_pubkey, false),
+        AccountMeta::new_readonly(*authorized_pubkey, true),
+    ];
+    Instruction::new_with_bincode(
+        SYSTEM_PROGRAM_ID,
+        &SystemInstruction::AuthorizeNonceAccount(*new_authority),
+        account_metas,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_keys(instruction: &Instruction) -> Vec<(Pubkey, bool, bool)> {
+        instruction
+            .accounts
+            .iter()
+            .map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable))
+            .collect()
+    }
+
+    #[test]
+    fn test_program_ids() {
+        assert_eq!(SYSTEM_PROGRAM_ID, Pubkey::default());
+        assert_eq!(
+            SYSVAR_RENT_ID.to_string(),
+            "SysvarRent111111111111111111111111111111111"
+        );
+    }
+
+    #[test]
+    fn test_transfer() {
+        let from = Pubkey::from([1; 32]);
+        let to = Pubkey::from([2; 32]);
+        let instruction = transfer(&from, &to, 42);
+
+        assert_eq!(instruction.program_id, SYSTEM_PROGRAM_ID);
+        assert_eq!(instruction.data, vec![2, 0, 0, 0, 42, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(get_keys(&instruction), vec![(from, true, true), (to, false, true)]);
+    }
+
+    #[test]
+    fn test_create_account_with_seed() {
+        let from = Pubkey::from([1; 32]);
+        let to = Pubkey::from([2; 32]);
+        let base = Pubkey::from([3; 32]);
+        let instruction = create_account_with_seed(&from, &to, &base, "seed", 42, 165, &from);
+
+        let mut data = vec![3, 0, 0, 0];
+        data.extend([3; 32]);
+        data.extend([4, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend(b"seed");
+        data.extend([42, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend([165, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend([1; 32]);
+
+        assert_eq!(instruction.data, data);
+        assert_eq!(
+            get_keys(&instruction),
+            vec![(from, true, true), (to, false, true), (base, true, false)]
+        );
+    }
+
+    #[test]
+    fn test_create_nonce_account() {
+        let from = Pubkey::from([1; 32]);
+        let nonce = Pubkey::from([2; 32]);
+        let authority = Pubkey::from([3; 32]);
+        let instructions = create_nonce_account(&from, &nonce, &authority, 42);
+
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(
+            bincode::deserialize::<SystemInstruction>(&instructions[0].data).unwrap(),
+            SystemInstruction::CreateAccount {
+                lamports: 42,
+                space: NONCE_STATE_SIZE,
+                owner: SYSTEM_PROGRAM_ID,
+            }
+        );
+        assert_eq!(
+            get_keys(&instructions[1]),
+            vec![
+                (nonce, false, true),
+                (SYSVAR_RECENT_BLOCKHASHES_ID, false, false),
+                (SYSVAR_RENT_ID, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_create_nonce_account_with_seed() {
+        let base = Pubkey::from([1; 32]);
+        let nonce = Pubkey::create_with_seed(&base, "nonce", &SYSTEM_PROGRAM_ID).unwrap();
+        let instructions = create_nonce_account_with_seed(&base, &nonce, &base, "nonce", &base, 42);
+
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(
+            bincode::deserialize::<SystemInstruction>(&instructions[0].data).unwrap(),
+            SystemInstruction::CreateAccountWithSeed {
+                base,
+                seed: "nonce".to_string(),
+                lamports: 42,
+                space: NONCE_STATE_SIZE,
+                owner: SYSTEM_PROGRAM_ID,
+            }
+        );
+        assert_eq!(
+            get_keys(&instructions[0]),
+            vec![(base, true, true), (nonce, false, true), (base, true, false)]
+        );
+        assert_eq!(
+            bincode::deserialize::<SystemInstruction>(&instructions[1].data).unwrap(),
+            SystemInstruction::InitializeNonceAccount(base)
+        );
+    }
+
+    #[test]
+    fn test_nonce_instructions() {
+        let nonce = Pubkey::from([2; 32]);
+        let authority = Pubkey::from([3; 32]);
+        let to = Pubkey::from([1; 32]);
+
+        let instruction = advance_nonce_account(&nonce, &authority);
+        assert_eq!(instruction.data, vec![4, 0, 0, 0]);
+        assert_eq!(
+            get_keys(&instruction),
+            vec![
+                (nonce, false, true),
+                (SYSVAR_RECENT_BLOCKHASHES_ID, false, false),
+                (authority, true, false),
+            ]
+        );
+
+        let instruction = withdraw_nonce_account(&nonce, &authority, &to, 42);
+        assert_eq!(instruction.data, vec![5, 0, 0, 0, 42, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            get_keys(&instruction),
+            vec![
+                (nonce, false, true),
+                (to, false, true),
+                (SYSVAR_RECENT_BLOCKHASHES_ID, false, false),
+                (SYSVAR_RENT_ID, false, false),
+                (authority, true, false),
+            ]
+        );
+
+        let instruction = authorize_nonce_account(&nonce, &authority, &to);
+        assert_eq!(instruction.data[..4], [7, 0, 0, 0]);
+        assert_eq!(instruction.data[4..], [1; 32]);
+        assert_eq!(
+            get_keys(&instruction),
+            vec![(nonce, false, true), (authority, true, false)]
+        );
+    }
+}
@@ -2,7 +2,11 @@
 
 use std::collections::BTreeMap;
 
-use crate::types::{instruction::Instruction, message::MessageHeader, pubkey::Pubkey};
+use crate::types::{
+    instruction::Instruction,
+    message::{AddressLookupTableAccount, MessageAddressTableLookup, MessageHeader},
+    pubkey::Pubkey,
+};
 
 /// A helper struct to collect pubkeys compiled for a set of instructions
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
@@ -28,6 +32,13 @@ pub enum CompileError {
     UnknownInstructionKey(Pubkey),
 }
 
+/// Addresses loaded from an address lookup table, split by access mode.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LoadedAddresses {
+    pub writable: Vec<Pubkey>,
+    pub readonly: Vec<Pubkey>,
+}
+
 impl CompiledKeys {
     /// Compiles the pubkeys referenced by a list of instructions and organizes by
     /// signer/non-signer and writable/readonly.
@@ -102,4 +113,65 @@ impl CompiledKeys {
 
         Ok((header, static_account_keys))
     }
+
+    /// Moves the non-signer, non-invoked keys found in the given lookup table out of the static
+    /// key set and returns the lookup referencing them, if any.
+    pub(crate) fn try_extract_table_lookup(
+        &mut self,
+        lookup_table_account: &AddressLookupTableAccount,
+    ) -> Result<Option<(MessageAddressTableLookup, LoadedAddresses)>, CompileError> {
+        let (writable_indexes, drained_writable_keys) = self
+            .try_drain_keys_found_in_lookup_table(&lookup_table_account.addresses, |meta| {
+                !meta.is_signer && !meta.is_invoked && meta.is_writable
+            })?;
+        let (readonly_indexes, drained_readonly_keys) = self
+            .try_drain_keys_found_in_lookup_table(&lookup_table_account.addresses, |meta| {
+                !meta.is_signer && !meta.is_invoked && !meta.is_writable
+            })?;
+
+        // Don't extract the lookup if no keys were found
+        if writable_indexes.is_empty() && readonly_indexes.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some((
+            MessageAddressTableLookup {
+                account_key: lookup_table_account.key,
+                writable_indexes,
+                readonly_indexes,
+            },
+            LoadedAddresses {
+                writable: drained_writable_keys,
+                readonly: drained_readonly_keys,
+            },
+        )))
+    }
+
+    fn try_drain_keys_found_in_lookup_table(
+        &mut self,
+        lookup_table_addresses: &[Pubkey],
+        key_meta_filter: impl Fn(&CompiledKeyMeta) -> bool,
+    ) -> Result<(Vec<u8>, Vec<Pubkey>), CompileError> {
+        let mut lookup_table_indexes = Vec::new();
+        let mut drained_keys = Vec::new();
+
+        for search_key in self
+            .key_meta_map
+            .iter()
+            .filter_map(|(key, meta)| key_meta_filter(meta).then_some(key))
+        {
+            if let Some(key_index) = lookup_table_addresses.iter().position(|key| key == search_key) {
+                let lookup_table_index =
+                    u8::try_from(key_index).map_err(|_| CompileError::AddressTableLookupIndexOverflow)?;
+                lookup_table_indexes.push(lookup_table_index);
+                drained_keys.push(*search_key);
+            }
+        }
+
+        for key in &drained_keys {
+            self.key_meta_map.remove_entry(key);
+        }
+
+        Ok((lookup_table_indexes, drained_keys))
+    }
 }
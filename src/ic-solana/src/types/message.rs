@@ -1,12 +1,19 @@
+use std::fmt;
+
 use candid::CandidType;
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{self, SeqAccess, Unexpected, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 
 use crate::{
+    system_instruction,
     types::{
         account::AccountKey,
         blockhash::BlockHash,
-        compiled_keys::CompiledKeys,
-        instruction::{CompiledInstruction, Instruction},
+        compiled_keys::{CompileError, CompiledKeys},
+        instruction::{AccountMeta, CompiledInstruction, Instruction},
         pubkey::Pubkey,
         UiCompiledInstruction, UiInstruction,
     },
@@ -35,10 +42,6 @@ pub struct Message {
     /// succeed.
     #[serde(with = "short_vec")]
     pub instructions: Vec<CompiledInstruction>,
-    // /// List of address table lookups used to load additional accounts
-    // /// for this transaction.
-    // #[serde(with = "short_vec")]
-    // pub address_table_lookups: Vec<MessageAddressTableLookup>,
 }
 
 impl Message {
@@ -62,6 +65,21 @@ impl Message {
         )
     }
 
+    /// Create a message for a durable nonce transaction.
+    ///
+    /// An `AdvanceNonceAccount` instruction is prepended to `instructions`, and the
+    /// recent blockhash must then be set to the value stored in the nonce account.
+    pub fn new_with_nonce(
+        mut instructions: Vec<Instruction>,
+        payer: Option<&Pubkey>,
+        nonce_account_pubkey: &Pubkey,
+        nonce_authority_pubkey: &Pubkey,
+    ) -> Self {
+        let nonce_ix = system_instruction::advance_nonce_account(nonce_account_pubkey, nonce_authority_pubkey);
+        instructions.insert(0, nonce_ix);
+        Self::new(&instructions, payer)
+    }
+
     pub fn new_with_compiled_instructions(
         num_required_signatures: u8,
         num_readonly_signed_accounts: u8,
@@ -98,6 +116,29 @@ impl Message {
         i < self.header.num_required_signatures as usize
     }
 
+    /// Returns true if the account at the specified index was requested to be writable by the
+    /// message header.
+    pub fn is_writable_index(&self, i: usize) -> bool {
+        let num_required_signatures = self.header.num_required_signatures as usize;
+        let num_writable_signed_accounts =
+            num_required_signatures.saturating_sub(self.header.num_readonly_signed_accounts as usize);
+        let num_unsigned_accounts_end = self
+            .account_keys
+            .len()
+            .saturating_sub(self.header.num_readonly_unsigned_accounts as usize);
+        i < num_writable_signed_accounts || (i >= num_required_signatures && i < num_unsigned_accounts_end)
+    }
+
+    /// Returns the keys of all accounts the message locks as writable.
+    pub fn writable_keys(&self) -> Vec<Pubkey> {
+        self.account_keys
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.is_writable_index(*i))
+            .map(|(_, key)| *key)
+            .collect()
+    }
+
     pub fn signer_keys(&self) -> Vec<&Pubkey> {
         let last_key = self
             .account_keys
@@ -105,6 +146,35 @@ impl Message {
             .min(self.header.num_required_signatures as usize);
         self.account_keys[..last_key].iter().collect()
     }
+
+    /// Reconstructs the instructions of the message from the compiled ones.
+    ///
+    /// Returns `None` if an instruction references an account index outside of `account_keys`.
+    pub fn decompile_instructions(&self) -> Option<Vec<Instruction>> {
+        self.instructions
+            .iter()
+            .map(|ix| {
+                let program_id = *self.account_keys.get(ix.program_id_index as usize)?;
+                let accounts = ix
+                    .accounts
+                    .iter()
+                    .map(|&index| {
+                        let index = index as usize;
+                        Some(AccountMeta {
+                            pubkey: *self.account_keys.get(index)?,
+                            is_signer: self.is_signer(index),
+                            is_writable: self.is_writable_index(index),
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Some(Instruction {
+                    program_id,
+                    accounts,
+                    data: ix.data.clone(),
+                })
+            })
+            .collect()
+    }
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq, Clone, Copy, CandidType)]
@@ -127,6 +197,331 @@ pub struct MessageHeader {
     pub num_readonly_unsigned_accounts: u8,
 }
 
+/// Address table lookups describe an on-chain address lookup table to use
+/// for loading more readonly and writable accounts in a single tx.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageAddressTableLookup {
+    /// Address lookup table account key
+    #[serde(rename = "accountKey")]
+    pub account_key: Pubkey,
+    /// List of indexes used to load writable account addresses
+    #[serde(with = "short_vec")]
+    #[serde(rename = "writableIndexes")]
+    pub writable_indexes: Vec<u8>,
+    /// List of indexes used to load readonly account addresses
+    #[serde(with = "short_vec")]
+    #[serde(rename = "readonlyIndexes")]
+    pub readonly_indexes: Vec<u8>,
+}
+
+/// The definition of an address lookup table account, as needed to compile a v0 message.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AddressLookupTableAccount {
+    pub key: Pubkey,
+    pub addresses: Vec<Pubkey>,
+}
+
+/// A v0 message, which extends the legacy format with address table lookups.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageV0 {
+    /// The message header, identifying signed and read-only `account_keys`.
+    /// Header values only describe static `account_keys`, they do not describe
+    /// any additional account keys loaded via address table lookups.
+    pub header: MessageHeader,
+
+    /// List of accounts loaded by this transaction.
+    #[serde(with = "short_vec")]
+    #[serde(rename = "accountKeys")]
+    pub account_keys: Vec<Pubkey>,
+
+    /// The blockhash of a recent block.
+    #[serde(rename = "recentBlockhash")]
+    pub recent_blockhash: BlockHash,
+
+    /// Instructions that invoke a designated program, are executed in sequence,
+    /// and committed in one atomic transaction if all succeed.
+    ///
+    /// Account indexes refer to the static `account_keys` first, followed by the
+    /// writable and then the readonly addresses loaded from lookup tables.
+    #[serde(with = "short_vec")]
+    pub instructions: Vec<CompiledInstruction>,
+
+    /// List of address table lookups used to load additional accounts
+    /// for this transaction.
+    #[serde(with = "short_vec")]
+    #[serde(rename = "addressTableLookups")]
+    pub address_table_lookups: Vec<MessageAddressTableLookup>,
+}
+
+impl MessageV0 {
+    /// Compiles a v0 message, moving every non-signer, non-program account found in
+    /// `address_lookup_table_accounts` out of the static keys and into table lookups.
+    pub fn try_compile(
+        payer: &Pubkey,
+        instructions: &[Instruction],
+        address_lookup_table_accounts: &[AddressLookupTableAccount],
+        recent_blockhash: BlockHash,
+    ) -> Result<Self, CompileError> {
+        let mut compiled_keys = CompiledKeys::compile(instructions, Some(*payer));
+
+        let mut address_table_lookups = Vec::with_capacity(address_lookup_table_accounts.len());
+        let mut loaded_writable_keys = vec![];
+        let mut loaded_readonly_keys = vec![];
+        for lookup_table_account in address_lookup_table_accounts {
+            if let Some((lookup, loaded_addresses)) = compiled_keys.try_extract_table_lookup(lookup_table_account)? {
+                address_table_lookups.push(lookup);
+                loaded_writable_keys.extend(loaded_addresses.writable);
+                loaded_readonly_keys.extend(loaded_addresses.readonly);
+            }
+        }
+
+        let (header, account_keys) = compiled_keys.try_into_message_components()?;
+
+        let all_keys: Vec<Pubkey> = account_keys
+            .iter()
+            .chain(&loaded_writable_keys)
+            .chain(&loaded_readonly_keys)
+            .copied()
+            .collect();
+        let instructions = try_compile_instructions(instructions, &all_keys)?;
+
+        Ok(Self {
+            header,
+            account_keys,
+            recent_blockhash,
+            instructions,
+            address_table_lookups,
+        })
+    }
+
+    /// Serialize this message with a version #0 prefix.
+    pub fn serialize(&self) -> Vec<u8> {
+        bincode::serialize(&(MESSAGE_VERSION_PREFIX, self)).unwrap()
+    }
+
+    pub fn is_signer(&self, i: usize) -> bool {
+        i < self.header.num_required_signatures as usize
+    }
+}
+
+/// Either a legacy message or a v0 message.
+///
+/// # Serialization
+///
+/// If the first bit is set, the remaining 7 bits will be used to determine
+/// which message version is serialized starting from version `0`. If the first
+/// is bit is not set, all bytes are used to encode the legacy `Message`
+/// format.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum VersionedMessage {
+    Legacy(Message),
+    V0(MessageV0),
+}
+
+impl Default for VersionedMessage {
+    fn default() -> Self {
+        Self::Legacy(Message::default())
+    }
+}
+
+impl VersionedMessage {
+    pub fn header(&self) -> &MessageHeader {
+        match self {
+            Self::Legacy(message) => &message.header,
+            Self::V0(message) => &message.header,
+        }
+    }
+
+    /// Account keys stored in the message itself, excluding those loaded via lookup tables.
+    pub fn static_account_keys(&self) -> &[Pubkey] {
+        match self {
+            Self::Legacy(message) => &message.account_keys,
+            Self::V0(message) => &message.account_keys,
+        }
+    }
+
+    pub fn address_table_lookups(&self) -> Option<&[MessageAddressTableLookup]> {
+        match self {
+            Self::Legacy(_) => None,
+            Self::V0(message) => Some(&message.address_table_lookups),
+        }
+    }
+
+    pub fn recent_blockhash(&self) -> &BlockHash {
+        match self {
+            Self::Legacy(message) => &message.recent_blockhash,
+            Self::V0(message) => &message.recent_blockhash,
+        }
+    }
+
+    pub fn set_recent_blockhash(&mut self, recent_blockhash: BlockHash) {
+        match self {
+            Self::Legacy(message) => message.recent_blockhash = recent_blockhash,
+            Self::V0(message) => message.recent_blockhash = recent_blockhash,
+        }
+    }
+
+    pub fn instructions(&self) -> &[CompiledInstruction] {
+        match self {
+            Self::Legacy(message) => &message.instructions,
+            Self::V0(message) => &message.instructions,
+        }
+    }
+
+    pub fn is_signer(&self, i: usize) -> bool {
+        i < self.header().num_required_signatures as usize
+    }
+
+    /// Serialize the message into the bytes that get signed.
+    pub fn serialize(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+}
+
+impl From<Message> for VersionedMessage {
+    fn from(message: Message) -> Self {
+        Self::Legacy(message)
+    }
+}
+
+impl From<MessageV0> for VersionedMessage {
+    fn from(message: MessageV0) -> Self {
+        Self::V0(message)
+    }
+}
+
+impl Serialize for VersionedMessage {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Legacy(message) => {
+                let mut seq = serializer.serialize_tuple(1)?;
+                seq.serialize_element(message)?;
+                seq.end()
+            }
+            Self::V0(message) => {
+                let mut seq = serializer.serialize_tuple(2)?;
+                seq.serialize_element(&MESSAGE_VERSION_PREFIX)?;
+                seq.serialize_element(message)?;
+                seq.end()
+            }
+        }
+    }
+}
+
+enum MessagePrefix {
+    Legacy(u8),
+    Versioned(u8),
+}
+
+impl<'de> Deserialize<'de> for MessagePrefix {
+    fn deserialize<D>(deserializer: D) -> Result<MessagePrefix, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PrefixVisitor;
+
+        impl Visitor<'_> for PrefixVisitor {
+            type Value = MessagePrefix;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("message prefix byte")
+            }
+
+            // Serde's integer visitors bubble up to u64 so check the prefix
+            // with this function instead of visit_u8. This approach is
+            // necessary to work around a bug in serde_json:
+            // https://github.com/serde-rs/json/issues/903
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<MessagePrefix, E> {
+                let byte = u8::try_from(value).map_err(|_| E::invalid_value(Unexpected::Unsigned(value), &self))?;
+                if byte & MESSAGE_VERSION_PREFIX != 0 {
+                    Ok(MessagePrefix::Versioned(byte & !MESSAGE_VERSION_PREFIX))
+                } else {
+                    Ok(MessagePrefix::Legacy(byte))
+                }
+            }
+        }
+
+        deserializer.deserialize_u8(PrefixVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for VersionedMessage {
+    fn deserialize<D>(deserializer: D) -> Result<VersionedMessage, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct MessageVisitor;
+
+        impl<'de> Visitor<'de> for MessageVisitor {
+            type Value = VersionedMessage;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("message bytes")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<VersionedMessage, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let prefix: MessagePrefix = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+
+                match prefix {
+                    MessagePrefix::Legacy(num_required_signatures) => {
+                        // The remaining fields of the legacy Message struct after the first byte.
+                        #[derive(Deserialize)]
+                        struct RemainingLegacyMessage {
+                            num_readonly_signed_accounts: u8,
+                            num_readonly_unsigned_accounts: u8,
+                            #[serde(with = "short_vec")]
+                            account_keys: Vec<Pubkey>,
+                            recent_blockhash: BlockHash,
+                            #[serde(with = "short_vec")]
+                            instructions: Vec<CompiledInstruction>,
+                        }
+
+                        let message: RemainingLegacyMessage =
+                            seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+                        Ok(VersionedMessage::Legacy(Message {
+                            header: MessageHeader {
+                                num_required_signatures,
+                                num_readonly_signed_accounts: message.num_readonly_signed_accounts,
+                                num_readonly_unsigned_accounts: message.num_readonly_unsigned_accounts,
+                            },
+                            account_keys: message.account_keys,
+                            recent_blockhash: message.recent_blockhash,
+                            instructions: message.instructions,
+                        }))
+                    }
+                    MessagePrefix::Versioned(version) => match version {
+                        0 => Ok(VersionedMessage::V0(
+                            seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?,
+                        )),
+                        127 => {
+                            // 0xff is used as the first byte of the off-chain messages
+                            // which corresponds to version 127 of the versioned messages.
+                            // This explicit check is added to prevent the usage of version 127
+                            // in the runtime as a valid transaction.
+                            Err(de::Error::custom("off-chain messages are not accepted"))
+                        }
+                        _ => Err(de::Error::invalid_value(
+                            Unexpected::Unsigned(version as u64),
+                            &"a valid transaction message version",
+                        )),
+                    },
+                }
+            }
+        }
+
+        deserializer.deserialize_tuple(2, MessageVisitor)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", untagged)]
 pub enum UiMessage {
@@ -217,6 +612,30 @@ fn compile_instructions(ixs: &[Instruction], keys: &[Pubkey]) -> Vec<CompiledIns
     ixs.iter().map(|ix| compile_instruction(ix, keys)).collect()
 }
 
+fn try_position(keys: &[Pubkey], key: &Pubkey) -> Result<u8, CompileError> {
+    let index = keys
+        .iter()
+        .position(|k| k == key)
+        .ok_or(CompileError::UnknownInstructionKey(*key))?;
+    u8::try_from(index).map_err(|_| CompileError::AccountIndexOverflow)
+}
+
+fn try_compile_instructions(ixs: &[Instruction], keys: &[Pubkey]) -> Result<Vec<CompiledInstruction>, CompileError> {
+    ixs.iter()
+        .map(|ix| {
+            Ok(CompiledInstruction {
+                program_id_index: try_position(keys, &ix.program_id)?,
+                accounts: ix
+                    .accounts
+                    .iter()
+                    .map(|account_meta| try_position(keys, &account_meta.pubkey))
+                    .collect::<Result<_, _>>()?,
+                data: ix.data.clone(),
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use candid::{Decode, Encode};
@@ -239,4 +658,150 @@ mod tests {
 
         assert_eq!(msg, decoded);
     }
+
+    fn create_sample_v0_message() -> MessageV0 {
+        let payer = Pubkey::from([1; 32]);
+        let program_id = Pubkey::from([2; 32]);
+        let writable = Pubkey::from([3; 32]);
+        let readonly = Pubkey::from([4; 32]);
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::from([5; 32]),
+            addresses: vec![readonly, writable],
+        };
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[7, 8, 9],
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(writable, false),
+                AccountMeta::new_readonly(readonly, false),
+            ],
+        );
+        MessageV0::try_compile(&payer, &[instruction], &[lookup_table], BlockHash([6; 32])).unwrap()
+    }
+
+    #[test]
+    fn test_writable_keys() {
+        let payer = Pubkey::from([1; 32]);
+        let program_id = Pubkey::from([2; 32]);
+        let writable = Pubkey::from([3; 32]);
+        let readonly = Pubkey::from([4; 32]);
+        let readonly_signer = Pubkey::from([5; 32]);
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[],
+            vec![
+                AccountMeta::new(writable, false),
+                AccountMeta::new_readonly(readonly, false),
+                AccountMeta::new_readonly(readonly_signer, true),
+            ],
+        );
+        let message = Message::new(&[instruction], Some(&payer));
+
+        assert_eq!(message.writable_keys(), vec![payer, writable]);
+    }
+
+    #[test]
+    fn test_new_with_nonce() {
+        let payer = Pubkey::from([1; 32]);
+        let nonce_account = Pubkey::from([2; 32]);
+        let to = Pubkey::from([3; 32]);
+        let transfer = system_instruction::transfer(&payer, &to, 42);
+
+        let message = Message::new_with_nonce(vec![transfer.clone()], Some(&payer), &nonce_account, &payer);
+
+        let instructions = message.decompile_instructions().unwrap();
+        let mut advance_nonce = system_instruction::advance_nonce_account(&nonce_account, &payer);
+        // The fee payer is always writable, even where an instruction only reads it.
+        advance_nonce.accounts[2].is_writable = true;
+
+        assert_eq!(message.header.num_required_signatures, 1);
+        assert_eq!(instructions, vec![advance_nonce, transfer]);
+    }
+
+    #[test]
+    fn test_decompile_invalid_instruction() {
+        let mut message = Message::new(&[Instruction::new_with_bytes(Pubkey::from([2; 32]), &[], vec![])], None);
+        message.instructions[0].accounts.push(2);
+        assert_eq!(message.decompile_instructions(), None);
+    }
+
+    #[test]
+    fn test_v0_try_compile() {
+        let message = create_sample_v0_message();
+
+        assert_eq!(
+            message.header,
+            MessageHeader {
+                num_required_signatures: 1,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 1,
+            }
+        );
+        assert_eq!(message.account_keys, vec![Pubkey::from([1; 32]), Pubkey::from([2; 32])]);
+        assert_eq!(
+            message.instructions,
+            vec![CompiledInstruction {
+                program_id_index: 1,
+                accounts: vec![0, 2, 3],
+                data: vec![7, 8, 9],
+            }]
+        );
+        assert_eq!(
+            message.address_table_lookups,
+            vec![MessageAddressTableLookup {
+                account_key: Pubkey::from([5; 32]),
+                writable_indexes: vec![1],
+                readonly_indexes: vec![0],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_v0_try_compile_index_overflow() {
+        let payer = Pubkey::from([1; 32]);
+        let key = Pubkey::from([3; 32]);
+        let mut addresses = vec![Pubkey::default(); 256];
+        addresses.push(key);
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::from([5; 32]),
+            addresses,
+        };
+        let instruction = Instruction::new_with_bytes(Pubkey::from([2; 32]), &[], vec![AccountMeta::new(key, false)]);
+
+        assert_eq!(
+            MessageV0::try_compile(&payer, &[instruction], &[lookup_table], BlockHash::default()),
+            Err(CompileError::AddressTableLookupIndexOverflow)
+        );
+    }
+
+    #[test]
+    fn test_versioned_message_serialize() {
+        let message = VersionedMessage::V0(create_sample_v0_message());
+        let bytes = message.serialize();
+
+        assert_eq!(bytes[0], MESSAGE_VERSION_PREFIX);
+        assert_eq!(bytes, MessageV0::serialize(&create_sample_v0_message()));
+        assert_eq!(bincode::deserialize::<VersionedMessage>(&bytes).unwrap(), message);
+
+        let legacy = Message::new(
+            &[Instruction::new_with_bytes(Pubkey::from([2; 32]), &[1], vec![])],
+            Some(&Pubkey::from([1; 32])),
+        );
+        let bytes = VersionedMessage::Legacy(legacy.clone()).serialize();
+
+        assert_eq!(bytes, legacy.serialize());
+        assert_eq!(
+            bincode::deserialize::<VersionedMessage>(&bytes).unwrap(),
+            VersionedMessage::Legacy(legacy)
+        );
+    }
+
+    #[test]
+    fn test_versioned_message_rejects_offchain_prefix() {
+        let mut bytes = VersionedMessage::V0(create_sample_v0_message()).serialize();
+        bytes[0] = 0xff;
+
+        assert!(bincode::deserialize::<VersionedMessage>(&bytes).is_err());
+    }
 }
@@ -1,8 +1,10 @@
 use std::{fmt, mem, str::FromStr};
 
 use candid::CandidType;
+use curve25519_dalek::edwards::CompressedEdwardsY;
 use ic_crypto_ed25519::PublicKey;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 
 /// Number of bytes in a pubkey
@@ -11,6 +13,14 @@ pub const PUBKEY_BYTES: usize = 32;
 /// Maximum string length of a base58 encoded pubkey
 const MAX_BASE58_LEN: usize = 44;
 
+/// Maximum length of derived `Pubkey` seed
+pub const MAX_SEED_LEN: usize = 32;
+
+/// Maximum number of seeds
+pub const MAX_SEEDS: usize = 16;
+
+const PDA_MARKER: &[u8; 21] = b"ProgramDerivedAddress";
+
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, CandidType)]
 pub struct Pubkey(pub(crate) [u8; PUBKEY_BYTES]);
 
@@ -22,11 +32,29 @@ pub enum ParsePubkeyError {
     Invalid,
 }
 
+#[derive(Error, Debug, Serialize, Clone, PartialEq, Eq)]
+pub enum PubkeyError {
+    /// Length of the seed is too long for address generation
+    #[error("Length of the seed is too long for address generation")]
+    MaxSeedLengthExceeded,
+    #[error("Provided seeds do not result in a valid address")]
+    InvalidSeeds,
+    #[error("Provided owner is not allowed")]
+    IllegalOwner,
+}
+
 impl Pubkey {
     pub fn new(key: [u8; PUBKEY_BYTES]) -> Self {
         Self(key)
     }
 
+    /// Decode a base58 string into a pubkey at compile time.
+    ///
+    /// Panics (or fails to compile in a const context) if the string is not a valid pubkey.
+    pub const fn from_str_const(s: &str) -> Self {
+        Self(bs58::decode(s.as_bytes()).into_array_const_unwrap())
+    }
+
     pub fn to_bytes(self) -> [u8; PUBKEY_BYTES] {
         self.0
     }
@@ -38,6 +66,95 @@ impl Pubkey {
         let pubkey = PublicKey::deserialize_raw(&self.0).expect("invalid public key");
         pubkey.verify_signature(msg, signature).is_ok()
     }
+
+    /// Returns `true` if the pubkey is a valid point on the ed25519 curve.
+    ///
+    /// Program derived addresses are guaranteed to be off the curve, so they
+    /// have no associated private key.
+    pub fn is_on_curve(&self) -> bool {
+        bytes_are_curve_point(&self.0)
+    }
+
+    /// Derive a pubkey from a base pubkey, a string seed and an owner program,
+    /// as done by `SystemInstruction::CreateAccountWithSeed`.
+    pub fn create_with_seed(base: &Pubkey, seed: &str, owner: &Pubkey) -> Result<Pubkey, PubkeyError> {
+        if seed.len() > MAX_SEED_LEN {
+            return Err(PubkeyError::MaxSeedLengthExceeded);
+        }
+
+        if owner.0.ends_with(PDA_MARKER) {
+            return Err(PubkeyError::IllegalOwner);
+        }
+
+        let hash = Sha256::new()
+            .chain_update(base)
+            .chain_update(seed)
+            .chain_update(owner)
+            .finalize();
+        Ok(Pubkey(hash.into()))
+    }
+
+    /// Create a valid program derived address without searching for a bump seed.
+    ///
+    /// Fails with [`PubkeyError::InvalidSeeds`] if the derived address lies on
+    /// the ed25519 curve, in which case a different set of seeds must be used.
+    /// Most callers want [`Pubkey::find_program_address`] instead.
+    pub fn create_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> Result<Pubkey, PubkeyError> {
+        if seeds.len() > MAX_SEEDS {
+            return Err(PubkeyError::MaxSeedLengthExceeded);
+        }
+        if seeds.iter().any(|seed| seed.len() > MAX_SEED_LEN) {
+            return Err(PubkeyError::MaxSeedLengthExceeded);
+        }
+
+        let mut hasher = Sha256::new();
+        for seed in seeds {
+            hasher.update(seed);
+        }
+        hasher.update(program_id);
+        hasher.update(PDA_MARKER);
+        let hash: [u8; PUBKEY_BYTES] = hasher.finalize().into();
+
+        if bytes_are_curve_point(&hash) {
+            return Err(PubkeyError::InvalidSeeds);
+        }
+
+        Ok(Pubkey(hash))
+    }
+
+    /// Find a valid program derived address and its corresponding bump seed.
+    ///
+    /// The bump seed is searched starting from `u8::MAX` downwards and appended
+    /// to `seeds` until an off-curve address is found.
+    ///
+    /// # Panics
+    ///
+    /// Panics in the statistically improbable event that no bump seed yields a
+    /// valid address. Use [`Pubkey::try_find_program_address`] to handle this case.
+    pub fn find_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> (Pubkey, u8) {
+        Self::try_find_program_address(seeds, program_id).expect("Unable to find a viable program address bump seed")
+    }
+
+    /// Find a valid program derived address and its corresponding bump seed,
+    /// returning `None` if no bump seed yields a valid address.
+    pub fn try_find_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> Option<(Pubkey, u8)> {
+        let mut bump_seed = [u8::MAX];
+        for _ in 0..u8::MAX {
+            let mut seeds_with_bump = seeds.to_vec();
+            seeds_with_bump.push(&bump_seed);
+            match Self::create_program_address(&seeds_with_bump, program_id) {
+                Ok(address) => return Some((address, bump_seed[0])),
+                Err(PubkeyError::InvalidSeeds) => (),
+                Err(_) => break,
+            }
+            bump_seed[0] -= 1;
+        }
+        None
+    }
+}
+
+fn bytes_are_curve_point(bytes: &[u8; PUBKEY_BYTES]) -> bool {
+    CompressedEdwardsY(*bytes).decompress().is_some()
 }
 
 impl FromStr for Pubkey {
@@ -105,3 +222,91 @@ impl AsRef<[u8]> for Pubkey {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pubkey(s: &str) -> Pubkey {
+        Pubkey::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_create_program_address() {
+        let program_id = pubkey("BPFLoaderUpgradeab1e11111111111111111111111");
+        let exceeded_seed = &[127; MAX_SEED_LEN + 1];
+
+        assert_eq!(
+            Pubkey::create_program_address(&[exceeded_seed], &program_id),
+            Err(PubkeyError::MaxSeedLengthExceeded)
+        );
+        assert_eq!(
+            Pubkey::create_program_address(&[b"".as_ref(); MAX_SEEDS + 1], &program_id),
+            Err(PubkeyError::MaxSeedLengthExceeded)
+        );
+        assert_eq!(
+            Pubkey::create_program_address(&[b"", &[1]], &program_id),
+            Ok(pubkey("BwqrghZA2htAcqq8dzP1WDAhTXYTYWj7CHxF5j7TDBAe"))
+        );
+        assert_eq!(
+            Pubkey::create_program_address(&["☉".as_ref(), &[0]], &program_id),
+            Ok(pubkey("13yWmRpaTR4r5nAktwLqMpRNr28tnVUZw26rTvPSSB19"))
+        );
+        assert_eq!(
+            Pubkey::create_program_address(&[b"Talking", b"Squirrels"], &program_id),
+            Ok(pubkey("2fnQrngrQT4SeLcdToJAD96phoEjNL2man2kfRLCASVk"))
+        );
+    }
+
+    #[test]
+    fn test_find_program_address() {
+        let wallet = pubkey("9ri4mUToddwCc6jg1GTL5sobkkFxjUzjZ6CZ6L91LzAR");
+        let token_program_id = pubkey("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+        let associated_token_program_id = pubkey("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+        let usdc_mint = pubkey("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+
+        let (address, bump_seed) = Pubkey::find_program_address(
+            &[wallet.as_ref(), token_program_id.as_ref(), usdc_mint.as_ref()],
+            &associated_token_program_id,
+        );
+
+        assert_eq!(address, pubkey("FdhE4EQMqKar7eWbQTZRJKTqBmqKwuq6W1M9rJMW5316"));
+        assert_eq!(bump_seed, 255);
+        assert!(!address.is_on_curve());
+        assert!(wallet.is_on_curve());
+        assert_eq!(
+            Pubkey::create_program_address(
+                &[
+                    wallet.as_ref(),
+                    token_program_id.as_ref(),
+                    usdc_mint.as_ref(),
+                    &[bump_seed]
+                ],
+                &associated_token_program_id,
+            ),
+            Ok(address)
+        );
+    }
+
+    #[test]
+    fn test_create_with_seed() {
+        let base = pubkey("9ri4mUToddwCc6jg1GTL5sobkkFxjUzjZ6CZ6L91LzAR");
+        let owner = pubkey("11111111111111111111111111111111");
+
+        assert_eq!(
+            Pubkey::create_with_seed(&base, "stake:0", &owner),
+            Ok(pubkey("9x5MikXFngdoZH211rrUHEbQdZTgBpksG43fuoqksJDW"))
+        );
+        assert_eq!(
+            Pubkey::create_with_seed(&base, &"x".repeat(MAX_SEED_LEN + 1), &owner),
+            Err(PubkeyError::MaxSeedLengthExceeded)
+        );
+
+        let mut illegal_owner = [0; PUBKEY_BYTES];
+        illegal_owner[PUBKEY_BYTES - PDA_MARKER.len()..].copy_from_slice(PDA_MARKER);
+        assert_eq!(
+            Pubkey::create_with_seed(&base, "", &Pubkey::from(illegal_owner)),
+            Err(PubkeyError::IllegalOwner)
+        );
+    }
+}
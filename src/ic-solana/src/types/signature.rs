@@ -1,5 +1,6 @@
 use std::{fmt, str::FromStr};
 
+use ic_crypto_ed25519::PublicKey;
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
 
@@ -23,19 +24,11 @@ impl Signature {
         std::str::from_utf8(&self.0).unwrap_or_default()
     }
 
-    // pub(self) fn verify_verbose(
-    //     &self,
-    //     pubkey_bytes: &[u8],
-    //     message_bytes: &[u8],
-    // ) -> Result<(), ed25519_dalek::SignatureError> {
-    //     let publickey = ed25519_dalek::PublicKey::from_bytes(pubkey_bytes)?;
-    //     let signature = self.0.as_slice().try_into()?;
-    //     publickey.verify_strict(message_bytes, &signature)
-    // }
-    //
-    // pub fn verify(&self, pubkey_bytes: &[u8], message_bytes: &[u8]) -> bool {
-    //     self.verify_verbose(pubkey_bytes, message_bytes).is_ok()
-    // }
+    /// Verifies the ed25519 signature of `message_bytes` against the given public key.
+    pub fn verify(&self, pubkey_bytes: &[u8], message_bytes: &[u8]) -> bool {
+        PublicKey::deserialize_raw(pubkey_bytes)
+            .is_ok_and(|pubkey| pubkey.verify_signature(message_bytes, &self.0).is_ok())
+    }
 }
 
 impl AsRef<[u8]> for Signature {
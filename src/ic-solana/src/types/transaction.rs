@@ -11,7 +11,7 @@ use super::UiInnerInstructions;
 use crate::{
     types::{
         account::{AccountKey, UiTokenAmount},
-        message::{Message, UiMessage},
+        message::{Message, UiMessage, VersionedMessage},
         pubkey::Pubkey,
         reward::Rewards,
         signature::Signature,
@@ -89,6 +89,24 @@ impl Transaction {
         self.signatures[position] = signature;
     }
 
+    /// Verifies that every required signer has a valid signature of the message.
+    pub fn verify(&self) -> TransactionResult<()> {
+        if self.verify_with_results().iter().all(|valid| *valid) {
+            Ok(())
+        } else {
+            Err(TransactionError::SignatureFailure)
+        }
+    }
+
+    /// Verifies the signature of each required signer of the message.
+    ///
+    /// Returns one result per signer, in the order of [`Message::signer_keys`]. Missing or default
+    /// signatures are reported as invalid, so a partially signed transaction can be checked as
+    /// well.
+    pub fn verify_with_results(&self) -> Vec<bool> {
+        verify_signatures(&self.signatures, &self.message.signer_keys(), &self.message_data())
+    }
+
     fn key_index(&self, instruction_index: usize, accounts_index: usize) -> Option<usize> {
         self.message
             .instructions
@@ -119,6 +137,119 @@ impl FromStr for Transaction {
     }
 }
 
+/// An atomic transaction that supports both legacy and versioned (v0) messages.
+#[derive(Debug, PartialEq, Default, Eq, Clone, Serialize, Deserialize)]
+pub struct VersionedTransaction {
+    /// List of signatures
+    #[serde(with = "short_vec")]
+    pub signatures: Vec<Signature>,
+    /// Message to sign.
+    pub message: VersionedMessage,
+}
+
+impl VersionedTransaction {
+    pub fn new_unsigned(message: VersionedMessage) -> Self {
+        Self {
+            signatures: vec![Signature::default(); message.header().num_required_signatures as usize],
+            message,
+        }
+    }
+
+    /// Return the serialized message data to sign.
+    pub fn message_data(&self) -> Vec<u8> {
+        self.message.serialize()
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.signatures
+            .iter()
+            .all(|signature| *signature != Signature::default())
+    }
+
+    pub fn sign(&mut self, position: usize, signer: &[u8]) {
+        let pk = PrivateKey::deserialize_raw(signer).unwrap();
+        let signature = Signature(pk.sign_message(&self.message_data()));
+        self.add_signature(position, signature)
+    }
+
+    pub fn add_signature(&mut self, position: usize, signature: Signature) {
+        self.signatures[position] = signature;
+    }
+
+    /// Verifies that every required signer has a valid signature of the message.
+    pub fn verify(&self) -> TransactionResult<()> {
+        if self.verify_with_results().iter().all(|valid| *valid) {
+            Ok(())
+        } else {
+            Err(TransactionError::SignatureFailure)
+        }
+    }
+
+    /// Verifies the signature of each required signer of the message, see
+    /// [`Transaction::verify_with_results`].
+    pub fn verify_with_results(&self) -> Vec<bool> {
+        let account_keys = self.message.static_account_keys();
+        let num_signers = account_keys
+            .len()
+            .min(self.message.header().num_required_signatures as usize);
+        let signer_keys: Vec<&Pubkey> = account_keys[..num_signers].iter().collect();
+        verify_signatures(&self.signatures, &signer_keys, &self.message_data())
+    }
+
+    /// Returns a legacy transaction if the transaction message is legacy.
+    pub fn into_legacy_transaction(self) -> Option<Transaction> {
+        match self.message {
+            VersionedMessage::Legacy(message) => Some(Transaction {
+                signatures: self.signatures,
+                message,
+            }),
+            VersionedMessage::V0(_) => None,
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("Transaction serialization failed")
+    }
+}
+
+fn verify_signatures(signatures: &[Signature], signer_keys: &[&Pubkey], message_data: &[u8]) -> Vec<bool> {
+    signer_keys
+        .iter()
+        .enumerate()
+        .map(|(i, pubkey)| {
+            signatures
+                .get(i)
+                .is_some_and(|signature| signature.verify(pubkey.as_ref(), message_data))
+        })
+        .collect()
+}
+
+impl From<Transaction> for VersionedTransaction {
+    fn from(transaction: Transaction) -> Self {
+        Self {
+            signatures: transaction.signatures,
+            message: VersionedMessage::Legacy(transaction.message),
+        }
+    }
+}
+
+impl Display for VersionedTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bs58::encode(self.serialize()).into_string())
+    }
+}
+
+impl FromStr for VersionedTransaction {
+    type Err = bincode::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = bs58::decode(s)
+            .into_vec()
+            .map_err(|_| bincode::Error::custom("Transaction deserialization failed"))?;
+        bincode::deserialize(&bytes)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, CandidType)]
 #[serde(rename_all = "camelCase")]
 pub enum Legacy {
@@ -394,6 +525,48 @@ mod tests {
         assert_eq!(tx, deser);
     }
 
+    #[test]
+    fn test_versioned_transaction_serialize() {
+        let tx = create_sample_transaction();
+        let versioned = VersionedTransaction::from(tx.clone());
+        assert_eq!(versioned.serialize(), tx.serialize());
+        assert_eq!(versioned.message_data(), tx.message_data());
+
+        let deser: VersionedTransaction = deserialize(&versioned.serialize()).unwrap();
+        assert_eq!(deser, versioned);
+        assert_eq!(deser.into_legacy_transaction(), Some(tx));
+    }
+
+    #[test]
+    fn test_transaction_verify() {
+        let tx = create_sample_transaction();
+        assert_eq!(tx.verify_with_results(), vec![true]);
+        assert_eq!(tx.verify(), Ok(()));
+        assert_eq!(VersionedTransaction::from(tx.clone()).verify(), Ok(()));
+
+        let mut tampered = tx.clone();
+        tampered.message.recent_blockhash = BlockHash([1; 32]);
+        assert_eq!(tampered.verify_with_results(), vec![false]);
+        assert_eq!(tampered.verify(), Err(TransactionError::SignatureFailure));
+
+        let unsigned = Transaction::new_unsigned(tx.message.clone());
+        assert_eq!(unsigned.verify_with_results(), vec![false]);
+        assert_eq!(
+            VersionedTransaction::from(unsigned).verify(),
+            Err(TransactionError::SignatureFailure)
+        );
+
+        // Partially signed by the first of two required signers.
+        let co_signer = PrivateKey::deserialize_raw(&[7; 32]).unwrap();
+        let mut message = tx.message;
+        message.header.num_required_signatures = 2;
+        message.header.num_readonly_signed_accounts = 1;
+        message.account_keys[1] = Pubkey::from(co_signer.public_key().serialize_raw());
+        let mut partial = Transaction::new_unsigned(message);
+        partial.sign(1, &co_signer.serialize_raw());
+        assert_eq!(partial.verify_with_results(), vec![false, true]);
+    }
+
     #[test]
     fn test_transaction_json_serialize() {
         let legacy_version_json = r#"{ "blockTime": 1726125580, "meta": { "computeUnitsConsumed": 150, "err": null, "fee": 5000, "innerInstructions": [], "loadedAddresses": { "readonly": [], "writable": [] }, "logMessages": [ "Program 11111111111111111111111111111111 invoke [1]", "Program 11111111111111111111111111111111 success" ], "postBalances": [ 19999934990, 12999985016, 1 ], "postTokenBalances": [], "preBalances": [ 19999939991, 12999985015, 1 ], "preTokenBalances": [], "rewards": [], "status": { "Ok": null } }, "slot": 325448256, "transaction": { "message": { "accountKeys": [ "EabqyjABpFwUGhw2t2HVPGavjD1uqGm6ciMPhBRrdTxh", "9ri4mUToddwCc6jg1GTL5sobkkFxjUzjZ6CZ6L91LzAR", "11111111111111111111111111111111" ], "header": { "numReadonlySignedAccounts": 0, "numReadonlyUnsignedAccounts": 1, "numRequiredSignatures": 1 }, "instructions": [ { "accounts": [ 0, 1 ], "data": "3Bxs412MvVNQj175", "programIdIndex": 2, "stackHeight": null } ], "recentBlockhash": "EMcudiFZWenakUVWtipQuu4ymZZcJmbsQFWUoPX4j35w" }, "signatures": [ "3t6afQP9Zp8FV49moN42x1QZCQYKHtpXYCakdpt1zxBHWQLbUHrhLCZmPxiNTN4A5HE6VJwnA2h5AjvZovqhcnGH" ] }, "version": "legacy" }"#;